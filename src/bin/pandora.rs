@@ -0,0 +1,600 @@
+/*!
+`pandora`: a command-line client for the Pandora JSON API.
+
+Covers the calls most useful for inspecting and adjusting an account: partner
+and user login, stations, playlists, search, feedback, station seeds,
+bookmarks and usage info.  Results are printed either as a table, or as the
+json result returned by the API.
+
+Credentials are taken from `--username`/`--password` or the
+`PANDORA_USERNAME`/`PANDORA_PASSWORD` environment variables.  A password given
+with `--password` is visible to other users in the process list, so
+`PANDORA_PASSWORD` is the safer choice.  The partner
+credentials are those of one of the built-in devices, or are loaded from a
+toml or json file with `--partner-config <file>`.  With `--session <file>`,
+`pandora login` saves the session tokens to the file, and later commands reuse
+them instead of logging in again.  When the saved tokens have expired, commands
+log in again if credentials are given, and otherwise ask for `pandora login` to
+be run again.  The auth tokens are left out of json output.
+
+```text
+pandora login --session ~/.pandora-session
+pandora --session ~/.pandora-session stations
+pandora --session ~/.pandora-session --output json playlist <station token>
+```
+*/
+// SPDX-License-Identifier: MIT
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use pandora_api::errors::Error;
+use pandora_api::json::blocking::{
+    auth, block_on, bookmark, music, station, user, BlockingPandoraJsonApiRequest,
+};
+use pandora_api::json::managed::ManagedSession;
+use pandora_api::json::middleware::Middleware;
+use pandora_api::json::redact::redact_secrets;
+use pandora_api::json::station::GetStation;
+use pandora_api::json::{PandoraResponse, PandoraSession, Partner, SessionTokens, ToEndpoint};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "pandora",
+    version,
+    about = "Command-line client for the Pandora JSON API"
+)]
+struct Cli {
+    /// Account username (email address)
+    #[arg(short, long, env = "PANDORA_USERNAME", global = true)]
+    username: Option<String>,
+    /// Account password.  Other users can see it in the process list when
+    /// it's given as an argument, so setting PANDORA_PASSWORD is preferred
+    #[arg(
+        short,
+        long,
+        env = "PANDORA_PASSWORD",
+        hide_env_values = true,
+        global = true
+    )]
+    password: Option<String>,
+    /// The partner (device) credentials to authenticate the client with
+    #[arg(long, value_enum, default_value_t = PartnerDevice::Android, global = true)]
+    partner: PartnerDevice,
+    /// Load the partner credentials from a toml or json file instead
+    #[arg(long, env = "PANDORA_PARTNER_CONFIG", global = true)]
+    partner_config: Option<PathBuf>,
+    /// Override the API endpoint url, e.g. `https://tuner.pandora.com/services/json`
+    #[arg(long, env = "PANDORA_ENDPOINT", global = true)]
+    endpoint: Option<String>,
+    /// File to save session tokens to on login, and to load them from for
+    /// other commands
+    #[arg(long, env = "PANDORA_SESSION", global = true)]
+    session: Option<PathBuf>,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    output: OutputFormat,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PartnerDevice {
+    Android,
+    Ios,
+    Palm,
+    WindowsMobile,
+    DesktopAir,
+    VistaWidget,
+}
+
+impl From<PartnerDevice> for Partner {
+    fn from(device: PartnerDevice) -> Self {
+        match device {
+            PartnerDevice::Android => Partner::new_android(),
+            PartnerDevice::Ios => Partner::new_ios(),
+            PartnerDevice::Palm => Partner::new_palm(),
+            PartnerDevice::WindowsMobile => Partner::new_windows_mobile(),
+            PartnerDevice::DesktopAir => Partner::new_desktop_air(),
+            PartnerDevice::VistaWidget => Partner::new_vista_widget(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable columns
+    Table,
+    /// The json result returned by the API
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Authenticate the partner (device) only
+    PartnerLogin,
+    /// Log in as the user, saving the session tokens if --session is given
+    Login,
+    /// List the user's stations
+    Stations,
+    /// Fetch the next playlist for a station
+    Playlist {
+        /// Station token
+        station: String,
+    },
+    /// Search for songs, artists and genre stations
+    Search {
+        /// Search text
+        text: String,
+    },
+    /// Rate a track on a station favorably
+    ThumbsUp {
+        /// Station token
+        station: String,
+        /// Track token, from the station playlist
+        track: String,
+    },
+    /// Rate a track on a station unfavorably
+    ThumbsDown {
+        /// Station token
+        station: String,
+        /// Track token, from the station playlist
+        track: String,
+    },
+    /// Manage the seeds of a station
+    #[command(subcommand)]
+    Seeds(SeedsCommand),
+    /// Manage the user's bookmarks
+    #[command(subcommand)]
+    Bookmarks(BookmarksCommand),
+    /// Show the account listening usage
+    Usage,
+}
+
+#[derive(Debug, Subcommand)]
+enum SeedsCommand {
+    /// List the seeds of a station
+    List {
+        /// Station token
+        station: String,
+    },
+    /// Add a seed to a station
+    Add {
+        /// Station token
+        station: String,
+        /// Music token, from search results
+        music: String,
+    },
+    /// Remove a seed from its station
+    Remove {
+        /// Seed id, from the seed list
+        seed: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum BookmarksCommand {
+    /// List the user's bookmarks
+    List,
+    /// Bookmark the artist of a track
+    AddArtist {
+        /// Track token, from a station playlist
+        track: String,
+    },
+    /// Bookmark a track
+    AddSong {
+        /// Track token, from a station playlist
+        track: String,
+    },
+    /// Delete an artist bookmark
+    DeleteArtist {
+        /// Bookmark token, from the bookmark list
+        bookmark: String,
+    },
+    /// Delete a song bookmark
+    DeleteSong {
+        /// Bookmark token, from the bookmark list
+        bookmark: String,
+    },
+}
+
+/// Keeps the untyped result of the most recent successful API call, for json
+/// output, with credentials and auth tokens redacted.
+#[derive(Debug, Default)]
+struct LastResult(Mutex<Option<serde_json::Value>>);
+
+impl LastResult {
+    fn take(&self) -> Option<serde_json::Value> {
+        self.0.lock().expect("Result lock poisoned").take()
+    }
+}
+
+impl Middleware for LastResult {
+    fn on_response(
+        &self,
+        _method: &str,
+        _call_id: u64,
+        response: &mut PandoraResponse<serde_json::Value>,
+    ) {
+        if let Some(result) = &response.result {
+            let mut result = result.clone();
+            redact_secrets(&mut result);
+            *self.0.lock().expect("Result lock poisoned") = Some(result);
+        }
+    }
+}
+
+/// Rows of text to be printed as aligned columns.
+struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn row<I: IntoIterator<Item = S>, S: ToString>(&mut self, row: I) -> &mut Self {
+        self.rows
+            .push(row.into_iter().map(|cell| cell.to_string()).collect());
+        self
+    }
+
+    fn print(&self) {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_uppercase()).collect();
+        for row in std::iter::once(&headers).chain(&self.rows) {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            println!("{}", line.join("  ").trim_end());
+        }
+    }
+}
+
+/// A table of field names and values, for responses describing a single item.
+fn fields<const N: usize>(fields: [(&str, String); N]) -> Table {
+    let mut table = Table::new(&["field", "value"]);
+    for (name, value) in fields {
+        table.row([name.to_string(), value]);
+    }
+    table
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("pandora: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let partner = match &cli.partner_config {
+        Some(path) => Partner::from_file(path)?,
+        None => Partner::from(cli.partner),
+    };
+    let last_result = Arc::new(LastResult::default());
+
+    let table = match &cli.command {
+        Command::PartnerLogin => {
+            let session = new_session(cli, &partner, &last_result);
+            let response = partner.blocking_login(&session)?;
+            fields([
+                ("partner id", response.partner_id),
+                (
+                    "station skip limit",
+                    response.station_skip_limit.to_string(),
+                ),
+                ("station skip unit", response.station_skip_unit),
+            ])
+        }
+        Command::Login => {
+            let session = new_session(cli, &partner, &last_result);
+            partner.blocking_login(&session)?;
+            let (username, password) = credentials(cli)?;
+            let response = auth::user_login(&session, username, password)?;
+            if let Some(path) = &cli.session {
+                save_session(&session, path)?;
+            }
+            fields([
+                ("username", response.username),
+                ("user id", response.user_id),
+                ("can listen", response.can_listen.to_string()),
+                (
+                    "max stations allowed",
+                    response.max_stations_allowed.to_string(),
+                ),
+            ])
+        }
+        command => {
+            let (session, restored) = user_session(cli, &partner, &last_result)?;
+            match user_command(&session, command) {
+                Err(e) if restored && e.requires_reauth() => {
+                    let session = login_again(cli, session, partner)?;
+                    user_command(&session, command)?
+                }
+                res => res?,
+            }
+        }
+    };
+
+    match cli.output {
+        OutputFormat::Table => table.print(),
+        OutputFormat::Json => {
+            let result = last_result.take().unwrap_or(serde_json::Value::Null);
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+    }
+    Ok(())
+}
+
+/// Run a command that requires a logged-in user.
+fn user_command(session: &PandoraSession, command: &Command) -> Result<Table, Error> {
+    let table = match command {
+        Command::PartnerLogin | Command::Login => unreachable!("Login handled by caller"),
+        Command::Stations => {
+            let response = user::get_station_list(session)?;
+            let mut table = Table::new(&["station token", "name", "quickmix"]);
+            for station in response.stations {
+                table.row([
+                    station.station_token,
+                    station.station_name,
+                    station.is_quick_mix.to_string(),
+                ]);
+            }
+            table
+        }
+        Command::Playlist { station } => {
+            let response = station::get_playlist(session, station)?;
+            let mut table = Table::new(&["track token", "song", "artist", "album", "rating"]);
+            for track in response.items.iter().filter_map(|entry| entry.get_track()) {
+                table.row([
+                    track.track_token,
+                    track.song_name,
+                    track.artist_name,
+                    track.album_name,
+                    track.song_rating.to_string(),
+                ]);
+            }
+            table
+        }
+        Command::Search { text } => {
+            let response = music::search(session, text)?;
+            let mut table = Table::new(&["music token", "type", "name", "score"]);
+            for song in response.songs {
+                let name = format!("{} - {}", song.song_name, song.artist_name);
+                table.row([
+                    song.music_token,
+                    "song".to_string(),
+                    name,
+                    song.score.to_string(),
+                ]);
+            }
+            for artist in response.artists {
+                table.row([
+                    artist.music_token,
+                    "artist".to_string(),
+                    artist.artist_name,
+                    artist.score.to_string(),
+                ]);
+            }
+            for genre in response.genre_stations {
+                table.row([
+                    genre.music_token,
+                    "genre".to_string(),
+                    genre.station_name,
+                    genre.score.to_string(),
+                ]);
+            }
+            table
+        }
+        Command::ThumbsUp { station, track } | Command::ThumbsDown { station, track } => {
+            let is_positive = matches!(command, Command::ThumbsUp { .. });
+            let response = station::add_feedback(session, station, track, is_positive)?;
+            fields([
+                ("feedback id", response.feedback_id),
+                ("song", response.song_name),
+                ("artist", response.artist_name),
+                ("positive", response.is_positive.to_string()),
+                ("total thumbs up", response.total_thumbs_up.to_string()),
+                ("total thumbs down", response.total_thumbs_down.to_string()),
+            ])
+        }
+        Command::Seeds(SeedsCommand::List { station }) => {
+            let response = GetStation::from(station)
+                .include_extended_attributes(true)
+                .blocking_response(session)?;
+            let mut table = Table::new(&["seed id", "music token", "type", "name"]);
+            if let Some(seeds) = response.music {
+                for song in seeds.songs {
+                    let name = format!("{} - {}", song.song_name, song.artist_name);
+                    table.row([song.seed_id, song.music_token, "song".to_string(), name]);
+                }
+                for artist in seeds.artists {
+                    table.row([
+                        artist.seed_id,
+                        artist.music_token,
+                        "artist".to_string(),
+                        artist.artist_name,
+                    ]);
+                }
+                for genre in seeds.genres {
+                    table.row([
+                        genre.seed_id,
+                        genre.music_token,
+                        "genre".to_string(),
+                        genre.genre_name,
+                    ]);
+                }
+            }
+            table
+        }
+        Command::Seeds(SeedsCommand::Add { station, music }) => {
+            let response = station::add_music(session, station, music)?;
+            fields([
+                ("seed id", response.seed_id),
+                ("music token", response.music_token),
+                ("artist", response.artist_name),
+            ])
+        }
+        Command::Seeds(SeedsCommand::Remove { seed }) => {
+            station::delete_music(session, seed)?;
+            fields([("deleted seed", seed.clone())])
+        }
+        Command::Bookmarks(BookmarksCommand::List) => {
+            let response = user::get_bookmarks(session)?;
+            let mut table = Table::new(&["bookmark token", "type", "name"]);
+            for artist in response.artists {
+                table.row([
+                    artist.bookmark_token,
+                    "artist".to_string(),
+                    artist.artist_name,
+                ]);
+            }
+            for song in response.songs {
+                let name = format!("{} - {}", song.song_name, song.artist_name);
+                table.row([song.bookmark_token, "song".to_string(), name]);
+            }
+            table
+        }
+        Command::Bookmarks(BookmarksCommand::AddArtist { track }) => {
+            let response = bookmark::add_artist_bookmark(session, track)?;
+            fields([
+                ("bookmark token", response.bookmark_token),
+                ("artist", response.artist_name),
+            ])
+        }
+        Command::Bookmarks(BookmarksCommand::AddSong { track }) => {
+            let response = bookmark::add_song_bookmark(session, track)?;
+            fields([
+                ("bookmark token", response.bookmark_token),
+                ("song", response.song_name),
+                ("artist", response.artist_name),
+            ])
+        }
+        Command::Bookmarks(BookmarksCommand::DeleteArtist { bookmark }) => {
+            bookmark::delete_artist_bookmark(session, bookmark)?;
+            fields([("deleted bookmark", bookmark.clone())])
+        }
+        Command::Bookmarks(BookmarksCommand::DeleteSong { bookmark }) => {
+            bookmark::delete_song_bookmark(session, bookmark)?;
+            fields([("deleted bookmark", bookmark.clone())])
+        }
+        Command::Usage => {
+            let response = user::get_usage_info(session)?;
+            fields([
+                (
+                    "account monthly listening",
+                    response.account_monthly_listening.to_string(),
+                ),
+                (
+                    "device monthly listening",
+                    response.device_monthly_listening.to_string(),
+                ),
+                ("monthly cap hours", response.monthly_cap_hours.to_string()),
+                ("monthly payer", response.is_monthly_payer.to_string()),
+                ("capped", response.is_capped.to_string()),
+            ])
+        }
+    };
+    Ok(table)
+}
+
+/// Create a session without any tokens, using the endpoint override, if any.
+/// An overridden endpoint doesn't fail over to the partner's usual hosts.
+fn new_session(cli: &Cli, partner: &Partner, last_result: &Arc<LastResult>) -> PandoraSession {
+    let mut session = match &cli.endpoint {
+        Some(endpoint) => PandoraSession::new(None, partner, endpoint),
+        None => partner.init_session(),
+    };
+    session.add_middleware(last_result.clone());
+    session
+}
+
+/// Create a session with user tokens, either restored from the session file
+/// or from a fresh login, along with whether it was restored.
+fn user_session(
+    cli: &Cli,
+    partner: &Partner,
+    last_result: &Arc<LastResult>,
+) -> Result<(PandoraSession, bool), Box<dyn std::error::Error>> {
+    if let Some(path) = cli.session.as_ref().filter(|path| path.exists()) {
+        let tokens: SessionTokens = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let endpoint = cli
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| partner.to_endpoint());
+        let mut session = PandoraSession::restore(None, tokens, &endpoint);
+        session.add_middleware(last_result.clone());
+        return Ok((session, true));
+    }
+
+    let session = new_session(cli, partner, last_result);
+    partner.blocking_login(&session)?;
+    let (username, password) = credentials(cli)?;
+    auth::user_login(&session, username, password)?;
+    Ok((session, false))
+}
+
+/// Log a restored session whose tokens have expired back in, saving the new
+/// tokens to the session file.  Without credentials, the user is asked to log
+/// in again instead.
+fn login_again(
+    cli: &Cli,
+    session: PandoraSession,
+    partner: Partner,
+) -> Result<PandoraSession, Box<dyn std::error::Error>> {
+    let Ok((username, password)) = credentials(cli) else {
+        return Err("the saved session has expired, run `pandora login` to log in again".into());
+    };
+    let managed = ManagedSession::with_session(session, partner, username, password);
+    block_on(managed.login())?;
+    if let Some(path) = &cli.session {
+        save_session(managed.session(), path)?;
+    }
+    Ok(managed.into_session())
+}
+
+/// The username and password for user login.
+fn credentials(cli: &Cli) -> Result<(&str, &str), String> {
+    match (&cli.username, &cli.password) {
+        (Some(username), Some(password)) => Ok((username, password)),
+        _ => Err("a username and password are required, either with \
+                  --username/--password or PANDORA_USERNAME/PANDORA_PASSWORD"
+            .to_string()),
+    }
+}
+
+/// Save the session tokens, which include the auth tokens, readable only by
+/// the current user where supported.  The mode given when opening only
+/// applies to newly created files, so the permissions of an existing file are
+/// restricted as well, before the tokens are written to it.
+fn save_session(session: &PandoraSession, path: &Path) -> Result<(), Error> {
+    let json = serde_json::to_string(&*session.session_tokens())?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    std::io::Write::write_all(&mut file, json.as_bytes())?;
+    Ok(())
+}
@@ -20,6 +20,42 @@ pub enum Error {
     /// Wraps reqwest errors
     #[error("HTTP I/O error: {0}")]
     HttpIoError(#[from] reqwest::Error),
+    /// The HTTP request completed, but with an unsuccessful status code and
+    /// no parseable API response
+    #[error("HTTP request failed with status {0}")]
+    HttpStatusError(u16),
+    /// Error reported by the transport used to submit requests
+    #[error("Transport error: {0}")]
+    TransportError(String),
+    /// A call was rejected locally because it exceeded the configured rate
+    /// limit for the method; it may be retried after the given duration
+    #[error("Rate limit exceeded for {0}, retry in {1:?}")]
+    RateLimitExceeded(String, std::time::Duration),
+    /// A request took longer than the timeout set for its method
+    #[error("Request for {0} timed out after {1:?}")]
+    RequestTimeout(String, std::time::Duration),
+    /// Wraps I/O errors from reading or writing files
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+    /// Wraps toml deserialization errors
+    #[cfg(feature = "toml")]
+    #[error("TOML deserialization error: {0}")]
+    TomlDeserializationError(#[from] toml::de::Error),
+    /// A partner definition has a missing or invalid value
+    #[error("Invalid partner definition: {0}")]
+    InvalidPartner(String),
+    /// Encryption or decryption failed, because of an unsupported key or
+    /// malformed input
+    #[error("Crypto error: {0}")]
+    CryptoError(String),
+    /// The syncTime returned by partner login couldn't be decrypted to a
+    /// server time
+    #[error("Invalid sync time: {0}")]
+    InvalidSyncTime(String),
+    /// A timestamp couldn't be converted to a date and time, because a value
+    /// was out of range
+    #[error("Invalid timestamp: {0}")]
+    InvalidTimestamp(String),
     /// Wraps url parse errors
     #[error("HTTP URL parse error: {0}")]
     HttpUrlParseError(#[from] url::ParseError),
@@ -37,3 +73,62 @@ pub enum Error {
     #[error("Invalid/unsupported gender value: {0}")]
     InvalidUserGender(String),
 }
+
+impl Error {
+    /// The API error, if this error was returned by the Pandora JSON API.
+    pub fn json_error(&self) -> Option<&JsonError> {
+        match self {
+            Error::PandoraJsonRequestError(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Whether the error is likely to be transient, so that the same request
+    /// may succeed if it's submitted again after a short delay: transient API
+    /// errors, HTTP 5xx statuses, connection failures and timeouts.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::PandoraJsonRequestError(e) => e.kind().is_retryable(),
+            Error::HttpStatusError(status) => *status >= 500,
+            Error::HttpIoError(e) => e.is_connect() || e.is_timeout(),
+            Error::RequestTimeout(_, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the session tokens are no longer accepted, so that partner and
+    /// user login have to be repeated before further requests can succeed.
+    pub fn requires_reauth(&self) -> bool {
+        self.json_error()
+            .is_some_and(|e| e.kind().requires_reauth())
+    }
+
+    /// Whether the error is due to the state of the account, partner or
+    /// device, rather than to the request itself.
+    pub fn is_account_problem(&self) -> bool {
+        self.json_error()
+            .is_some_and(|e| e.kind().is_account_problem())
+    }
+
+    /// Whether the request was refused because too many requests were made
+    /// recently, either by the API, with an HTTP 429 status, or by the
+    /// session rate limits.
+    pub fn is_rate_limit(&self) -> bool {
+        match self {
+            Error::PandoraJsonRequestError(e) => e.kind().is_rate_limit(),
+            Error::HttpStatusError(status) => *status == 429,
+            Error::RateLimitExceeded(_, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the error is due to a missing or invalid value supplied with
+    /// the request.
+    pub fn is_user_input_error(&self) -> bool {
+        match self {
+            Error::PandoraJsonRequestError(e) => e.kind().is_user_input_error(),
+            Error::InvalidAudioFormat(_) | Error::InvalidUserGender(_) => true,
+            _ => false,
+        }
+    }
+}
@@ -107,7 +107,7 @@ impl<TS: ToString> From<&TS> for GetAdMetadata {
 ///    }
 ///}
 ///```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetAdMetadataResponse {
     /// Unknown field.
@@ -124,7 +124,7 @@ pub struct GetAdMetadataResponse {
 }
 
 /// A description of an audio stream.  Where to get it, and how to decode it.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AudioStream {
     /// The bitrate for this audio stream.
@@ -139,7 +139,7 @@ pub struct AudioStream {
 
 /// Convenience function to do a basic getAdMetadata call.
 pub async fn get_ad_metadata(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     ad_token: &str,
 ) -> Result<GetAdMetadataResponse, Error> {
     GetAdMetadata::from(&ad_token)
@@ -188,7 +188,7 @@ impl<TS: ToString> From<&TS> for RegisterAd {
 }
 
 /// There's no known response to data to this request.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RegisterAdResponse {
     /// The fields of the registerAd response are unknown.
@@ -198,7 +198,7 @@ pub struct RegisterAdResponse {
 
 /// Convenience function to do a basic registerAd call.
 pub async fn register_ad(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_id: &str,
     ad_tracking_tokens: Vec<String>,
 ) -> Result<RegisterAdResponse, Error> {
@@ -218,16 +218,16 @@ mod tests {
     #[tokio::test]
     async fn ad_test() {
         let partner = Partner::default();
-        let mut session = session_login(&partner)
+        let session = session_login(&partner)
             .await
             .expect("Failed initializing login session");
 
-        for station in get_station_list(&mut session)
+        for station in get_station_list(&session)
             .await
             .expect("Failed getting station list to look up a track to bookmark")
             .stations
         {
-            for ad in get_playlist(&mut session, &station.station_token)
+            for ad in get_playlist(&session, &station.station_token)
                 .await
                 .expect("Failed completing request for playlist")
                 .items
@@ -238,13 +238,13 @@ mod tests {
                     .return_ad_tracking_tokens(true)
                     .support_audio_ads(true)
                     .include_banner_ad(true)
-                    .response(&mut session)
+                    .response(&session)
                     .await
                     .expect("Failed getting ad metadata");
 
                 if !ad_metadata.ad_tracking_tokens.is_empty() {
                     let _ad_registered = register_ad(
-                        &mut session,
+                        &session,
                         &station.station_id,
                         ad_metadata.ad_tracking_tokens,
                     )
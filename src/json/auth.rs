@@ -10,6 +10,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::errors::Error;
 use crate::json::{PandoraJsonApiRequest, PandoraSession, ToPartnerTokens, ToUserTokens};
+use crate::secret::Secret;
 
 /// **Unsupported!**
 /// Undocumented method
@@ -47,7 +48,7 @@ pub struct PartnerLogin {
     pub username: String,
     /// The partner login password (not the account-holder's username)
     /// used to authenticate the application with the Pandora service.
-    pub password: String,
+    pub password: Secret,
     /// The partner device model name.
     pub device_model: String,
     /// The Pandora JSON API version
@@ -68,7 +69,7 @@ impl PartnerLogin {
     ) -> Self {
         PartnerLogin {
             username: username.to_string(),
-            password: password.to_string(),
+            password: Secret::from(password),
             device_model: device_model.to_string(),
             version: version.unwrap_or_else(|| String::from("5")),
             optional: HashMap::new(),
@@ -99,13 +100,22 @@ impl PartnerLogin {
 
     /// This is a wrapper around the `response` method from the
     /// PandoraJsonApiRequest trait that automatically merges the partner tokens
-    /// from the response back into the session.
+    /// from the response back into the session, along with any API endpoints
+    /// among the returned urls, if endpoint discovery is enabled on the
+    /// session.
     pub async fn merge_response(
         &self,
-        session: &mut PandoraSession,
+        session: &PandoraSession,
     ) -> Result<PartnerLoginResponse, Error> {
         let response = self.response(session).await?;
-        session.update_partner_tokens(&response);
+        {
+            let mut tokens = session.session_tokens_mut();
+            tokens.update_partner_tokens(&response)?;
+            tokens.set_partner_login(self.clone());
+        }
+        if let Some(urls) = &response.urls {
+            session.endpoints().discover(urls);
+        }
         Ok(response)
     }
 }
@@ -139,13 +149,13 @@ impl PartnerLogin {
 /// ```
 /// | Code | Description |
 /// | 1002 | INVALID_PARTNER_LOGIN. Invalid partner credentials. |
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PartnerLoginResponse {
     /// The partner id that should be used for this session
     pub partner_id: String,
     /// The partner auth token that should be used for this session
-    pub partner_auth_token: String,
+    pub partner_auth_token: Secret,
     /// The server sync time that should be used for this session
     /// Note that this field is encrypted, and must be decrypted before use
     pub sync_time: String,
@@ -153,7 +163,10 @@ pub struct PartnerLoginResponse {
     pub station_skip_unit: String,
     /// Unknown field
     pub station_skip_limit: u32,
-    /// Unknown field
+    /// Urls of related services, returned when the request sets
+    /// `includeUrls`.  Any JSON API endpoints among them are used by
+    /// `PartnerLogin::merge_response()` as session endpoints, if endpoint
+    /// discovery is enabled on the session.
     pub urls: Option<HashMap<String, String>>,
     /// Optional response fields
     #[serde(flatten)]
@@ -166,7 +179,7 @@ impl ToPartnerTokens for PartnerLoginResponse {
     }
 
     fn to_partner_token(&self) -> Option<String> {
-        Some(self.partner_auth_token.clone())
+        Some(self.partner_auth_token.expose_secret().to_string())
     }
 
     fn to_sync_time(&self) -> Option<String> {
@@ -176,7 +189,7 @@ impl ToPartnerTokens for PartnerLoginResponse {
 
 /// Convenience function to do a basic partnerLogin call.
 pub async fn partner_login(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     username: &str,
     password: &str,
     device_model: &str,
@@ -249,7 +262,7 @@ pub struct UserLogin {
     /// The account username to login with.
     pub username: String,
     /// The account password to login with.
-    pub password: String,
+    pub password: Secret,
     /// Optional parameters on the call
     #[serde(flatten)]
     pub optional: HashMap<String, serde_json::value::Value>,
@@ -262,7 +275,7 @@ impl UserLogin {
             // This field should always have the value `user`.
             login_type: "user".to_string(),
             username: username.to_string(),
-            password: password.to_string(),
+            password: Secret::from(password),
             optional: HashMap::new(),
         }
     }
@@ -422,7 +435,7 @@ impl UserLogin {
     /// the response back into the session.
     pub async fn merge_response(
         &self,
-        session: &mut PandoraSession,
+        session: &PandoraSession,
     ) -> Result<UserLoginResponse, Error> {
         let response = self.response(session).await?;
         session.update_user_tokens(&response);
@@ -458,13 +471,13 @@ impl UserLogin {
 /// ```
 /// | Code | Description |
 /// | 1002 | Wrong user credentials. |
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserLoginResponse {
     /// The user id that should be used for this session
     pub user_id: String,
     /// The user auth token that should be used for this session
-    pub user_auth_token: String,
+    pub user_auth_token: Secret,
     /// Unknown field.
     pub station_creation_ad_url: String,
     /// Unknown field.
@@ -498,13 +511,13 @@ impl ToUserTokens for UserLoginResponse {
     }
 
     fn to_user_token(&self) -> Option<String> {
-        Some(self.user_auth_token.clone())
+        Some(self.user_auth_token.expose_secret().to_string())
     }
 }
 
 /// Convenience function to perform a basic user login.
 pub async fn user_login(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     username: &str,
     password: &str,
 ) -> Result<UserLoginResponse, Error> {
@@ -541,6 +554,8 @@ pub async fn user_login(
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::json::mock::MockPandora;
     use crate::json::{tests::session_login, Partner};
 
     // Tests both PartnerLogin and UserLogin
@@ -558,5 +573,30 @@ mod tests {
             .await
             .expect("Failed initializing login session");
         log::debug!("Session tokens: {:?}", session);
+
+        // Credentials, keys and tokens are redacted
+        let tokens = session.session_tokens().clone();
+        let secrets = [
+            Some(&partner.password),
+            Some(&tokens.encrypt_key),
+            Some(&tokens.decrypt_key),
+            tokens.partner_token.as_ref(),
+            tokens.user_token.as_ref(),
+        ];
+        let logged = [
+            format!("{:?}", session),
+            format!("{:?}", partner),
+            format!("{:?}", tokens),
+        ];
+        for secret in secrets {
+            let secret = secret.expect("Missing session secret").expose_secret();
+            assert!(logged.iter().all(|logged| !logged.contains(secret)));
+        }
+        let request = UserLogin::new(MockPandora::DEFAULT_USERNAME, "hunter2");
+        assert!(!format!("{:?}", request).contains("hunter2"));
+        assert_eq!(
+            request.get_json().expect("Failed serializing request")["password"],
+            "hunter2"
+        );
     }
 }
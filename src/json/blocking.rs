@@ -0,0 +1,588 @@
+/*!
+Blocking (synchronous) versions of the API calls, for use outside of an async
+runtime.
+
+Requests are run to completion on a tokio runtime shared by every thread
+that makes blocking calls, so the functions in this module must not be called
+from within an async context.  The connections pooled by a session belong to
+the runtime that opened them, and the shared runtime keeps them usable from
+any thread and by every clone of the session.  For the same reason, a session
+should be used either with blocking calls or from within a single async
+runtime, not both.
+
+Each submodule mirrors the convenience functions of the module with the same
+name under [`crate::json`], and [`BlockingPandoraJsonApiRequest`] provides a
+blocking counterpart to `PandoraJsonApiRequest::response()` for every request
+type.
+
+``` no_run
+# fn example() -> Result<(), pandora_api::errors::Error> {
+use pandora_api::json::blocking::{auth::user_login, user::get_station_list};
+use pandora_api::json::Partner;
+
+let partner = Partner::default();
+let session = partner.init_session();
+partner.blocking_login(&session)?;
+user_login(&session, "user@example.com", "example")?;
+let stations = get_station_list(&session)?;
+# Ok(())
+# }
+```
+*/
+// SPDX-License-Identifier: MIT
+
+use std::future::Future;
+use std::sync::OnceLock;
+
+use crate::errors::Error;
+use crate::json::auth::PartnerLoginResponse;
+use crate::json::{PandoraJsonApiRequest, PandoraSession, Partner};
+
+static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+/// Run `future` to completion on the runtime shared by blocking calls, which
+/// is started by the first call.  The connections opened by the future, and
+/// any tasks it spawns, are driven by the runtime's worker thread, so they
+/// outlive the calling thread.
+///
+/// Panics if called from within an async runtime.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME
+        .get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("pandora-api-blocking")
+                .enable_all()
+                .build()
+                .expect("Failed to start runtime for blocking requests")
+        })
+        .block_on(future)
+}
+
+/// Blocking counterpart to the submission methods of PandoraJsonApiRequest.
+pub trait BlockingPandoraJsonApiRequest: PandoraJsonApiRequest {
+    /// Build the request, submit it, and extract the response content from
+    /// the body json, and deserialize it into the Self::Response type,
+    /// blocking until the request completes.
+    fn blocking_response(
+        &self,
+        session: &PandoraSession,
+    ) -> std::result::Result<Self::Response, Self::Error>;
+}
+
+impl<T: PandoraJsonApiRequest + Sync> BlockingPandoraJsonApiRequest for T {
+    fn blocking_response(
+        &self,
+        session: &PandoraSession,
+    ) -> std::result::Result<Self::Response, Self::Error> {
+        block_on(self.response(session))
+    }
+}
+
+impl Partner {
+    /// Blocking version of [`Partner::login()`].
+    pub fn blocking_login(&self, session: &PandoraSession) -> Result<PartnerLoginResponse, Error> {
+        block_on(self.login(session))
+    }
+}
+
+/// Blocking versions of the convenience functions in [`crate::json::ad`].
+pub mod ad {
+    use super::block_on;
+    use crate::errors::Error;
+    use crate::json::ad::{GetAdMetadataResponse, RegisterAdResponse};
+    use crate::json::PandoraSession;
+
+    /// Blocking version of [`crate::json::ad::get_ad_metadata()`].
+    pub fn get_ad_metadata(
+        session: &PandoraSession,
+        ad_token: &str,
+    ) -> Result<GetAdMetadataResponse, Error> {
+        block_on(crate::json::ad::get_ad_metadata(session, ad_token))
+    }
+
+    /// Blocking version of [`crate::json::ad::register_ad()`].
+    pub fn register_ad(
+        session: &PandoraSession,
+        station_id: &str,
+        ad_tracking_tokens: Vec<String>,
+    ) -> Result<RegisterAdResponse, Error> {
+        block_on(crate::json::ad::register_ad(
+            session,
+            station_id,
+            ad_tracking_tokens,
+        ))
+    }
+}
+
+/// Blocking versions of the convenience functions in [`crate::json::auth`].
+pub mod auth {
+    use super::block_on;
+    use crate::errors::Error;
+    use crate::json::auth::{PartnerLoginResponse, UserLoginResponse};
+    use crate::json::PandoraSession;
+
+    /// Blocking version of [`crate::json::auth::partner_login()`].
+    pub fn partner_login(
+        session: &PandoraSession,
+        username: &str,
+        password: &str,
+        device_model: &str,
+    ) -> Result<PartnerLoginResponse, Error> {
+        block_on(crate::json::auth::partner_login(
+            session,
+            username,
+            password,
+            device_model,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::auth::user_login()`].
+    pub fn user_login(
+        session: &PandoraSession,
+        username: &str,
+        password: &str,
+    ) -> Result<UserLoginResponse, Error> {
+        block_on(crate::json::auth::user_login(session, username, password))
+    }
+}
+
+/// Blocking versions of the convenience functions in [`crate::json::bookmark`].
+pub mod bookmark {
+    use super::block_on;
+    use crate::errors::Error;
+    use crate::json::bookmark::{
+        AddArtistBookmarkResponse, AddSongBookmarkResponse, DeleteArtistBookmarkResponse,
+        DeleteSongBookmarkResponse,
+    };
+    use crate::json::PandoraSession;
+
+    /// Blocking version of [`crate::json::bookmark::add_artist_bookmark()`].
+    pub fn add_artist_bookmark(
+        session: &PandoraSession,
+        track_token: &str,
+    ) -> Result<AddArtistBookmarkResponse, Error> {
+        block_on(crate::json::bookmark::add_artist_bookmark(
+            session,
+            track_token,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::bookmark::add_song_bookmark()`].
+    pub fn add_song_bookmark(
+        session: &PandoraSession,
+        track_token: &str,
+    ) -> Result<AddSongBookmarkResponse, Error> {
+        block_on(crate::json::bookmark::add_song_bookmark(
+            session,
+            track_token,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::bookmark::delete_artist_bookmark()`].
+    pub fn delete_artist_bookmark(
+        session: &PandoraSession,
+        bookmark_token: &str,
+    ) -> Result<DeleteArtistBookmarkResponse, Error> {
+        block_on(crate::json::bookmark::delete_artist_bookmark(
+            session,
+            bookmark_token,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::bookmark::delete_song_bookmark()`].
+    pub fn delete_song_bookmark(
+        session: &PandoraSession,
+        bookmark_token: &str,
+    ) -> Result<DeleteSongBookmarkResponse, Error> {
+        block_on(crate::json::bookmark::delete_song_bookmark(
+            session,
+            bookmark_token,
+        ))
+    }
+}
+
+/// Blocking versions of the convenience functions in [`crate::json::music`].
+pub mod music {
+    use super::block_on;
+    use crate::errors::Error;
+    use crate::json::music::{GetTrackResponse, SearchResponse};
+    use crate::json::PandoraSession;
+
+    /// Blocking version of [`crate::json::music::get_track()`].
+    pub fn get_track(
+        session: &PandoraSession,
+        track_token: &str,
+    ) -> Result<GetTrackResponse, Error> {
+        block_on(crate::json::music::get_track(session, track_token))
+    }
+
+    /// Blocking version of [`crate::json::music::search()`].
+    pub fn search(session: &PandoraSession, search_text: &str) -> Result<SearchResponse, Error> {
+        block_on(crate::json::music::search(session, search_text))
+    }
+}
+
+/// Blocking versions of the convenience functions in [`crate::json::station`].
+pub mod station {
+    use super::block_on;
+    use crate::errors::Error;
+    use crate::json::station::{
+        AddFeedbackResponse, AddMusicResponse, CreateStationResponse, DeleteFeedbackResponse,
+        DeleteMusicResponse, DeleteStationResponse, GetGenreStationsChecksumResponse,
+        GetGenreStationsResponse, GetPlaylistResponse, GetStationResponse, RenameStationResponse,
+        ShareStationResponse, TransformSharedStationResponse,
+    };
+    use crate::json::PandoraSession;
+
+    /// Blocking version of [`crate::json::station::add_feedback()`].
+    pub fn add_feedback(
+        session: &PandoraSession,
+        station_token: &str,
+        track_token: &str,
+        is_positive: bool,
+    ) -> Result<AddFeedbackResponse, Error> {
+        block_on(crate::json::station::add_feedback(
+            session,
+            station_token,
+            track_token,
+            is_positive,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::station::add_music()`].
+    pub fn add_music(
+        session: &PandoraSession,
+        station_token: &str,
+        music_token: &str,
+    ) -> Result<AddMusicResponse, Error> {
+        block_on(crate::json::station::add_music(
+            session,
+            station_token,
+            music_token,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::station::create_station_from_track_song()`].
+    pub fn create_station_from_track_song(
+        session: &PandoraSession,
+        track_token: &str,
+    ) -> Result<CreateStationResponse, Error> {
+        block_on(crate::json::station::create_station_from_track_song(
+            session,
+            track_token,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::station::create_station_from_artist()`].
+    pub fn create_station_from_artist(
+        session: &PandoraSession,
+        track_token: &str,
+    ) -> Result<CreateStationResponse, Error> {
+        block_on(crate::json::station::create_station_from_artist(
+            session,
+            track_token,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::station::create_station_from_music_token()`].
+    pub fn create_station_from_music_token(
+        session: &PandoraSession,
+        music_token: &str,
+    ) -> Result<CreateStationResponse, Error> {
+        block_on(crate::json::station::create_station_from_music_token(
+            session,
+            music_token,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::station::delete_feedback()`].
+    pub fn delete_feedback(
+        session: &PandoraSession,
+        feedback_id: &str,
+    ) -> Result<DeleteFeedbackResponse, Error> {
+        block_on(crate::json::station::delete_feedback(session, feedback_id))
+    }
+
+    /// Blocking version of [`crate::json::station::delete_music()`].
+    pub fn delete_music(
+        session: &PandoraSession,
+        seed_id: &str,
+    ) -> Result<DeleteMusicResponse, Error> {
+        block_on(crate::json::station::delete_music(session, seed_id))
+    }
+
+    /// Blocking version of [`crate::json::station::delete_station()`].
+    pub fn delete_station(
+        session: &PandoraSession,
+        station_token: &str,
+    ) -> Result<DeleteStationResponse, Error> {
+        block_on(crate::json::station::delete_station(session, station_token))
+    }
+
+    /// Blocking version of [`crate::json::station::get_genre_stations_checksum()`].
+    pub fn get_genre_stations_checksum(
+        session: &PandoraSession,
+    ) -> Result<GetGenreStationsChecksumResponse, Error> {
+        block_on(crate::json::station::get_genre_stations_checksum(session))
+    }
+
+    /// Blocking version of [`crate::json::station::get_genre_stations()`].
+    pub fn get_genre_stations(session: &PandoraSession) -> Result<GetGenreStationsResponse, Error> {
+        block_on(crate::json::station::get_genre_stations(session))
+    }
+
+    /// Blocking version of [`crate::json::station::get_playlist()`].
+    pub fn get_playlist(
+        session: &PandoraSession,
+        station_token: &str,
+    ) -> Result<GetPlaylistResponse, Error> {
+        block_on(crate::json::station::get_playlist(session, station_token))
+    }
+
+    /// Blocking version of [`crate::json::station::get_station()`].
+    pub fn get_station(
+        session: &PandoraSession,
+        station_token: &str,
+    ) -> Result<GetStationResponse, Error> {
+        block_on(crate::json::station::get_station(session, station_token))
+    }
+
+    /// Blocking version of [`crate::json::station::rename_station()`].
+    pub fn rename_station(
+        session: &PandoraSession,
+        station_token: &str,
+        station_name: &str,
+    ) -> Result<RenameStationResponse, Error> {
+        block_on(crate::json::station::rename_station(
+            session,
+            station_token,
+            station_name,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::station::share_station()`].
+    pub fn share_station(
+        session: &PandoraSession,
+        station_id: &str,
+        station_token: &str,
+        emails: Vec<String>,
+    ) -> Result<ShareStationResponse, Error> {
+        block_on(crate::json::station::share_station(
+            session,
+            station_id,
+            station_token,
+            emails,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::station::transform_shared_station()`].
+    pub fn transform_shared_station(
+        session: &PandoraSession,
+        station_token: &str,
+    ) -> Result<TransformSharedStationResponse, Error> {
+        block_on(crate::json::station::transform_shared_station(
+            session,
+            station_token,
+        ))
+    }
+}
+
+/// Blocking versions of the convenience functions in [`crate::json::test`].
+pub mod test {
+    use super::block_on;
+    use crate::errors::Error;
+    use crate::json::test::CheckLicensingResponse;
+    use crate::json::PandoraSession;
+
+    /// Blocking version of [`crate::json::test::check_licensing()`].
+    pub fn check_licensing(session: &PandoraSession) -> Result<CheckLicensingResponse, Error> {
+        block_on(crate::json::test::check_licensing(session))
+    }
+}
+
+/// Blocking versions of the convenience functions in [`crate::json::track`].
+pub mod track {
+    use super::block_on;
+    use crate::errors::Error;
+    use crate::json::track::ExplainTrackResponse;
+    use crate::json::PandoraSession;
+
+    /// Blocking version of [`crate::json::track::explain_track()`].
+    pub fn explain_track(
+        session: &PandoraSession,
+        track_token: &str,
+    ) -> Result<ExplainTrackResponse, Error> {
+        block_on(crate::json::track::explain_track(session, track_token))
+    }
+}
+
+/// Blocking versions of the convenience functions in [`crate::json::user`].
+pub mod user {
+    use super::block_on;
+    use crate::errors::Error;
+    use crate::json::user::{
+        CanSubscribeResponse, ChangeSettingsResponse, CreateUserResponse, EmailPasswordResponse,
+        GetBookmarksResponse, GetSettingsResponse, GetStationListResponse, GetUsageInfoResponse,
+        UserGender, ValidateUsernameResponse,
+    };
+    use crate::json::PandoraSession;
+
+    /// Blocking version of [`crate::json::user::can_subscribe()`].
+    pub fn can_subscribe(session: &PandoraSession) -> Result<CanSubscribeResponse, Error> {
+        block_on(crate::json::user::can_subscribe(session))
+    }
+
+    /// Blocking version of [`crate::json::user::change_settings()`].
+    pub fn change_settings(
+        session: &PandoraSession,
+        username: &str,
+        password: &str,
+    ) -> Result<ChangeSettingsResponse, Error> {
+        block_on(crate::json::user::change_settings(
+            session, username, password,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::user::create_user()`].
+    pub fn create_user(
+        session: &PandoraSession,
+        username: &str,
+        password: &str,
+        gender: UserGender,
+        birth_year: u32,
+        zip_code: &str,
+        country_code: &str,
+    ) -> Result<CreateUserResponse, Error> {
+        block_on(crate::json::user::create_user(
+            session,
+            username,
+            password,
+            gender,
+            birth_year,
+            zip_code,
+            country_code,
+        ))
+    }
+
+    /// Blocking version of [`crate::json::user::email_password()`].
+    pub fn email_password(
+        session: &PandoraSession,
+        username: &str,
+    ) -> Result<EmailPasswordResponse, Error> {
+        block_on(crate::json::user::email_password(session, username))
+    }
+
+    /// Blocking version of [`crate::json::user::get_bookmarks()`].
+    pub fn get_bookmarks(session: &PandoraSession) -> Result<GetBookmarksResponse, Error> {
+        block_on(crate::json::user::get_bookmarks(session))
+    }
+
+    /// Blocking version of [`crate::json::user::get_settings()`].
+    pub fn get_settings(session: &PandoraSession) -> Result<GetSettingsResponse, Error> {
+        block_on(crate::json::user::get_settings(session))
+    }
+
+    /// Blocking version of [`crate::json::user::get_station_list()`].
+    pub fn get_station_list(session: &PandoraSession) -> Result<GetStationListResponse, Error> {
+        block_on(crate::json::user::get_station_list(session))
+    }
+
+    /// Blocking version of [`crate::json::user::get_usage_info()`].
+    pub fn get_usage_info(session: &PandoraSession) -> Result<GetUsageInfoResponse, Error> {
+        block_on(crate::json::user::get_usage_info(session))
+    }
+
+    /// Blocking version of [`crate::json::user::validate_username()`].
+    pub fn validate_username(
+        session: &PandoraSession,
+        username: &str,
+    ) -> Result<ValidateUsernameResponse, Error> {
+        block_on(crate::json::user::validate_username(session, username))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::json::mock::{MockPandora, MockServer};
+    use crate::json::station::GetStation;
+
+    #[test]
+    fn blocking_session_test() {
+        let partner = Partner::default();
+        let session = partner.init_session_with_transport(Arc::new(MockPandora::new()));
+
+        let licensing =
+            test::check_licensing(&session).expect("Failed checking licensing restrictions");
+        assert!(licensing.is_allowed);
+
+        partner
+            .blocking_login(&session)
+            .expect("Failed blocking partner login");
+        auth::user_login(
+            &session,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+        .expect("Failed blocking user login");
+
+        let station = user::get_station_list(&session)
+            .expect("Failed getting station list")
+            .stations
+            .into_iter()
+            .find(|s| !s.is_quick_mix)
+            .expect("Station list request returned no results");
+        let station = GetStation::from(&station.station_token)
+            .include_extended_attributes(true)
+            .blocking_response(&session)
+            .expect("Failed getting station attributes");
+
+        let track = station::get_playlist(&session, &station.station_token)
+            .expect("Failed getting playlist")
+            .items
+            .iter()
+            .find_map(|p| p.get_track())
+            .expect("Playlist contained no tracks");
+        let feedback =
+            station::add_feedback(&session, &station.station_token, &track.track_token, true)
+                .expect("Failed adding feedback to track");
+        station::delete_feedback(&session, &feedback.feedback_id)
+            .expect("Failed deleting feedback from track");
+    }
+
+    #[test]
+    fn shared_session_threads_test() {
+        let server = block_on(MockServer::start(Arc::new(MockPandora::new())))
+            .expect("Failed starting mock Pandora server");
+        let partner = Partner::default();
+        let session = PandoraSession::new(None, &partner, &server.endpoint());
+
+        // The connection opened by the first thread is pooled by the client
+        // shared with the clone used by the second thread, after the first
+        // thread exits
+        let login_session = session.clone();
+        std::thread::spawn(move || {
+            partner
+                .blocking_login(&login_session)
+                .expect("Failed blocking partner login");
+            auth::user_login(
+                &login_session,
+                MockPandora::DEFAULT_USERNAME,
+                MockPandora::DEFAULT_PASSWORD,
+            )
+            .expect("Failed blocking user login");
+        })
+        .join()
+        .expect("Login thread panicked");
+
+        let station_session = session.clone();
+        let stations = std::thread::spawn(move || user::get_station_list(&station_session))
+            .join()
+            .expect("Station list thread panicked")
+            .expect("Failed getting station list from another thread");
+        assert!(!stations.stations.is_empty());
+    }
+}
@@ -58,7 +58,7 @@ impl<TS: ToString> From<&TS> for AddArtistBookmark {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AddArtistBookmarkResponse {
     /// The name of the artist being bookmarked.
@@ -76,7 +76,7 @@ pub struct AddArtistBookmarkResponse {
 
 /// Convenience function to do a basic addArtistBookmark call.
 pub async fn add_artist_bookmark(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     track_token: &str,
 ) -> Result<AddArtistBookmarkResponse, Error> {
     AddArtistBookmark::from(&track_token)
@@ -136,7 +136,7 @@ impl<TS: ToString> From<&TS> for AddSongBookmark {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AddSongBookmarkResponse {
     /// The audio gain for the bookmarked track. (?)
@@ -162,7 +162,7 @@ pub struct AddSongBookmarkResponse {
 
 /// Convenience function to do a basic addSongBookmark call.
 pub async fn add_song_bookmark(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     track_token: &str,
 ) -> Result<AddSongBookmarkResponse, Error> {
     AddSongBookmark::from(&track_token).response(session).await
@@ -196,13 +196,13 @@ impl<TS: ToString> From<&TS> for DeleteArtistBookmark {
 }
 
 /// This method does not return data.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteArtistBookmarkResponse {}
 
 /// Convenience function to do a basic deleteArtistBookmark call.
 pub async fn delete_artist_bookmark(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     bookmark_token: &str,
 ) -> Result<DeleteArtistBookmarkResponse, Error> {
     DeleteArtistBookmark::from(&bookmark_token)
@@ -238,13 +238,13 @@ impl<TS: ToString> From<&TS> for DeleteSongBookmark {
 }
 
 /// This method does not return data.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteSongBookmarkResponse {}
 
 /// Convenience function to do a basic deleteSongBookmark call.
 pub async fn delete_song_bookmark(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     bookmark_token: &str,
 ) -> Result<DeleteSongBookmarkResponse, Error> {
     DeleteSongBookmark::from(&bookmark_token)
@@ -273,13 +273,13 @@ mod tests {
             .await
             .expect("Failed initializing login session");
 
-        if let Some(station) = get_station_list(&mut session)
+        if let Some(station) = get_station_list(&session)
             .await
             .expect("Failed getting station list to look up a track to bookmark")
             .stations
             .first()
         {
-            if let Some(track) = get_playlist(&mut session, &station.station_token)
+            if let Some(track) = get_playlist(&session, &station.station_token)
                 .await
                 .expect("Failed completing request for playlist")
                 .items
@@ -287,14 +287,14 @@ mod tests {
                 .flat_map(|p| p.get_track())
                 .next()
             {
-                let artist_bookmark = add_artist_bookmark(&mut session, &track.track_token)
+                let artist_bookmark = add_artist_bookmark(&session, &track.track_token)
                     .await
                     .expect("Failed submitting artist bookmark creation request");
                 log::debug!("Bookmark creation result: {:?}", artist_bookmark);
 
             // TODO: song bookmark deletion doesn't seem to work yet, so lets
             // not go creating more with each run.
-            //let song_bookmark = add_song_bookmark(&mut session, &track.track_token)
+            //let song_bookmark = add_song_bookmark(&session, &track.track_token)
             //    .expect("Failed submitting song bookmark creation request");
             //log::debug!("Bookmark creation result: {:?}", song_bookmark);
             } else {
@@ -304,20 +304,20 @@ mod tests {
             panic!("Station list request returned no results, so no bookmarkable content.");
         }
 
-        let user_bookmarks = get_bookmarks(&mut session)
+        let user_bookmarks = get_bookmarks(&session)
             .await
             .expect("Failed submitting request for user bookmarks");
 
         for artist_bookmark in user_bookmarks.artists {
             let _del_bookmark =
-                delete_artist_bookmark(&mut session, &artist_bookmark.bookmark_token)
+                delete_artist_bookmark(&session, &artist_bookmark.bookmark_token)
                     .await
                     .expect("Failed submitting artist bookmark deletion request");
         }
 
         // TODO: song bookmark deletion is borken, can't figure out why
         for song_bookmark in user_bookmarks.songs {
-            let _del_bookmark = delete_artist_bookmark(&mut session, &song_bookmark.bookmark_token)
+            let _del_bookmark = delete_artist_bookmark(&session, &song_bookmark.bookmark_token)
                 .expect("Failed submitting song bookmark deletion request");
         }
     }
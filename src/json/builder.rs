@@ -0,0 +1,319 @@
+/*!
+A builder for [`PandoraSession`] instances, covering the HTTP client options
+most often needed with the Pandora service, without having to configure a
+`reqwest::Client` directly.
+
+``` no_run
+# fn example() -> Result<(), pandora_api::errors::Error> {
+use std::time::Duration;
+
+use pandora_api::json::builder::PandoraSessionBuilder;
+use pandora_api::json::Partner;
+
+let session = PandoraSessionBuilder::new(&Partner::default())
+    .proxy("http://proxy.example.com:3128")
+    .connect_timeout(Duration::from_secs(5))
+    .read_timeout(Duration::from_secs(30))
+    .method_timeout("station.getPlaylist", Duration::from_secs(10))
+    .build()?;
+# Ok(())
+# }
+```
+*/
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::errors::Error;
+use crate::json::{PandoraSession, Partner, ToEncryptionTokens, ToEndpoint};
+use crate::secret::Secret;
+
+/// Builds a PandoraSession, along with the HTTP client it submits requests
+/// with.
+#[derive(Debug, Clone)]
+pub struct PandoraSessionBuilder {
+    encrypt_key: Secret,
+    decrypt_key: Secret,
+    endpoint: String,
+    fallback_endpoints: Vec<String>,
+    endpoint_discovery: bool,
+    proxies: Vec<String>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    root_certificates: Vec<Vec<u8>>,
+    user_agent: Option<String>,
+    method_timeouts: HashMap<String, Duration>,
+}
+
+impl PandoraSessionBuilder {
+    /// Create a new PandoraSessionBuilder for a session using the tokens and
+    /// endpoint of `partner`, failing over to the other well-known endpoint
+    /// host like `Partner::init_session()`, and identifying itself with
+    /// `Partner::user_agent()`.
+    pub fn new(partner: &Partner) -> Self {
+        let builder = Self::with_tokens(partner, partner).user_agent(&partner.user_agent());
+        match partner.alternate_endpoint_host() {
+            Some(host) => builder.fallback_endpoint(format!("https://{}/services/json", host)),
+            None => builder,
+        }
+    }
+
+    /// Create a new PandoraSessionBuilder for a session using the given
+    /// encryption tokens and endpoint, and the default user agent.
+    pub fn with_tokens<T: ToEncryptionTokens, E: ToEndpoint>(
+        to_encryption_tokens: &T,
+        to_endpoint: &E,
+    ) -> Self {
+        Self {
+            encrypt_key: to_encryption_tokens.to_encrypt_key().clone(),
+            decrypt_key: to_encryption_tokens.to_decrypt_key().clone(),
+            endpoint: to_endpoint.to_endpoint(),
+            fallback_endpoints: Vec::new(),
+            endpoint_discovery: false,
+            proxies: Vec::new(),
+            connect_timeout: None,
+            read_timeout: None,
+            timeout: None,
+            root_certificates: Vec::new(),
+            user_agent: None,
+            method_timeouts: HashMap::new(),
+        }
+    }
+
+    /// Add an endpoint to fail over to when the endpoints ahead of it can't
+    /// be reached. (Chaining call)
+    pub fn fallback_endpoint<E: ToEndpoint>(mut self, to_endpoint: E) -> Self {
+        self.fallback_endpoints.push(to_endpoint.to_endpoint());
+        self
+    }
+
+    /// Whether to discover API endpoints through partner login, as
+    /// described for `PandoraSession::set_endpoint_discovery()`.
+    /// (Chaining call)
+    pub fn endpoint_discovery(mut self, value: bool) -> Self {
+        self.endpoint_discovery = value;
+        self
+    }
+
+    /// Send all requests through the proxy at `url`, e.g.
+    /// `http://proxy.example.com:3128`.  SOCKS proxies (`socks5://...`)
+    /// require the `socks` feature.  When no proxy is set, the proxy
+    /// environment variables (`HTTPS_PROXY`, etc.) are used.
+    /// (Chaining call)
+    pub fn proxy(mut self, url: &str) -> Self {
+        self.proxies.push(url.to_string());
+        self
+    }
+
+    /// The time allowed to establish a connection. (Chaining call)
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// The time allowed between reads of the response. (Chaining call)
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// The time allowed for each request, from connecting until the response
+    /// has been read completely. (Chaining call)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The time allowed for requests for `method`, given in the form returned
+    /// by `PandoraJsonApiRequest::get_method()`, e.g. `"station.getPlaylist"`.
+    /// This overrides `timeout()` for that method, and applies whatever
+    /// transport the session uses. (Chaining call)
+    pub fn method_timeout(mut self, method: &str, timeout: Duration) -> Self {
+        self.method_timeouts.insert(method.to_string(), timeout);
+        self
+    }
+
+    /// Trust the PEM-encoded root certificate `pem`, in addition to the
+    /// system trust store. (Chaining call)
+    pub fn root_certificate_pem(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// The User-Agent header sent with each request. (Chaining call)
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Build the HTTP client configured by this builder.
+    pub fn build_client(&self) -> Result<reqwest::Client, Error> {
+        let mut client = reqwest::Client::builder();
+        for proxy in &self.proxies {
+            client = client.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            client = client.read_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+        for pem in &self.root_certificates {
+            client = client.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            client = client.user_agent(user_agent);
+        }
+        Ok(client.build()?)
+    }
+
+    /// Build the PandoraSession.  This fails if a proxy url or root
+    /// certificate is invalid, or the HTTP client can't be initialized.
+    pub fn build(&self) -> Result<PandoraSession, Error> {
+        let mut session = PandoraSession::new(Some(self.build_client()?), self, &self.endpoint);
+        for endpoint in &self.fallback_endpoints {
+            session.add_fallback_endpoint(endpoint.clone());
+        }
+        session.set_endpoint_discovery(self.endpoint_discovery);
+        for (method, timeout) in &self.method_timeouts {
+            session.set_method_timeout(method, *timeout);
+        }
+        Ok(session)
+    }
+}
+
+impl ToEncryptionTokens for PandoraSessionBuilder {
+    fn to_encrypt_key(&self) -> &Secret {
+        &self.encrypt_key
+    }
+
+    fn to_decrypt_key(&self) -> &Secret {
+        &self.decrypt_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::json::mock::MockPandora;
+    use crate::json::test::check_licensing;
+    use crate::json::transport::{Transport, TransportResponse};
+
+    /// Accept a single connection, and answer the first request on it with a
+    /// successful licensing check, returning the request line and headers.
+    async fn answer_once(listener: tokio::net::TcpListener) -> Vec<String> {
+        let (mut stream, _) = listener.accept().await.expect("Failed accepting");
+        let (reader, mut writer) = stream.split();
+        let mut reader = tokio::io::BufReader::new(reader);
+        let mut head = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.expect("Failed reading");
+            if line.trim_end().is_empty() {
+                break;
+            }
+            head.push(line.trim_end().to_string());
+        }
+        let body = r#"{"stat": "ok", "result": {"isAllowed": true}}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        writer
+            .write_all(response.as_bytes())
+            .await
+            .expect("Failed writing");
+        head
+    }
+
+    #[tokio::test]
+    async fn proxy_user_agent_test() {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .expect("Failed binding local port");
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        let proxied = tokio::spawn(answer_once(listener));
+
+        let partner = Partner::new_ios();
+        let builder = PandoraSessionBuilder::with_tokens(
+            &partner,
+            &String::from("http://tuner.example.invalid/services/json"),
+        )
+        .proxy(&proxy)
+        .connect_timeout(Duration::from_secs(5))
+        .user_agent(&partner.user_agent());
+        // The encryption keys aren't shown in debug output
+        let debug = format!("{:?}", builder);
+        assert!(!debug.contains(partner.encrypt_password.expose_secret()));
+        assert!(!debug.contains(partner.decrypt_password.expose_secret()));
+        let session = builder.build().expect("Failed building session");
+        let licensing = check_licensing(&session)
+            .await
+            .expect("Failed checking licensing through proxy");
+        assert!(licensing.is_allowed);
+
+        let head = proxied.await.expect("Proxy task failed");
+        assert!(head[0].starts_with("POST http://tuner.example.invalid/services/json?"));
+        let user_agent = format!("user-agent: {}", partner.user_agent());
+        assert!(head.iter().any(|h| h.eq_ignore_ascii_case(&user_agent)));
+
+        let invalid = PandoraSessionBuilder::new(&partner)
+            .proxy("not a url")
+            .build();
+        assert!(matches!(invalid, Err(Error::HttpIoError(_))));
+        let invalid = PandoraSessionBuilder::new(&partner)
+            .root_certificate_pem(b"not a certificate")
+            .build();
+        assert!(matches!(invalid, Err(Error::HttpIoError(_))));
+    }
+
+    /// Delays every answer to `station.getGenreStations`.
+    #[derive(Debug)]
+    struct SlowGenreStations(MockPandora);
+
+    #[async_trait::async_trait]
+    impl Transport for SlowGenreStations {
+        async fn post(&self, url: url::Url, body: String) -> Result<TransportResponse, Error> {
+            if url.query().unwrap_or_default().contains("getGenreStations") {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            self.0.post(url, body).await
+        }
+    }
+
+    #[tokio::test]
+    async fn method_timeout_test() {
+        let partner = Partner::default();
+        let mut session = PandoraSessionBuilder::new(&partner)
+            .method_timeout("test.checkLicensing", Duration::from_secs(5))
+            .method_timeout("station.getGenreStations", Duration::from_millis(50))
+            .build()
+            .expect("Failed building session");
+        session.set_transport(Arc::new(SlowGenreStations(MockPandora::new())));
+        assert_eq!(
+            session.method_timeout("station.getGenreStations"),
+            Some(Duration::from_millis(50))
+        );
+
+        let _licensing = check_licensing(&session)
+            .await
+            .expect("Failed checking licensing");
+        match crate::json::station::get_genre_stations(&session).await {
+            Err(Error::RequestTimeout(method, timeout)) => {
+                assert_eq!(method, "station.getGenreStations");
+                assert_eq!(timeout, Duration::from_millis(50));
+            }
+            res => panic!("Unexpected genre stations result: {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,366 @@
+/*!
+Record-and-replay of JSON API traffic.
+
+A [`CassetteRecorder`] is [`Middleware`] that captures each call made through
+a session as a [`CassetteEntry`]: the method name, the plaintext request json,
+and the raw response.  The resulting [`Cassette`] can be saved to a file, and
+later served by a [`CassettePlayer`] transport, which answers each request with
+the recorded response whose method and request json match, so that a captured
+session can be replayed deterministically without network access.
+
+Credentials and auth tokens are redacted from both the recorded requests and
+responses, including auth tokens embedded in other response values such as
+urls, so cassettes are safe to commit alongside the tests that use them.
+The `syncTime` of each request changes from run to run, so it is redacted as
+well, to allow replayed requests to match.
+*/
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::json::crypt;
+use crate::json::middleware::Middleware;
+use crate::json::redact::{collect_tokens, redact, redact_secrets};
+use crate::json::transport::{Transport, TransportResponse};
+use crate::json::ToEncryptionTokens;
+use crate::secret::Secret;
+
+pub use crate::json::redact::REDACTED;
+
+/// Keys whose values are redacted in request json because they differ between
+/// otherwise identical requests.
+const VOLATILE_KEYS: &[&str] = &["syncTime"];
+
+/// A single recorded API call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CassetteEntry {
+    /// The name of the API method called, e.g. `"station.getPlaylist"`.
+    pub method: String,
+    /// The plaintext request json, with secrets redacted.
+    pub request: serde_json::Value,
+    /// The HTTP status code of the response.
+    pub status: u16,
+    /// The body of the response, with secrets redacted.
+    pub response: String,
+}
+
+/// A sequence of recorded API calls.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cassette {
+    /// The recorded calls, in the order they were made.
+    pub entries: Vec<CassetteEntry>,
+}
+
+impl Cassette {
+    /// Create a new, empty Cassette.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a cassette from the json file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    /// Save the cassette as json to the file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let file = std::fs::File::create(path)?;
+        Ok(serde_json::to_writer_pretty(file, self)?)
+    }
+}
+
+/// Middleware that records every API call made through a session.
+///
+/// Each request is paired with its response by the call id passed to the
+/// middleware, so a single recorder may be shared by sessions that make
+/// concurrent calls.  Entries are recorded in the order the responses arrive.
+#[derive(Debug, Default)]
+pub struct CassetteRecorder {
+    pending: Mutex<HashMap<u64, serde_json::Value>>,
+    tokens: Mutex<Vec<String>>,
+    cassette: Mutex<Cassette>,
+}
+
+impl CassetteRecorder {
+    /// Create a new CassetteRecorder with nothing recorded yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a copy of the calls recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette
+            .lock()
+            .expect("CassetteRecorder cassette lock poisoned")
+            .clone()
+    }
+
+    /// Save the calls recorded so far as json to the file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        self.cassette().save(path)
+    }
+
+    /// Redact the secret values from a response body, along with any auth
+    /// tokens issued so far.  Bodies that aren't json are kept as-is.
+    fn redact_response(&self, body: &str) -> String {
+        let mut json = match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(json) => json,
+            Err(_) => return body.to_string(),
+        };
+        let mut tokens = self
+            .tokens
+            .lock()
+            .expect("CassetteRecorder token lock poisoned");
+        collect_tokens(&json, &mut tokens);
+        redact_secrets(&mut json);
+
+        let mut body = json.to_string();
+        for token in tokens.iter() {
+            let encoded: String = url::form_urlencoded::byte_serialize(token.as_bytes()).collect();
+            body = body.replace(token, REDACTED).replace(&encoded, REDACTED);
+        }
+        body
+    }
+}
+
+impl Middleware for CassetteRecorder {
+    fn on_request_json(&self, _method: &str, call_id: u64, json: &mut serde_json::Value) {
+        self.pending
+            .lock()
+            .expect("CassetteRecorder pending request lock poisoned")
+            .insert(call_id, redact_request(json.clone()));
+    }
+
+    fn on_response_body(&self, method: &str, call_id: u64, response: &mut TransportResponse) {
+        let request = self
+            .pending
+            .lock()
+            .expect("CassetteRecorder pending request lock poisoned")
+            .remove(&call_id);
+        if let Some(request) = request {
+            self.cassette
+                .lock()
+                .expect("CassetteRecorder cassette lock poisoned")
+                .entries
+                .push(CassetteEntry {
+                    method: method.to_string(),
+                    request,
+                    status: response.status,
+                    response: self.redact_response(&response.body),
+                });
+        }
+    }
+}
+
+/// A transport that answers requests from a recorded [`Cassette`].
+///
+/// Each request is answered with the first entry that hasn't been played yet
+/// whose method and (redacted) request json match the request.  Requests
+/// that don't match any remaining entry fail with `Error::TransportError`.
+#[derive(Debug)]
+pub struct CassettePlayer {
+    encrypt_key: Secret,
+    entries: Mutex<Vec<(CassetteEntry, bool)>>,
+}
+
+impl CassettePlayer {
+    /// Create a new CassettePlayer serving the entries of `cassette`, that
+    /// decrypts request bodies using the encryption key from
+    /// `to_encryption_tokens`, typically the same Partner used to create the
+    /// session.
+    pub fn new<TE: ToEncryptionTokens>(cassette: Cassette, to_encryption_tokens: &TE) -> Self {
+        Self {
+            encrypt_key: to_encryption_tokens.to_encrypt_key().clone(),
+            entries: Mutex::new(
+                cassette
+                    .entries
+                    .into_iter()
+                    .map(|entry| (entry, false))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The number of entries that haven't been played yet.
+    pub fn unplayed(&self) -> usize {
+        self.lock_entries()
+            .iter()
+            .filter(|(_, played)| !played)
+            .count()
+    }
+
+    fn lock_entries(&self) -> std::sync::MutexGuard<'_, Vec<(CassetteEntry, bool)>> {
+        self.entries
+            .lock()
+            .expect("CassettePlayer entry lock poisoned")
+    }
+
+    /// Decode the body of a request, decrypting it first if it isn't plain
+    /// json.
+    fn decode_body(&self, body: &str) -> Option<serde_json::Value> {
+        if let Ok(request) = serde_json::from_str(body) {
+            return Some(request);
+        }
+        let plaintext = crypt::decrypt(self.encrypt_key.expose_secret(), body).ok()?;
+        let plaintext = String::from_utf8(plaintext).ok()?;
+        serde_json::from_str(&plaintext).ok()
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for CassettePlayer {
+    async fn post(&self, url: url::Url, body: String) -> Result<TransportResponse, Error> {
+        let method = url
+            .query_pairs()
+            .find(|(key, _)| key == "method")
+            .map(|(_, value)| value.into_owned())
+            .ok_or_else(|| Error::TransportError(String::from("Request has no method")))?;
+        let request = self.decode_body(&body).map(redact_request).ok_or_else(|| {
+            Error::TransportError(format!("Unable to decode {} request body", method))
+        })?;
+
+        let mut entries = self.lock_entries();
+        let (entry, played) = entries
+            .iter_mut()
+            .find(|(entry, played)| !played && entry.method == method && entry.request == request)
+            .ok_or_else(|| {
+                Error::TransportError(format!("No cassette entry matches {} request", method))
+            })?;
+        *played = true;
+        Ok(TransportResponse::new(entry.status, &entry.response))
+    }
+}
+
+/// Redact the secret and volatile values from request json.
+fn redact_request(mut json: serde_json::Value) -> serde_json::Value {
+    redact_secrets(&mut json);
+    redact(&mut json, &|key| VOLATILE_KEYS.contains(&key));
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::json::mock::MockPandora;
+    use crate::json::retry::RetryPolicy;
+    use crate::json::{
+        auth::user_login,
+        station::{get_playlist, get_station, PlaylistEntry},
+        tests::session_login_with,
+        user::{get_station_list, ChangeSettings},
+        PandoraJsonApiRequest, PandoraSession, Partner,
+    };
+
+    async fn login() -> PandoraSession {
+        session_login_with(Arc::new(MockPandora::new()), RetryPolicy::new())
+            .await
+            .expect("Failed login against mock")
+    }
+
+    #[tokio::test]
+    async fn redact_credentials_test() {
+        let mut session = login().await;
+        let recorder = Arc::new(CassetteRecorder::new());
+        session.add_middleware(recorder.clone());
+
+        user_login(
+            &session,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+        .await
+        .expect("Failed user login");
+        ChangeSettings::new(MockPandora::DEFAULT_USERNAME, MockPandora::DEFAULT_PASSWORD)
+            .new_password("correct horse")
+            .response(&session)
+            .await
+            .expect("Failed changing settings");
+
+        let cassette = recorder.cassette();
+        assert_eq!(cassette.entries.len(), 2);
+        let settings = &cassette.entries[1];
+        assert_eq!(settings.method, "user.changeSettings");
+        for key in ["currentUsername", "currentPassword", "newPassword"] {
+            assert_eq!(settings.request[key], REDACTED, "{}", key);
+        }
+        let saved = serde_json::to_string(&cassette).expect("Failed saving cassette");
+        assert!(!saved.contains(MockPandora::DEFAULT_USERNAME));
+        assert!(!saved.contains(MockPandora::DEFAULT_PASSWORD));
+        assert!(!saved.contains("correct horse"));
+    }
+
+    #[tokio::test]
+    async fn record_and_replay_test() {
+        let mut session = login().await;
+        let recorder = Arc::new(CassetteRecorder::new());
+        session.add_middleware(recorder.clone());
+
+        let station_token = get_station_list(&session)
+            .await
+            .expect("Failed getting station list")
+            .stations[0]
+            .station_token
+            .clone();
+        let station = get_station(&session, &station_token)
+            .await
+            .expect("Failed getting station");
+        let playlist = get_playlist(&session, &station_token)
+            .await
+            .expect("Failed getting playlist");
+
+        let saved = serde_json::to_string(&recorder.cassette()).expect("Failed saving cassette");
+        let user_token = session
+            .session_tokens()
+            .user_token
+            .clone()
+            .expect("Missing user token");
+        assert!(!saved.contains(user_token.expose_secret()));
+
+        let cassette: Cassette = serde_json::from_str(&saved).expect("Failed loading cassette");
+        assert_eq!(cassette.entries.len(), 3);
+        assert_eq!(cassette.entries[2].method, "station.getPlaylist");
+
+        // Replay through a copy of the session, sharing its login
+        let player = Arc::new(CassettePlayer::new(cassette, &Partner::default()));
+        let mut replay = session.clone();
+        replay.clear_middleware().set_transport(player.clone());
+        let _stations = get_station_list(&replay)
+            .await
+            .expect("Failed replaying station list");
+        let replayed_station = get_station(&replay, &station_token)
+            .await
+            .expect("Failed replaying station");
+        let replayed_playlist = get_playlist(&replay, &station_token)
+            .await
+            .expect("Failed replaying playlist");
+        assert_eq!(replayed_station.station_name, station.station_name);
+        let track_tokens = |items: &[PlaylistEntry]| -> Vec<String> {
+            items
+                .iter()
+                .filter_map(PlaylistEntry::get_track)
+                .map(|track| track.track_token)
+                .collect()
+        };
+        assert_eq!(
+            track_tokens(&replayed_playlist.items),
+            track_tokens(&playlist.items)
+        );
+        assert_eq!(player.unplayed(), 0);
+
+        // Each entry is only played once
+        match get_playlist(&replay, &station_token).await {
+            Err(Error::TransportError(_)) => (),
+            res => panic!("Unexpected replay result: {:?}", res),
+        }
+    }
+}
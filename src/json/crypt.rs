@@ -5,68 +5,151 @@
 // SPDX-License-Identifier: MIT
 
 use blowfish::Blowfish;
-use cipher::block_padding::NoPadding;
-use cipher::{BlockDecryptMut, BlockEncryptMut, KeyInit};
+use cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+
+use crate::errors::Error;
+use crate::secret::Secret;
 
 const PADDING_BYTE: u8 = 2;
 const BLOCK_LEN: usize = 8;
+/// The shortest key supported by Blowfish, in bytes.
+pub const MIN_KEY_LEN: usize = 4;
+/// The longest key supported by Blowfish, in bytes.
+pub const MAX_KEY_LEN: usize = 56;
+
+/// Whether `key` has a length supported by Blowfish.
+pub fn is_valid_key(key: &str) -> bool {
+    (MIN_KEY_LEN..=MAX_KEY_LEN).contains(&key.len())
+}
+
+/// The digits used for hexadecimal notation.
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// A Blowfish cipher initialized with a key, so that the key schedule is
+/// only computed once, however many times it's used.
+#[derive(Clone)]
+pub struct Cipher {
+    key: Secret,
+    blowfish: Blowfish,
+}
+
+impl Cipher {
+    /// Initialize the cipher for `key`, failing if it has an unsupported
+    /// length.
+    pub fn new(key: &str) -> Result<Self, Error> {
+        let blowfish = Blowfish::new_from_slice(key.as_bytes()).map_err(|_| {
+            Error::CryptoError(format!(
+                "unsupported key length {}, expected {} to {} bytes",
+                key.len(),
+                MIN_KEY_LEN,
+                MAX_KEY_LEN
+            ))
+        })?;
+        Ok(Self {
+            key: Secret::from(key),
+            blowfish,
+        })
+    }
+
+    /// The key the cipher was initialized with.
+    pub fn key(&self) -> &Secret {
+        &self.key
+    }
+
+    /// Returns the encrypted input, encoded in hexadecimal notation.
+    pub fn encrypt(&self, input: &str) -> String {
+        let mut inputbytes = input.as_bytes().to_vec();
+        let padded_len = round_len(inputbytes.len(), BLOCK_LEN);
+        inputbytes.resize(padded_len, PADDING_BYTE);
+        for block in inputbytes.chunks_exact_mut(BLOCK_LEN) {
+            self.blowfish.encrypt_block(block.into());
+        }
+
+        // Generate hexadecimal representation of `inputbytes`.
+        let mut output = Vec::with_capacity(inputbytes.len() * 2);
+        for b in inputbytes {
+            output.push(HEX_DIGITS[usize::from(b >> 4)]);
+            output.push(HEX_DIGITS[usize::from(b & 0x0f)]);
+        }
+        String::from_utf8(output).expect("Hexadecimal digits are valid UTF-8")
+    }
+
+    /// Returns the decrypted input, which doesn't have to be UTF-8 compliant.
+    /// This fails if the input isn't a whole number of blocks in hexadecimal
+    /// notation.
+    pub fn decrypt(&self, hex_input: &str) -> Result<Vec<u8>, Error> {
+        let mut cipherbytes = decode_hex(hex_input)?;
+        if !cipherbytes.len().is_multiple_of(BLOCK_LEN) {
+            return Err(Error::CryptoError(format!(
+                "input length {} is not a multiple of the {} byte block length",
+                cipherbytes.len(),
+                BLOCK_LEN
+            )));
+        }
+        for block in cipherbytes.chunks_exact_mut(BLOCK_LEN) {
+            self.blowfish.decrypt_block(block.into());
+        }
+
+        // Ignore up to `PADDING_BYTE`.
+        if let Some(index) = cipherbytes.iter().position(|&b| b == PADDING_BYTE) {
+            cipherbytes.truncate(index);
+        }
+
+        Ok(cipherbytes)
+    }
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cipher").finish_non_exhaustive()
+    }
+}
 
 /// Returns the encrypted input using the given key.
 ///
 /// The returned string is encoded in hexadecimal notation,
 /// which is a UTF-8 string, so it's fine to return it using
-/// the `String` type.
-pub fn encrypt(key: &str, input: &str) -> String {
-    let mut inputbytes = input.as_bytes().to_vec();
-    let padded_len = round_len(inputbytes.len(), BLOCK_LEN);
-    inputbytes.resize(padded_len, PADDING_BYTE);
-
-    let encryptor: Blowfish =
-        Blowfish::new_from_slice(key.as_bytes()).expect("Invalid key: unsupported key length");
-
-    let cipherbytes = encryptor
-        .encrypt_padded_mut::<NoPadding>(&mut inputbytes, padded_len)
-        .expect("Error encrypting input");
-
-    // Generate hexadecimal representation of `cipherbytes`.
-    let mut output = String::with_capacity(cipherbytes.len() * 2);
-    for b in cipherbytes {
-        output.push_str(&format!("{b:02x}"));
-    }
-    output
+/// the `String` type.  This fails if the key has an unsupported length.
+///
+/// This computes the key schedule on every call; use a [`Cipher`] to
+/// encrypt repeatedly with the same key.
+pub fn encrypt(key: &str, input: &str) -> Result<String, Error> {
+    Ok(Cipher::new(key)?.encrypt(input))
 }
 
 /// Returns the decrypted input using the given key.
 ///
 /// Because Strings must be UTF-8 compilant, and decrypting
 /// doesn't guarantees an UTF-8 string, we return
-/// a OsString which doesn't have to be UTF-8 compilant.
-pub fn decrypt(key: &str, hex_input: &str) -> Vec<u8> {
-    use std::str;
-    use std::u8;
-
-    // Gets bytes from hexadecimal representation.
-    let mut inputbytes = Vec::with_capacity(hex_input.len());
-    for chunk in hex_input.as_bytes().chunks(2) {
-        // `chunk` is utf-8 since it is comming from &str.
-        let fragment = unsafe { str::from_utf8_unchecked(chunk) };
-        let byte = u8::from_str_radix(fragment, 16).unwrap_or(0);
-        inputbytes.push(byte);
-    }
-
-    let decryptor: Blowfish =
-        Blowfish::new_from_slice(key.as_bytes()).expect("Invalid key: unsupported key length");
-    let mut cipherbytes = decryptor
-        .decrypt_padded_mut::<NoPadding>(&mut inputbytes)
-        .expect("Error decrypting input")
-        .to_vec();
+/// the raw bytes, which don't have to be UTF-8 compilant.  This fails if
+/// the key has an unsupported length, or if the input isn't a whole number
+/// of blocks in hexadecimal notation.
+pub fn decrypt(key: &str, hex_input: &str) -> Result<Vec<u8>, Error> {
+    Cipher::new(key)?.decrypt(hex_input)
+}
 
-    // Ignore up to `PADDING_BYTE`.
-    if let Some(index) = cipherbytes.iter().position(|&b| b == PADDING_BYTE) {
-        cipherbytes.truncate(index);
+/// Gets bytes from hexadecimal representation.
+fn decode_hex(hex_input: &str) -> Result<Vec<u8>, Error> {
+    if !hex_input.len().is_multiple_of(2) {
+        return Err(Error::CryptoError(format!(
+            "odd hexadecimal input length {}",
+            hex_input.len()
+        )));
     }
-
-    cipherbytes
+    hex_input
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digit = |b: u8| (b as char).to_digit(16);
+            match (digit(pair[0]), digit(pair[1])) {
+                (Some(high), Some(low)) => Ok((high * 16 + low) as u8),
+                _ => Err(Error::CryptoError(format!(
+                    "invalid hexadecimal pair {:?}",
+                    String::from_utf8_lossy(pair)
+                ))),
+            }
+        })
+        .collect()
 }
 
 /// Rounds the given len so that it contains blocks
@@ -82,7 +165,8 @@ fn round_len(len: usize, block_size: usize) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::encrypt;
+    use super::{decrypt, encrypt, Cipher};
+    use crate::errors::Error;
 
     struct Test {
         key: String,
@@ -101,8 +185,52 @@ mod tests {
     #[test]
     fn encrypt_test_vector() {
         for test in get_test_vector() {
-            let cipher_text = encrypt(&test.key, &test.plain_text);
+            let cipher_text = encrypt(&test.key, &test.plain_text).expect("Failed encrypting");
             assert_eq!(test.cipher_text, cipher_text);
         }
     }
+
+    #[test]
+    fn decrypt_test_vector() {
+        for test in get_test_vector() {
+            let plain_text = decrypt(&test.key, &test.cipher_text).expect("Failed decrypting");
+            assert_eq!(test.plain_text.as_bytes(), plain_text);
+        }
+    }
+
+    #[test]
+    fn cipher_reuse_test() {
+        let cipher = Cipher::new("R=U!LH$O2B#").expect("Failed initializing cipher");
+        for test in get_test_vector() {
+            for _ in 0..2 {
+                assert_eq!(cipher.encrypt(&test.plain_text), test.cipher_text);
+                let plain_text = cipher
+                    .decrypt(&test.cipher_text)
+                    .expect("Failed decrypting");
+                assert_eq!(plain_text, test.plain_text.as_bytes());
+            }
+        }
+        assert_eq!(format!("{:?}", cipher), "Cipher { .. }");
+    }
+
+    #[test]
+    fn invalid_input_test() {
+        let key = "R=U!LH$O2B#";
+        let invalid = [
+            encrypt("abc", "input").map(|_| ()),
+            encrypt(&"k".repeat(57), "input").map(|_| ()),
+            decrypt("abc", "4a6b45612b018614").map(|_| ()),
+            // Odd length
+            decrypt(key, "4a6b45612b018614c").map(|_| ()),
+            // Not hexadecimal
+            decrypt(key, "4a6b45612b0186zz").map(|_| ()),
+            decrypt(key, "4a6b45612b0186\u{e8}").map(|_| ()),
+            // Not a whole number of blocks
+            decrypt(key, "4a6b45612b01").map(|_| ()),
+        ];
+        for result in invalid {
+            assert!(matches!(result, Err(Error::CryptoError(_))), "{:?}", result);
+        }
+        assert_eq!(decrypt(key, "").expect("Failed decrypting"), b"");
+    }
 }
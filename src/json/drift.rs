@@ -0,0 +1,444 @@
+/*!
+Detection of drift between the responses Pandora returns and the response
+types of this crate.
+
+Most response types sweep fields they don't know about into an `optional`
+map, so additions, renames and removals by Pandora go unnoticed.  In strict
+mode, enabled with `PandoraSession::set_strict_mode()`, the untyped result of
+each successful API call is checked against the response type of the request
+before it is deserialized, and each difference is recorded:
+
+* [`DriftKind::Unknown`] for a key that isn't a field of the response type,
+  i.e. one that lands in an `optional` map or is ignored,
+* [`DriftKind::Missing`] for a required field that is absent,
+* [`DriftKind::Invalid`] for a result that can't be deserialized for any
+  other reason.
+
+There is no separate schema to maintain: a key is a field of the response
+type when the result no longer deserializes after its value is replaced with
+one that no field accepts.
+
+Every clone of the session records into the same [`DriftReport`], which
+serializes to json for automated checks:
+
+``` no_run
+# async fn example(session: &mut pandora_api::json::PandoraSession) -> Result<(), pandora_api::errors::Error> {
+use pandora_api::json::user::get_station_list;
+
+session.set_strict_mode(true);
+let _stations = get_station_list(session).await?;
+if let Some(report) = session.drift_report() {
+    println!("{}", report.to_json()?);
+}
+# Ok(())
+# }
+```
+*/
+// SPDX-License-Identifier: MIT
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::errors::Error;
+use crate::json::{PandoraResponse, PandoraStatus};
+
+/// How a response differed from its response type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DriftKind {
+    /// The response included a key that isn't a field of the response type
+    Unknown,
+    /// The response didn't include a required field
+    Missing,
+    /// The response couldn't be deserialized for another reason
+    Invalid,
+}
+
+/// A difference between the responses to an API method and its response
+/// type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Drift {
+    /// The API method, e.g. `"station.getPlaylist"`
+    pub method: String,
+    /// How the responses differed
+    pub kind: DriftKind,
+    /// The path of the field within the result, e.g.
+    /// `"items[].songExplorerUrl"`.  Serde only reports the name of a missing
+    /// field, without its path, and the deserialization error takes the
+    /// place of the field for invalid responses.
+    pub field: String,
+    /// The number of responses that differed this way
+    pub count: u32,
+}
+
+/// Every difference from the response types seen by a session, in order of
+/// method, kind and field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DriftReport {
+    /// The number of responses that were checked
+    pub responses_checked: u32,
+    /// The differences found
+    pub drift: Vec<Drift>,
+}
+
+impl DriftReport {
+    /// Whether no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.drift.is_empty()
+    }
+
+    /// Serialize the report as a json document.
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Records the differences between API responses and their response types,
+/// for one session.
+#[derive(Debug, Default)]
+pub struct DriftDetector {
+    state: Mutex<DetectorState>,
+}
+
+#[derive(Debug, Default)]
+struct DetectorState {
+    responses_checked: u32,
+    drift: BTreeMap<(String, DriftKind, String), u32>,
+}
+
+impl DriftDetector {
+    /// Create a new DriftDetector that hasn't seen any responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check the result of a successful response to `method` against the
+    /// response type `T` it will be deserialized into.  Each difference is
+    /// logged the first time it is seen.
+    pub fn check<T: DeserializeOwned>(&self, method: &str, response: &PandoraResponse<Value>) {
+        if response.stat != PandoraStatus::Ok {
+            return;
+        }
+        let found = match &response.result {
+            Some(result) => find_drift::<T>(result),
+            None => BTreeSet::new(),
+        };
+
+        let mut state = self.state.lock().expect("DriftDetector lock poisoned");
+        state.responses_checked += 1;
+        for (kind, field) in found {
+            let count = state
+                .drift
+                .entry((method.to_string(), kind, field.clone()))
+                .or_insert(0);
+            if *count == 0 {
+                log::warn!(
+                    "{} response schema drift: {:?} field {}",
+                    method,
+                    kind,
+                    field
+                );
+            }
+            *count += 1;
+        }
+    }
+
+    /// A snapshot of the differences recorded so far.
+    pub fn report(&self) -> DriftReport {
+        let state = self.state.lock().expect("DriftDetector lock poisoned");
+        DriftReport {
+            responses_checked: state.responses_checked,
+            drift: state
+                .drift
+                .iter()
+                .map(|((method, kind, field), count)| Drift {
+                    method: method.clone(),
+                    kind: *kind,
+                    field: field.clone(),
+                    count: *count,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One step of the path to a field within a result.
+#[derive(Debug, Clone)]
+enum Step {
+    /// The value of the key in an object
+    Key(String),
+    /// Each of the elements of an array
+    Each,
+}
+
+/// Find the differences between `result` and the response type `T`.
+fn find_drift<T: DeserializeOwned>(result: &Value) -> BTreeSet<(DriftKind, String)> {
+    let mut found = BTreeSet::new();
+    match serde_json::from_value::<T>(result.clone()) {
+        Ok(_) => find_unknown_fields::<T>(result, &mut Vec::new(), &mut found),
+        Err(e) => {
+            let message = e.to_string();
+            let missing = message
+                .strip_prefix("missing field `")
+                .and_then(|field| field.strip_suffix('`'));
+            found.insert(match missing {
+                Some(field) => (DriftKind::Missing, field.to_string()),
+                None => (DriftKind::Invalid, message),
+            });
+        }
+    }
+    found
+}
+
+/// Probe each key of the objects at `path` within `result`, which
+/// deserializes into `T`, adding the keys that aren't fields of `T` to
+/// `found`, and descending into the values of those that are.
+///
+/// A key is a field when replacing its value with `[null]` makes `result`
+/// fail to deserialize.  No field type accepts that value: strings, numbers,
+/// booleans and maps reject an array, element types reject `null`, and
+/// structs read from a sequence reject it unless their only field is
+/// optional.
+fn find_unknown_fields<T: DeserializeOwned>(
+    result: &Value,
+    path: &mut Vec<Step>,
+    found: &mut BTreeSet<(DriftKind, String)>,
+) {
+    let keys: BTreeSet<&String> = values_at(result, path)
+        .into_iter()
+        .filter_map(Value::as_object)
+        .flat_map(|object| object.keys())
+        .collect();
+    for key in keys {
+        path.push(Step::Key(key.clone()));
+        let mut probed = result.clone();
+        replace_at(&mut probed, path, &serde_json::json!([null]));
+        if serde_json::from_value::<T>(probed).is_ok() {
+            found.insert((DriftKind::Unknown, path_to_string(path)));
+        } else {
+            let values = values_at(result, path);
+            if values.iter().any(|value| value.is_array()) {
+                path.push(Step::Each);
+                find_unknown_fields::<T>(result, path, found);
+                path.pop();
+            } else if values.iter().any(|value| value.is_object()) {
+                find_unknown_fields::<T>(result, path, found);
+            }
+        }
+        path.pop();
+    }
+}
+
+/// The values at `path` within `value`.
+fn values_at<'a>(value: &'a Value, path: &[Step]) -> Vec<&'a Value> {
+    match path.split_first() {
+        None => vec![value],
+        Some((Step::Key(key), rest)) => value
+            .get(key)
+            .map(|value| values_at(value, rest))
+            .unwrap_or_default(),
+        Some((Step::Each, rest)) => value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|value| values_at(value, rest))
+            .collect(),
+    }
+}
+
+/// Replace the values at `path` within `value` with `replacement`.
+fn replace_at(value: &mut Value, path: &[Step], replacement: &Value) {
+    match path.split_first() {
+        None => *value = replacement.clone(),
+        Some((Step::Key(key), rest)) => {
+            if let Some(value) = value.get_mut(key) {
+                replace_at(value, rest, replacement);
+            }
+        }
+        Some((Step::Each, rest)) => {
+            for value in value.as_array_mut().into_iter().flatten() {
+                replace_at(value, rest, replacement);
+            }
+        }
+    }
+}
+
+/// Format `path` the way it is reported, e.g. `"items[].audioUrlMap"`.
+fn path_to_string(path: &[Step]) -> String {
+    let mut formatted = String::new();
+    for step in path {
+        match step {
+            Step::Key(key) if formatted.is_empty() => formatted.push_str(key),
+            Step::Key(key) => {
+                formatted.push('.');
+                formatted.push_str(key);
+            }
+            Step::Each => formatted.push_str("[]"),
+        }
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::json::middleware::Middleware;
+    use crate::json::station::{get_playlist, GetPlaylistResponse};
+    use crate::json::tests::session_login;
+    use crate::json::user::get_station_list;
+    use crate::json::Partner;
+
+    /// Adds a field to each playlist track, and removes the name of each
+    /// station in the station list.
+    #[derive(Debug)]
+    struct Drifted;
+
+    impl Middleware for Drifted {
+        fn on_response(&self, method: &str, _call_id: u64, response: &mut PandoraResponse<Value>) {
+            let (list, key) = match method {
+                "station.getPlaylist" => ("items", "trackToken"),
+                "user.getStationList" => ("stations", "stationName"),
+                _ => return,
+            };
+            let entries = response
+                .result
+                .as_mut()
+                .and_then(|r| r[list].as_array_mut());
+            for entry in entries.into_iter().flatten() {
+                let entry = entry.as_object_mut().expect("List entry isn't an object");
+                if list == "stations" {
+                    entry.remove(key);
+                } else if entry.contains_key(key) {
+                    entry.insert("songRatingV2".to_string(), Value::from(1));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn drift_report_test() {
+        let partner = Partner::default();
+        let mut session = session_login(&partner)
+            .await
+            .expect("Failed initializing login session");
+        assert_eq!(session.drift_report(), None);
+        session.set_strict_mode(true);
+        assert!(session.strict_mode());
+
+        let stations = get_station_list(&session)
+            .await
+            .expect("Failed getting station list");
+        let station_token = &stations.stations[0].station_token;
+        get_playlist(&session, station_token)
+            .await
+            .expect("Failed getting playlist");
+        let report = session.drift_report().expect("Strict mode has no report");
+        assert_eq!(report.responses_checked, 2);
+        assert!(!report
+            .drift
+            .iter()
+            .any(|d| d.method == "station.getPlaylist"));
+
+        let mut drifted = session.clone();
+        drifted.add_middleware(Arc::new(Drifted));
+        get_playlist(&drifted, station_token)
+            .await
+            .expect("Failed getting playlist with an added field");
+        get_playlist(&drifted, station_token)
+            .await
+            .expect("Failed getting playlist with an added field");
+        // The station name is required, so the list fails deserialization,
+        // after the drift was recorded
+        assert!(get_station_list(&drifted).await.is_err());
+
+        let report = session.drift_report().expect("Strict mode has no report");
+        assert_eq!(report.responses_checked, 5);
+        let drift: Vec<(&str, DriftKind, &str, u32)> = report
+            .drift
+            .iter()
+            .map(|d| (d.method.as_str(), d.kind, d.field.as_str(), d.count))
+            .filter(|(_, _, field, _)| ["items[].songRatingV2", "stationName"].contains(field))
+            .collect();
+        assert_eq!(
+            drift,
+            vec![
+                (
+                    "station.getPlaylist",
+                    DriftKind::Unknown,
+                    "items[].songRatingV2",
+                    2
+                ),
+                ("user.getStationList", DriftKind::Missing, "stationName", 1),
+            ]
+        );
+
+        let json: Value =
+            serde_json::from_str(&report.to_json().expect("Failed serializing drift report"))
+                .expect("Drift report isn't valid json");
+        assert_eq!(json["responsesChecked"], 5);
+        assert!(json["drift"]
+            .as_array()
+            .expect("Drift report has no drift list")
+            .iter()
+            .any(|d| d["kind"] == "missing" && d["field"] == "stationName"));
+
+        session.set_strict_mode(false);
+        assert_eq!(session.drift_report(), None);
+    }
+
+    #[test]
+    fn nested_fields_test() {
+        let stream = serde_json::json!({
+            "bitrate": "64",
+            "encoding": "aacplus",
+            "audioUrl": "http://localhost/audio",
+            "protocol": "http"
+        });
+        let mut track = serde_json::json!({
+            "trackToken": "t",
+            "musicId": "m",
+            "stationId": "s",
+            "audioUrlMap": {
+                "highQuality": stream,
+                "mediumQuality": stream,
+                "lowQuality": stream
+            },
+            "artistName": "artist",
+            "albumName": "album",
+            "songName": "song",
+            "songRating": 0,
+            "trackGain": "0.0",
+            "additionalAudioUrl": "http://localhost/additional"
+        });
+        track["audioUrlMap"]["lowQuality"]["codec"] = Value::from("aac");
+        let result = serde_json::json!({
+            "items": [{"adToken": "ad", "adTrackingTokens": []}, track]
+        });
+        let found = find_drift::<GetPlaylistResponse>(&result);
+        assert_eq!(
+            found.into_iter().collect::<Vec<_>>(),
+            vec![
+                (DriftKind::Unknown, "items[].adTrackingTokens".to_string()),
+                (
+                    DriftKind::Unknown,
+                    "items[].audioUrlMap.lowQuality.codec".to_string()
+                ),
+            ]
+        );
+
+        // A track without a song rating matches neither kind of entry
+        track.as_object_mut().unwrap().remove("songRating");
+        let result = serde_json::json!({ "items": [track] });
+        let found = find_drift::<GetPlaylistResponse>(&result);
+        assert_eq!(found.len(), 1);
+        assert!(found.iter().all(|(kind, _)| *kind == DriftKind::Invalid));
+    }
+}
@@ -108,6 +108,100 @@ pub enum JsonErrorKind {
     UnknownErrorMessage,
 }
 
+impl JsonErrorKind {
+    /// The kinds of error that are likely to be transient.
+    pub const RETRYABLE: [JsonErrorKind; 3] = [
+        JsonErrorKind::InternalError,
+        JsonErrorKind::MaintenanceMode,
+        JsonErrorKind::InsufficientConnectivity,
+    ];
+
+    /// Whether the error is likely to be transient, so that the same request
+    /// may succeed if it's submitted again after a short delay.
+    pub fn is_retryable(&self) -> bool {
+        Self::RETRYABLE.contains(self)
+    }
+
+    /// Decode an error code returned by the API method `method`, e.g.
+    /// `"user.createUser"`, along with the message accompanying it.  This
+    /// differs from `JsonErrorKind::from()` only where the meaning of a code
+    /// depends on the method: code 1027 is reported for both an invalid
+    /// country code and an invalid gender.
+    ///
+    /// `user.changeSettings` doesn't take a country code, so for it 1027
+    /// always means an invalid gender.  `user.createUser` takes both, so they
+    /// are told apart by the message: 1027 means an invalid gender if the
+    /// message mentions "gender", and otherwise, including when there's no
+    /// message, falls back to an invalid country code.
+    pub fn for_method(method: &str, code: u32, message: Option<&str>) -> Self {
+        let about_gender = || message.is_some_and(|m| m.to_lowercase().contains("gender"));
+        match (method, code) {
+            ("user.changeSettings", 1027) => JsonErrorKind::InvalidGender,
+            ("user.createUser", 1027) if about_gender() => JsonErrorKind::InvalidGender,
+            (_, code) => JsonErrorKind::from(code),
+        }
+    }
+
+    /// Whether the session tokens are no longer accepted, so that partner and
+    /// user login have to be repeated before further requests can succeed.
+    pub fn requires_reauth(&self) -> bool {
+        matches!(
+            self,
+            JsonErrorKind::InvalidAuthToken | JsonErrorKind::InsufficientConnectivity
+        )
+    }
+
+    /// Whether the error is due to the state of the account, partner or
+    /// device, e.g. wrong credentials, an expired subscription or a disabled
+    /// device, rather than to the request itself.
+    pub fn is_account_problem(&self) -> bool {
+        matches!(
+            self,
+            JsonErrorKind::LicensingRestrictions
+                | JsonErrorKind::InvalidPartnerLogin
+                | JsonErrorKind::ListenerNotAuthorized
+                | JsonErrorKind::UserNotAuthorized
+                | JsonErrorKind::MaxStationsReached
+                | JsonErrorKind::ComplimentaryPeriodAlreadyInUse
+                | JsonErrorKind::DeviceNotFound
+                | JsonErrorKind::PartnerNotAuthorized
+                | JsonErrorKind::DeviceAlreadyAssociatedToAccount
+                | JsonErrorKind::DeviceDisabled
+                | JsonErrorKind::DailyTrialLimitReached
+                | JsonErrorKind::UserAlreadyUsedTrial
+        )
+    }
+
+    /// Whether the request was refused because too many similar requests
+    /// were made recently.
+    pub fn is_rate_limit(&self) -> bool {
+        matches!(self, JsonErrorKind::PlaylistExceeded)
+    }
+
+    /// Whether the error is due to a missing or invalid value supplied with
+    /// the request, such as a malformed zip code or an unknown station.
+    pub fn is_user_input_error(&self) -> bool {
+        matches!(
+            self,
+            JsonErrorKind::ParameterTypeMismatch
+                | JsonErrorKind::ParameterMissing
+                | JsonErrorKind::ParameterValueInvalid
+                | JsonErrorKind::StationDoesNotExist
+                | JsonErrorKind::InvalidUsername
+                | JsonErrorKind::InvalidPassword
+                | JsonErrorKind::UsernameAlreadyExists
+                | JsonErrorKind::ExplicitPinIncorrect
+                | JsonErrorKind::ExplicitPinMalformed
+                | JsonErrorKind::ZipCodeInvalid
+                | JsonErrorKind::BirthYearInvalid
+                | JsonErrorKind::BirthYearTooYoung
+                | JsonErrorKind::InvalidCountryCode
+                | JsonErrorKind::InvalidGender
+                | JsonErrorKind::InvalidSponsor
+        )
+    }
+}
+
 impl From<u32> for JsonErrorKind {
     /// Create a JsonError from an error code.
     fn from(code: u32) -> Self {
@@ -150,9 +244,9 @@ impl From<u32> for JsonErrorKind {
             1024 => JsonErrorKind::ZipCodeInvalid,
             1025 => JsonErrorKind::BirthYearInvalid,
             1026 => JsonErrorKind::BirthYearTooYoung,
-            /* TODO: these two error codes collide - verify value */
+            // Also reported for an invalid gender, which is decoded by
+            // JsonErrorKind::for_method() for the methods that can cause it
             1027 => JsonErrorKind::InvalidCountryCode,
-            //1027 => JsonErrorKind::InvalidGender,
             1034 => JsonErrorKind::DeviceDisabled,
             1035 => JsonErrorKind::DailyTrialLimitReached,
             1036 => JsonErrorKind::InvalidSponsor,
@@ -239,6 +333,7 @@ impl std::fmt::Display for JsonErrorKind {
 #[derive(Debug, Clone, PartialEq)]
 pub struct JsonError {
     pub(crate) kind: JsonErrorKind,
+    pub(crate) code: Option<u32>,
     pub(crate) message: Option<String>,
 }
 
@@ -249,13 +344,27 @@ impl JsonError {
             Some(code) => JsonErrorKind::from(code),
             None => JsonErrorKind::UnknownErrorMessage,
         };
-        JsonError { kind, message }
+        JsonError {
+            kind,
+            code,
+            message,
+        }
     }
 
     /// Return what kind of error this is.
     pub fn kind(&self) -> JsonErrorKind {
         self.kind
     }
+
+    /// Return the numeric error code returned by the API, if any.
+    pub fn code(&self) -> Option<u32> {
+        self.code
+    }
+
+    /// Return the message describing the error returned by the API, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
 }
 
 impl std::error::Error for JsonError {
@@ -275,3 +384,49 @@ impl std::fmt::Display for JsonError {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::errors::Error;
+
+    #[test]
+    fn classification_test() {
+        let error = JsonError::new(Some(1001), Some(String::from("An unexpected error")));
+        assert_eq!(error.kind(), JsonErrorKind::InvalidAuthToken);
+        assert_eq!(error.code(), Some(1001));
+        assert_eq!(error.message(), Some("An unexpected error"));
+        let error = Error::from(error);
+        assert!(error.requires_reauth());
+        assert!(!error.is_retryable());
+
+        let error = JsonError::new(None, None);
+        assert_eq!(error.code(), None);
+        assert_eq!(error.message(), None);
+
+        // The categories describing what went wrong don't overlap
+        for code in (0..=15).chain(1000..=1039) {
+            let kind = JsonErrorKind::from(code);
+            let categories = [
+                kind.is_account_problem(),
+                kind.is_rate_limit(),
+                kind.is_user_input_error(),
+                kind.is_retryable(),
+            ];
+            assert!(categories.iter().filter(|c| **c).count() <= 1, "{kind}");
+        }
+
+        let playlist_exceeded = Error::from(JsonError::new(Some(1039), None));
+        assert!(playlist_exceeded.is_rate_limit());
+        assert!(Error::HttpStatusError(429).is_rate_limit());
+        assert!(Error::RateLimitExceeded(String::new(), Duration::from_secs(1)).is_rate_limit());
+        assert!(Error::HttpStatusError(503).is_retryable());
+        assert!(Error::RequestTimeout(String::new(), Duration::from_secs(1)).is_retryable());
+        assert!(Error::from(JsonError::new(Some(1024), None)).is_user_input_error());
+        assert!(Error::InvalidUserGender(String::new()).is_user_input_error());
+        assert!(Error::from(JsonError::new(Some(1003), None)).is_account_problem());
+        assert!(!Error::TransportError(String::new()).is_account_problem());
+    }
+}
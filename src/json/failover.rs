@@ -0,0 +1,426 @@
+/*!
+Endpoint discovery and failover.
+
+Pandora serves the JSON API from more than one host, e.g. `tuner.pandora.com`
+and `internal-tuner.pandora.com`.  The [`Endpoints`] of a
+[`PandoraSession`](crate::json::PandoraSession) are the endpoint it was
+created with, any fallback endpoints added to it, and, when endpoint
+discovery is enabled, any API endpoints discovered in the `urls` returned by
+partner login.  Requests are submitted to the active endpoint, and when it
+can't be reached at all, the request is resubmitted to the next endpoint,
+which then becomes the active endpoint for later requests.  An endpoint that
+times out may have received the request already, so only idempotent requests
+fail over on a timeout; the others only fail over when no connection could be
+made.
+
+Endpoint discovery is disabled by default, and partner login only asks for
+the `urls` when it's enabled.  Discovered endpoints are tried after the
+primary endpoint and ahead of the fallbacks, and discovering them doesn't move
+the active endpoint, so the primary stays in use until it fails.  Only urls
+with the same scheme as the primary endpoint, a path ending in
+`/services/json`, and a host that is either in the `pandora.com` domain or
+the host of a configured endpoint, are considered to be API endpoints.
+*/
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::errors::Error;
+
+/// The path of the JSON API on each host.
+const API_PATH: &str = "/services/json";
+
+/// The domains that discovered endpoints may be hosted in.
+const DISCOVERY_DOMAINS: &[&str] = &["pandora.com"];
+
+/// Whether `error` indicates that the endpoint couldn't be reached at all,
+/// rather than that it rejected the request.  This includes timeouts, after
+/// which the endpoint may have received the request, see
+/// [`is_connect_failure()`].
+pub fn is_unreachable(error: &Error) -> bool {
+    match error {
+        Error::HttpIoError(e) => e.is_connect() || e.is_timeout(),
+        Error::RequestTimeout(_, _) => true,
+        _ => false,
+    }
+}
+
+/// Whether `error` indicates that no connection to the endpoint could be
+/// made, so that the request was never delivered, and may be resubmitted to
+/// another endpoint whether or not it's idempotent.
+pub fn is_connect_failure(error: &Error) -> bool {
+    matches!(error, Error::HttpIoError(e) if e.is_connect())
+}
+
+/// Endpoint state shared between clones of a session.
+#[derive(Debug, Default)]
+struct SharedEndpoints {
+    /// API endpoints discovered through partner login
+    discovered: Vec<url::Url>,
+    /// The endpoint requests are currently submitted to, if it isn't the
+    /// first candidate
+    active: Option<url::Url>,
+}
+
+/// The endpoints a session can submit requests to.
+///
+/// Clones of an Endpoints share the discovered endpoints and the choice of
+/// active endpoint, so that once one request fails over, requests made
+/// through other copies of the session use the new endpoint too.
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    /// The session endpoint, followed by any fallback endpoints
+    configured: Vec<url::Url>,
+    /// Whether to discover endpoints through partner login
+    discovery: bool,
+    shared: Arc<RwLock<SharedEndpoints>>,
+}
+
+impl Endpoints {
+    /// Create a new Endpoints using `endpoint`, no fallbacks, and endpoint
+    /// discovery disabled.
+    pub fn new(endpoint: url::Url) -> Self {
+        Self {
+            configured: vec![endpoint],
+            discovery: false,
+            shared: Arc::new(RwLock::new(SharedEndpoints::default())),
+        }
+    }
+
+    /// Whether API endpoints are discovered through partner login.
+    pub fn discovery(&self) -> bool {
+        self.discovery
+    }
+
+    /// Enable or disable the discovery of API endpoints through partner
+    /// login.  Disabling it forgets any endpoints already discovered, for
+    /// every copy of the session.
+    pub fn set_discovery(&mut self, discovery: bool) -> &mut Self {
+        self.discovery = discovery;
+        if !discovery {
+            self.lock_write().discovered.clear();
+        }
+        self
+    }
+
+    /// The endpoint the session was created with.
+    pub fn primary(&self) -> &url::Url {
+        &self.configured[0]
+    }
+
+    /// Get a mutable reference to the endpoint the session was created with.
+    /// This forgets any discovered endpoints and failover, for this copy of
+    /// the session only.
+    pub fn primary_mut(&mut self) -> &mut url::Url {
+        self.detach();
+        &mut self.configured[0]
+    }
+
+    /// The fallback endpoints, in the order they are tried.
+    pub fn fallbacks(&self) -> &[url::Url] {
+        &self.configured[1..]
+    }
+
+    /// Add an endpoint to try after the primary and any earlier fallbacks.
+    pub fn add_fallback(&mut self, endpoint: url::Url) -> &mut Self {
+        if !self.configured.contains(&endpoint) {
+            self.configured.push(endpoint);
+        }
+        self
+    }
+
+    /// The API endpoints discovered through partner login.
+    pub fn discovered(&self) -> Vec<url::Url> {
+        self.lock_read().discovered.clone()
+    }
+
+    /// Every endpoint requests may be submitted to, in the order they're
+    /// tried: the primary, then discovered endpoints, then the fallbacks.
+    pub fn candidates(&self) -> Vec<url::Url> {
+        let mut candidates = vec![self.primary().clone()];
+        let shared = self.lock_read();
+        for endpoint in shared.discovered.iter().chain(self.fallbacks()) {
+            if !candidates.contains(endpoint) {
+                candidates.push(endpoint.clone());
+            }
+        }
+        candidates
+    }
+
+    /// The endpoint requests are currently submitted to.
+    pub fn active(&self) -> url::Url {
+        let candidates = self.candidates();
+        self.lock_read()
+            .active
+            .as_ref()
+            .filter(|active| candidates.contains(active))
+            .unwrap_or(&candidates[0])
+            .clone()
+    }
+
+    /// Make the candidate following `unreachable` the active endpoint, and
+    /// return it.  If `unreachable` is no longer the active endpoint, because
+    /// a concurrent request already failed over, the active endpoint is left
+    /// as it is.
+    pub fn fail_over(&self, unreachable: &url::Url) -> url::Url {
+        let candidates = self.candidates();
+        let active = self.active();
+        if &active != unreachable {
+            return active;
+        }
+        let position = candidates.iter().position(|c| c == unreachable);
+        let next = candidates[position.map(|p| (p + 1) % candidates.len()).unwrap_or(0)].clone();
+        self.lock_write().active = Some(next.clone());
+        next
+    }
+
+    /// Record the API endpoints among the `urls` returned by partner login,
+    /// if endpoint discovery is enabled.  Urls are considered in the order of
+    /// their names.  The active endpoint is left as it is.
+    pub fn discover(&self, urls: &HashMap<String, String>) {
+        if !self.discovery {
+            return;
+        }
+        let mut names: Vec<&String> = urls.keys().collect();
+        names.sort();
+        let discovered: Vec<url::Url> = names
+            .into_iter()
+            .filter_map(|name| url::Url::parse(&urls[name]).ok())
+            .filter(|url| self.is_api_endpoint(url))
+            .collect();
+        if discovered.is_empty() {
+            return;
+        }
+        log::debug!("Discovered API endpoints: {:?}", discovered);
+        self.lock_write().discovered = discovered;
+    }
+
+    /// Whether a url returned by partner login may be used as an API
+    /// endpoint.
+    fn is_api_endpoint(&self, url: &url::Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return false,
+        };
+        let allowed_host = DISCOVERY_DOMAINS
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+            || self.configured.iter().any(|c| c.host_str() == Some(host));
+        allowed_host
+            && url.scheme() == self.primary().scheme()
+            && url.path().trim_end_matches('/').ends_with(API_PATH)
+    }
+
+    /// Stop sharing discovered endpoints and failover with other copies of
+    /// the session, and forget them.
+    pub(crate) fn detach(&mut self) {
+        self.shared = Arc::new(RwLock::new(SharedEndpoints::default()));
+    }
+
+    fn lock_read(&self) -> std::sync::RwLockReadGuard<'_, SharedEndpoints> {
+        self.shared.read().expect("Endpoints lock poisoned")
+    }
+
+    fn lock_write(&self) -> std::sync::RwLockWriteGuard<'_, SharedEndpoints> {
+        self.shared.write().expect("Endpoints lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::json::mock::{MockPandora, MockServer};
+    use crate::json::retry::RetryPolicy;
+    use crate::json::station::delete_station;
+    use crate::json::{test::check_licensing, PandoraSession, Partner};
+
+    fn url(s: &str) -> url::Url {
+        url::Url::parse(s).expect("Invalid test url")
+    }
+
+    #[test]
+    fn endpoint_order_test() {
+        let mut endpoints = Endpoints::new(url("https://tuner.pandora.com/services/json"));
+        endpoints.add_fallback(url("https://internal-tuner.pandora.com/services/json"));
+        let copy = endpoints.clone();
+
+        let next = endpoints.fail_over(&url("https://tuner.pandora.com/services/json"));
+        assert_eq!(next.host_str(), Some("internal-tuner.pandora.com"));
+        // A stale failure doesn't move the active endpoint again
+        let next = copy.fail_over(&url("https://tuner.pandora.com/services/json"));
+        assert_eq!(next.host_str(), Some("internal-tuner.pandora.com"));
+        assert_eq!(copy.active(), next);
+
+        let urls = HashMap::from([
+            (
+                "autoComplete".to_string(),
+                "https://autocomplete.pandora.com/search".to_string(),
+            ),
+            (
+                "tuner".to_string(),
+                "https://tuner2.pandora.com/services/json/".to_string(),
+            ),
+            (
+                "insecure".to_string(),
+                "http://tuner3.pandora.com/services/json/".to_string(),
+            ),
+            (
+                "elsewhere".to_string(),
+                "https://tuner.pandora.com.example.net/services/json/".to_string(),
+            ),
+        ]);
+        // Discovery is opt-in
+        endpoints.discover(&urls);
+        assert!(copy.discovered().is_empty());
+
+        endpoints.set_discovery(true);
+        endpoints.discover(&urls);
+        let hosts = |endpoints: &Endpoints| -> Vec<String> {
+            endpoints
+                .candidates()
+                .iter()
+                .filter_map(|c| c.host_str().map(String::from))
+                .collect()
+        };
+        assert_eq!(
+            hosts(&copy),
+            [
+                "tuner.pandora.com",
+                "tuner2.pandora.com",
+                "internal-tuner.pandora.com"
+            ]
+        );
+        // Discovery doesn't move the active endpoint
+        assert_eq!(copy.active().host_str(), Some("internal-tuner.pandora.com"));
+
+        // Replacing the primary endpoint forgets discovery for that copy only
+        endpoints.primary_mut().set_host(Some("localhost")).unwrap();
+        assert_eq!(endpoints.active().host_str(), Some("localhost"));
+        assert_eq!(hosts(&endpoints).len(), 2);
+        assert_eq!(copy.active().host_str(), Some("internal-tuner.pandora.com"));
+
+        // Until it fails, the primary stays active
+        let fresh = Endpoints::new(url("https://tuner.pandora.com/services/json"));
+        let mut fresh_copy = fresh.clone();
+        fresh_copy.set_discovery(true).discover(&urls);
+        assert_eq!(fresh.active().host_str(), Some("tuner.pandora.com"));
+        let next = fresh.fail_over(&url("https://tuner.pandora.com/services/json"));
+        assert_eq!(next.host_str(), Some("tuner2.pandora.com"));
+    }
+
+    /// An endpoint url on a local port with nothing listening on it.
+    async fn unreachable_endpoint() -> String {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .expect("Failed binding local port");
+        let address = listener.local_addr().expect("Failed getting local address");
+        drop(listener);
+        format!("http://{}/services/json", address)
+    }
+
+    #[tokio::test]
+    async fn failover_test() {
+        let mock = Arc::new(MockPandora::new());
+        let server = MockServer::start(mock.clone())
+            .await
+            .expect("Failed starting mock Pandora server");
+        let partner = Partner::default();
+        let unreachable = unreachable_endpoint().await;
+        let mut session = PandoraSession::new(None, &partner, &unreachable);
+        session
+            .set_retry_policy(RetryPolicy::never())
+            .add_fallback_endpoint(server.endpoint());
+
+        let licensing = check_licensing(&session)
+            .await
+            .expect("Failed to fail over to reachable endpoint");
+        assert!(licensing.is_allowed);
+        assert_eq!(
+            session.endpoints().active().as_str(),
+            server.endpoint().as_str()
+        );
+
+        // Partner login can add endpoints on known hosts to fail over to
+        let other_mock = Arc::new(MockPandora::new());
+        let other = MockServer::start(other_mock.clone())
+            .await
+            .expect("Failed starting mock Pandora server");
+        mock.add_partner_url("tuner", &other.endpoint());
+        mock.add_partner_url("untrusted", "http://tuner.example.com/services/json");
+        partner.login(&session).await.expect("Failed partner login");
+        assert!(session.endpoints().discovered().is_empty());
+
+        session.set_endpoint_discovery(true);
+        partner.login(&session).await.expect("Failed partner login");
+        assert_eq!(
+            session.endpoints().discovered(),
+            vec![url::Url::parse(&other.endpoint()).expect("Invalid mock endpoint")]
+        );
+        assert_eq!(
+            session.endpoints().active().as_str(),
+            server.endpoint().as_str()
+        );
+
+        // Once the active endpoint fails too, the discovered one takes over
+        let active = session.endpoints().active();
+        assert_eq!(session.endpoints().fail_over(&active).as_str(), unreachable);
+        let _licensing = check_licensing(&session)
+            .await
+            .expect("Failed checking licensing on discovered endpoint");
+        assert_eq!(
+            mock.method_calls(),
+            vec![
+                "test.checkLicensing",
+                "auth.partnerLogin",
+                "auth.partnerLogin"
+            ]
+        );
+        assert_eq!(other_mock.method_calls(), vec!["test.checkLicensing"]);
+    }
+
+    #[tokio::test]
+    async fn timeout_failover_test() {
+        let mock = Arc::new(MockPandora::new());
+        let server = MockServer::start(mock.clone())
+            .await
+            .expect("Failed starting mock Pandora server");
+        // Connections are accepted into the backlog, but never answered
+        let hanging = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .expect("Failed binding local port");
+        let hanging_endpoint = format!(
+            "http://{}/services/json",
+            hanging.local_addr().expect("Failed getting local address")
+        );
+        let partner = Partner::default();
+        let mut session = PandoraSession::new(None, &partner, &hanging_endpoint);
+        let timeout = std::time::Duration::from_millis(200);
+        session
+            .set_retry_policy(RetryPolicy::never())
+            .set_method_timeout("station.deleteStation", timeout)
+            .set_method_timeout("test.checkLicensing", timeout)
+            .add_fallback_endpoint(server.endpoint());
+
+        // The endpoint may have received a request that timed out, so one
+        // that isn't idempotent isn't submitted again elsewhere
+        match delete_station(&session, "st1").await {
+            Err(Error::RequestTimeout(method, _)) => assert_eq!(method, "station.deleteStation"),
+            res => panic!("Unexpected result of timed out request: {:?}", res),
+        }
+        assert!(mock.method_calls().is_empty());
+        assert_eq!(session.endpoints().active().as_str(), hanging_endpoint);
+
+        let licensing = check_licensing(&session)
+            .await
+            .expect("Failed to fail over from endpoint that timed out");
+        assert!(licensing.is_allowed);
+        assert_eq!(mock.method_calls(), vec!["test.checkLicensing"]);
+        assert_eq!(
+            session.endpoints().active().as_str(),
+            server.endpoint().as_str()
+        );
+    }
+}
@@ -0,0 +1,225 @@
+/*!
+A session wrapper that transparently re-authenticates when the session tokens
+expire.
+
+[`PandoraJsonApiRequest::response()`] clears the session tokens when Pandora
+reports that they're no longer valid (`InvalidAuthToken`), or that the
+session's time base has drifted (`InsufficientConnectivity`) and re-syncing it
+didn't help.  A [`ManagedSession`] holds on to the partner and user
+credentials, so that when either of those errors occurs it can redo the
+partner and user login, and then retry the original request once.
+*/
+// SPDX-License-Identifier: MIT
+
+use crate::errors::Error;
+use crate::json::auth::{user_login, UserLoginResponse};
+use crate::json::{PandoraJsonApiRequest, PandoraSession, Partner};
+use crate::secret::Secret;
+
+/// A PandoraSession bundled with the partner and user credentials needed to
+/// log it back in whenever its tokens expire.
+#[derive(Debug, Clone)]
+pub struct ManagedSession {
+    session: PandoraSession,
+    partner: Partner,
+    username: String,
+    password: Secret,
+}
+
+impl ManagedSession {
+    /// Create a new ManagedSession that submits requests using a session
+    /// initialized from `partner`, and logs in as the user `username`.
+    ///
+    /// No requests are made until the first call to `login()` or
+    /// `response()`.
+    pub fn new(partner: Partner, username: &str, password: &str) -> Self {
+        Self::with_session(partner.init_session(), partner, username, password)
+    }
+
+    /// Create a new ManagedSession wrapping an existing `session`.  If the
+    /// session already holds valid tokens, they will continue to be used
+    /// until they expire.
+    pub fn with_session(
+        session: PandoraSession,
+        partner: Partner,
+        username: &str,
+        password: &str,
+    ) -> Self {
+        Self {
+            session,
+            partner,
+            username: username.to_string(),
+            password: Secret::from(password),
+        }
+    }
+
+    /// Get a reference to the wrapped session.
+    pub fn session(&self) -> &PandoraSession {
+        &self.session
+    }
+
+    /// Get a mutable reference to the wrapped session, for making calls that
+    /// shouldn't be automatically retried.
+    pub fn session_mut(&mut self) -> &mut PandoraSession {
+        &mut self.session
+    }
+
+    /// Unwrap the session, discarding the stored credentials.
+    pub fn into_session(self) -> PandoraSession {
+        self.session
+    }
+
+    /// Get a reference to the partner used for partner login.
+    pub fn partner(&self) -> &Partner {
+        &self.partner
+    }
+
+    /// Get the username used for user login.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Whether the wrapped session currently holds user tokens.
+    pub fn is_logged_in(&self) -> bool {
+        let tokens = self.session.session_tokens();
+        tokens.partner_token.is_some() && tokens.user_token.is_some()
+    }
+
+    /// Perform partner and user login, replacing any tokens already held by
+    /// the session.
+    pub async fn login(&self) -> Result<UserLoginResponse, Error> {
+        self.session.session_tokens_mut().clear_user_tokens();
+        self.partner.login(&self.session).await?;
+        user_login(&self.session, &self.username, self.password.expose_secret()).await
+    }
+
+    /// Submit `request`, logging in first if the session doesn't hold user
+    /// tokens yet.  If the request fails because the session tokens are no
+    /// longer accepted, log back in and retry the request once.
+    pub async fn response<T>(&self, request: &T) -> Result<T::Response, Error>
+    where
+        T: PandoraJsonApiRequest<Error = Error> + Sync,
+    {
+        if !self.is_logged_in() {
+            self.login().await?;
+        }
+
+        match request.response(&self.session).await {
+            Err(e) if e.requires_reauth() => {
+                log::debug!(
+                    "Session tokens rejected for {}, logging in again: {}",
+                    request.get_method(),
+                    e
+                );
+                self.login().await?;
+                request.response(&self.session).await
+            }
+            res => res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::json::errors::JsonErrorKind;
+    use crate::json::mock::{MockFailure, MockPandora};
+    use crate::json::retry::RetryPolicy;
+    use crate::json::user::GetStationList;
+
+    fn managed_session(mock: &Arc<MockPandora>) -> ManagedSession {
+        let partner = Partner::default();
+        let mut session = partner.init_session_with_transport(mock.clone());
+        // Recover through logging back in, rather than retries
+        session.set_retry_policy(RetryPolicy::never());
+        ManagedSession::with_session(
+            session,
+            partner,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+    }
+
+    #[tokio::test]
+    async fn managed_session_relogin_test() {
+        let mock = Arc::new(MockPandora::new());
+        let session = managed_session(&mock);
+
+        // Logs in on first use
+        let _stations = session
+            .response(&GetStationList::new())
+            .await
+            .expect("Failed getting station list through managed session");
+        assert!(session.is_logged_in());
+        assert_eq!(
+            mock.method_calls(),
+            vec!["auth.partnerLogin", "auth.userLogin", "user.getStationList"]
+        );
+
+        // Expired tokens
+        mock.expire_tokens();
+        let _stations = session
+            .response(&GetStationList::new())
+            .await
+            .expect("Failed recovering from expired session tokens");
+
+        // Server time drift only requires re-syncing the time base
+        mock.set_clock_offset(3600);
+        let _stations = session
+            .response(&GetStationList::new())
+            .await
+            .expect("Failed recovering from sync time drift");
+        assert_eq!(
+            mock.method_calls()[3..],
+            [
+                "user.getStationList",
+                "auth.partnerLogin",
+                "auth.userLogin",
+                "user.getStationList",
+                "user.getStationList",
+                "auth.partnerLogin",
+                "user.getStationList"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn managed_session_retry_once_test() {
+        let mock = Arc::new(MockPandora::new());
+        let session = managed_session(&mock);
+        session.login().await.expect("Failed managed session login");
+
+        // Only a single retry is attempted
+        mock.fail_next_call("user.getStationList", MockFailure::Api(1001));
+        mock.fail_next_call("user.getStationList", MockFailure::Api(1001));
+        match session.response(&GetStationList::new()).await {
+            Err(Error::PandoraJsonRequestError(e)) => {
+                assert_eq!(e.kind(), JsonErrorKind::InvalidAuthToken)
+            }
+            res => panic!("Unexpected station list result: {:?}", res),
+        }
+
+        // Other errors aren't retried
+        mock.fail_next_call("user.getStationList", MockFailure::Api(1006));
+        match session.response(&GetStationList::new()).await {
+            Err(Error::PandoraJsonRequestError(e)) => {
+                assert_eq!(e.kind(), JsonErrorKind::StationDoesNotExist)
+            }
+            res => panic!("Unexpected station list result: {:?}", res),
+        }
+        assert_eq!(
+            mock.method_calls()[2..],
+            [
+                "user.getStationList",
+                "auth.partnerLogin",
+                "auth.userLogin",
+                "user.getStationList",
+                "auth.partnerLogin",
+                "auth.userLogin",
+                "user.getStationList"
+            ]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+/*!
+Hooks for observing or modifying API calls as they pass through a
+[`PandoraSession`](crate::json::PandoraSession).
+
+Middleware registered on a session with `PandoraSession::add_middleware()` is
+called at each stage of every request:
+
+1. [`Middleware::on_method()`] with the name of the API method being called,
+   once per request; the resulting name is used for every attempt, and
+   determines its rate limit, retries and timeout,
+2. [`Middleware::on_request_json()`] with the plaintext json body, after the
+   session tokens are added but before it is encrypted,
+3. [`Middleware::on_request_url()`] with the final url, including the query
+   arguments,
+4. [`Middleware::on_response_body()`] with the raw response from the server,
+5. [`Middleware::on_response()`] with the parsed response status, before the
+   result is converted to the response type of the request.
+
+Request stages call each middleware in the order it was registered, and
+response stages call them in the reverse order, so that the first middleware
+registered is the closest to the caller.
+
+Each submission of a request is given a call id, unique within the process,
+which is passed to every stage from `on_request_json()` on, so that
+middleware can match up the stages of calls that are in flight concurrently.
+A request that is retried is submitted, and given a call id, once per
+attempt.
+*/
+// SPDX-License-Identifier: MIT
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::json::redact;
+use crate::json::transport::TransportResponse;
+use crate::json::PandoraResponse;
+
+/// The call id given to the next request submitted.
+static NEXT_CALL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Allocate the call id for a new request submission.
+pub(crate) fn next_call_id() -> u64 {
+    NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A set of hooks called at each stage of every API call made through a
+/// session.  All hooks do nothing by default.
+pub trait Middleware: std::fmt::Debug + Send + Sync {
+    /// Observe or modify the name of the API method being called, e.g.
+    /// `"station.getPlaylist"`.
+    fn on_method(&self, _method: &mut String) {}
+
+    /// Observe or modify the plaintext json body of the request, before it is
+    /// encrypted.
+    fn on_request_json(&self, _method: &str, _call_id: u64, _json: &mut serde_json::Value) {}
+
+    /// Observe or modify the final url the request will be submitted to.
+    fn on_request_url(&self, _method: &str, _call_id: u64, _url: &mut url::Url) {}
+
+    /// Observe or modify the raw response to the request, before it is parsed.
+    fn on_response_body(&self, _method: &str, _call_id: u64, _response: &mut TransportResponse) {}
+
+    /// Observe or modify the parsed response, including its status, error
+    /// code and message, and the still-untyped result.
+    fn on_response(
+        &self,
+        _method: &str,
+        _call_id: u64,
+        _response: &mut PandoraResponse<serde_json::Value>,
+    ) {
+    }
+}
+
+/// Middleware that logs every stage of each API call at debug level.
+///
+/// Credentials and auth tokens are redacted from the logged request bodies,
+/// urls and response bodies, the same way they are redacted from cassettes,
+/// but response bodies may still contain account details, so this is best
+/// reserved for debugging.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingMiddleware;
+
+impl Middleware for LoggingMiddleware {
+    fn on_request_json(&self, method: &str, _call_id: u64, json: &mut serde_json::Value) {
+        let mut json = json.clone();
+        redact::redact_secrets(&mut json);
+        log::debug!("{} request body: {}", method, json);
+    }
+
+    fn on_request_url(&self, method: &str, _call_id: u64, url: &mut url::Url) {
+        log::debug!("{} request url: {}", method, redact::redact_url(url));
+    }
+
+    fn on_response_body(&self, method: &str, _call_id: u64, response: &mut TransportResponse) {
+        let body = match serde_json::from_str::<serde_json::Value>(&response.body) {
+            Ok(mut json) => {
+                redact::redact_secrets(&mut json);
+                json.to_string()
+            }
+            Err(_) => response.body.clone(),
+        };
+        log::debug!("{} response (HTTP {}): {}", method, response.status, body);
+    }
+
+    fn on_response(
+        &self,
+        method: &str,
+        _call_id: u64,
+        response: &mut PandoraResponse<serde_json::Value>,
+    ) {
+        log::debug!(
+            "{} response status: {:?} (code {:?}, message {:?})",
+            method,
+            response.stat,
+            response.code,
+            response.message
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::errors::Error;
+    use crate::json::mock::MockPandora;
+    use crate::json::ratelimit::{RateLimit, RateLimitAction, RateLimiter};
+    use crate::json::test::{check_licensing, CheckLicensingResponse};
+    use crate::json::{PandoraJsonApiRequest, PandoraStatus, Partner};
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        stages: Mutex<Vec<String>>,
+        call_ids: Mutex<Vec<u64>>,
+    }
+
+    impl Recorder {
+        fn record(&self, stage: String) {
+            self.stages.lock().unwrap().push(stage);
+        }
+
+        fn record_call(&self, call_id: u64, stage: String) {
+            self.call_ids.lock().unwrap().push(call_id);
+            self.record(stage);
+        }
+    }
+
+    impl Middleware for Recorder {
+        fn on_method(&self, method: &mut String) {
+            self.record(format!("method {method}"));
+        }
+
+        fn on_request_json(&self, method: &str, call_id: u64, json: &mut serde_json::Value) {
+            let has_sync_time = json.get("syncTime").is_some();
+            self.record_call(call_id, format!("json {method} {has_sync_time}"));
+        }
+
+        fn on_request_url(&self, method: &str, call_id: u64, url: &mut url::Url) {
+            self.record_call(call_id, format!("url {method} {}", url.query().is_some()));
+        }
+
+        fn on_response_body(&self, method: &str, call_id: u64, response: &mut TransportResponse) {
+            self.record_call(call_id, format!("body {method} {}", response.status));
+        }
+
+        fn on_response(
+            &self,
+            method: &str,
+            call_id: u64,
+            response: &mut PandoraResponse<serde_json::Value>,
+        ) {
+            self.record_call(call_id, format!("status {method} {:?}", response.stat));
+        }
+    }
+
+    /// Rewrites the result of every successful licensing check.
+    #[derive(Debug)]
+    struct Unlicensed;
+
+    impl Middleware for Unlicensed {
+        fn on_response(
+            &self,
+            method: &str,
+            _call_id: u64,
+            response: &mut PandoraResponse<serde_json::Value>,
+        ) {
+            if method == "test.checkLicensing" && response.stat == PandoraStatus::Ok {
+                response.result = Some(serde_json::json!({"isAllowed": false}));
+            }
+        }
+    }
+
+    /// A request for a method that Pandora no longer answers.
+    #[derive(Debug, serde::Serialize)]
+    struct LegacyCheckLicensing {}
+
+    impl PandoraJsonApiRequest for LegacyCheckLicensing {
+        type Response = CheckLicensingResponse;
+        type Error = Error;
+
+        fn get_method(&self) -> String {
+            "test.legacyCheckLicensing".to_string()
+        }
+    }
+
+    /// Renames the legacy licensing check to the current one.
+    #[derive(Debug)]
+    struct RenameLegacy;
+
+    impl Middleware for RenameLegacy {
+        fn on_method(&self, method: &mut String) {
+            if method == "test.legacyCheckLicensing" {
+                *method = "test.checkLicensing".to_string();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn renamed_method_test() {
+        let partner = Partner::default();
+        let mut session = partner.init_session_with_transport(Arc::new(MockPandora::new()));
+        let recorder = Arc::new(Recorder::default());
+        session
+            .add_middleware(Arc::new(RenameLegacy))
+            .add_middleware(recorder.clone())
+            .set_rate_limiter(
+                RateLimiter::unlimited()
+                    .limit(
+                        "test.checkLicensing",
+                        RateLimit::new(1, std::time::Duration::from_secs(60)),
+                    )
+                    .on_limit(RateLimitAction::Reject),
+            );
+
+        let licensing = LegacyCheckLicensing {}
+            .response(&session)
+            .await
+            .expect("Failed checking licensing with a renamed method");
+        assert!(licensing.is_allowed);
+        assert_eq!(
+            recorder.stages.lock().unwrap()[..2],
+            [
+                "method test.checkLicensing",
+                "json test.checkLicensing false"
+            ]
+        );
+
+        // The renamed method is subject to the limit of the method it was
+        // renamed to
+        let limited = LegacyCheckLicensing {}.response(&session).await;
+        match limited {
+            Err(Error::RateLimitExceeded(method, _)) => assert_eq!(method, "test.checkLicensing"),
+            res => panic!("Renamed method wasn't rate limited: {:?}", res),
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_chain_test() {
+        let partner = Partner::default();
+        let mut session = partner.init_session_with_transport(Arc::new(MockPandora::new()));
+        let recorder = Arc::new(Recorder::default());
+        session
+            .add_middleware(recorder.clone())
+            .add_middleware(Arc::new(LoggingMiddleware));
+
+        partner
+            .login(&session)
+            .await
+            .expect("Failed partner login against mock");
+        assert_eq!(
+            *recorder.stages.lock().unwrap(),
+            vec![
+                "method auth.partnerLogin",
+                "json auth.partnerLogin false",
+                "url auth.partnerLogin true",
+                "body auth.partnerLogin 200",
+                "status auth.partnerLogin Ok",
+            ]
+        );
+
+        session.add_middleware(Arc::new(Unlicensed));
+        let licensing = check_licensing(&session)
+            .await
+            .expect("Failed checking licensing restrictions");
+        assert!(!licensing.is_allowed);
+        assert_eq!(
+            recorder.stages.lock().unwrap()[5..],
+            [
+                "method test.checkLicensing",
+                "json test.checkLicensing true",
+                "url test.checkLicensing true",
+                "body test.checkLicensing 200",
+                "status test.checkLicensing Ok",
+            ]
+        );
+
+        session.clear_middleware();
+        let licensing = check_licensing(&session)
+            .await
+            .expect("Failed checking licensing restrictions");
+        assert!(licensing.is_allowed);
+        assert_eq!(recorder.stages.lock().unwrap().len(), 10);
+
+        // Every stage of a call sees the same call id, which differs from
+        // that of any other call
+        let call_ids = recorder.call_ids.lock().unwrap();
+        assert_eq!(call_ids.len(), 8);
+        assert!(call_ids[..4].iter().all(|id| *id == call_ids[0]));
+        assert!(call_ids[4..].iter().all(|id| *id == call_ids[4]));
+        assert_ne!(call_ids[0], call_ids[4]);
+    }
+}
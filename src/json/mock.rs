@@ -0,0 +1,1943 @@
+/*!
+A local stand-in for the Pandora JSON API v5 service, for offline testing.
+
+[`MockPandora`] implements enough of the protocol to exercise every supported
+method without network access: it decrypts Blowfish-encrypted request bodies
+using the [`Partner`] keys, issues an encrypted `syncTime` on partner login,
+validates the `auth_token`, `partner_id` and `user_id` query arguments, and
+serves stations, playlists, searches, feedback and bookmarks from an in-memory
+account model.
+
+It can be used directly as a [`Transport`], or served over HTTP on a local
+port with [`MockServer`].
+
+``` no_run
+# async fn example() -> Result<(), pandora_api::errors::Error> {
+use std::sync::Arc;
+
+use pandora_api::json::mock::MockPandora;
+use pandora_api::json::{auth::user_login, user::get_station_list, Partner};
+
+let partner = Partner::default();
+let session = partner.init_session_with_transport(Arc::new(MockPandora::new()));
+partner.login(&session).await?;
+user_login(
+    &session,
+    MockPandora::DEFAULT_USERNAME,
+    MockPandora::DEFAULT_PASSWORD,
+)
+.await?;
+let stations = get_station_list(&session).await?;
+# Ok(())
+# }
+```
+*/
+// SPDX-License-Identifier: MIT
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use chrono::{Datelike, Timelike};
+use serde_json::{json, Value};
+
+use crate::errors::Error;
+use crate::json::crypt;
+use crate::json::transport::{Transport, TransportResponse};
+use crate::json::Partner;
+
+/// How far (in seconds) the syncTime submitted with a request may stray from
+/// the mock server clock before the request is rejected with
+/// INSUFFICIENT_CONNECTIVITY.
+const SYNC_TIME_TOLERANCE: i64 = 300;
+
+/// Methods that are called before the user has logged in, and so are
+/// authenticated with the partner auth token alone.
+const PARTNER_METHODS: &[&str] = &[
+    "auth.userLogin",
+    "user.createUser",
+    "user.emailPassword",
+    "user.validateUsername",
+];
+
+/// A failure to inject into the response to the next request received by
+/// the mock service.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockFailure {
+    /// Answer with a Pandora API error with the given code.
+    Api(u32),
+    /// Answer with the given HTTP status and a non-JSON body.
+    Http(u16),
+}
+
+/// A track in the mock music catalog.
+#[derive(Debug, Clone)]
+struct CatalogSong {
+    music_token: &'static str,
+    song_name: &'static str,
+    artist_token: &'static str,
+    album_name: &'static str,
+    track_length: u32,
+    track_gain: &'static str,
+}
+
+/// An artist in the mock music catalog.
+#[derive(Debug, Clone)]
+struct CatalogArtist {
+    music_token: &'static str,
+    artist_name: &'static str,
+}
+
+/// A genre station in the mock music catalog.
+#[derive(Debug, Clone)]
+struct CatalogGenre {
+    music_token: &'static str,
+    station_name: &'static str,
+    category_name: &'static str,
+    artist_tokens: &'static [&'static str],
+}
+
+const ARTISTS: &[CatalogArtist] = &[
+    CatalogArtist {
+        music_token: "R1119",
+        artist_name: "INXS",
+    },
+    CatalogArtist {
+        music_token: "R271371",
+        artist_name: "Panic! At the Disco",
+    },
+    CatalogArtist {
+        music_token: "R2580",
+        artist_name: "Tori Amos",
+    },
+    CatalogArtist {
+        music_token: "R3461",
+        artist_name: "Radiohead",
+    },
+    CatalogArtist {
+        music_token: "R130360",
+        artist_name: "Cannabich, Christian",
+    },
+    CatalogArtist {
+        music_token: "R278544",
+        artist_name: "Wallis Bird",
+    },
+    CatalogArtist {
+        music_token: "R175304",
+        artist_name: "Encore",
+    },
+    CatalogArtist {
+        music_token: "R4015",
+        artist_name: "Foo Fighters",
+    },
+];
+
+const SONGS: &[CatalogSong] = &[
+    CatalogSong {
+        music_token: "S1508963",
+        song_name: "Need You Tonight",
+        artist_token: "R1119",
+        album_name: "Kick",
+        track_length: 181,
+        track_gain: "-4.21",
+    },
+    CatalogSong {
+        music_token: "S1508964",
+        song_name: "Never Tear Us Apart",
+        artist_token: "R1119",
+        album_name: "Kick",
+        track_length: 185,
+        track_gain: "-2.02",
+    },
+    CatalogSong {
+        music_token: "S3066741",
+        song_name: "High Hopes",
+        artist_token: "R271371",
+        album_name: "Pray for the Wicked",
+        track_length: 190,
+        track_gain: "-8.47",
+    },
+    CatalogSong {
+        music_token: "S346672",
+        song_name: "Winter",
+        artist_token: "R2580",
+        album_name: "Little Earthquakes",
+        track_length: 342,
+        track_gain: "1.35",
+    },
+    CatalogSong {
+        music_token: "S346673",
+        song_name: "Cornflake Girl",
+        artist_token: "R2580",
+        album_name: "Under the Pink",
+        track_length: 305,
+        track_gain: "-1.10",
+    },
+    CatalogSong {
+        music_token: "S452112",
+        song_name: "Talk Show Host",
+        artist_token: "R3461",
+        album_name: "Romeo + Juliet",
+        track_length: 251,
+        track_gain: "0.66",
+    },
+    CatalogSong {
+        music_token: "S452113",
+        song_name: "Karma Police",
+        artist_token: "R3461",
+        album_name: "OK Computer",
+        track_length: 264,
+        track_gain: "-3.73",
+    },
+    CatalogSong {
+        music_token: "S2894329",
+        song_name: "Symphony In G Major",
+        artist_token: "R130360",
+        album_name: "London Mozart Players, Christian Cannabich: Symphonies",
+        track_length: 277,
+        track_gain: "10.09",
+    },
+    CatalogSong {
+        music_token: "S1143982",
+        song_name: "Encore",
+        artist_token: "R175304",
+        album_name: "Encore",
+        track_length: 214,
+        track_gain: "-5.50",
+    },
+    CatalogSong {
+        music_token: "S1781202",
+        song_name: "Everlong",
+        artist_token: "R4015",
+        album_name: "The Colour and the Shape",
+        track_length: 250,
+        track_gain: "-7.94",
+    },
+];
+
+const GENRES: &[CatalogGenre] = &[
+    CatalogGenre {
+        music_token: "G165",
+        station_name: "90s Alternative",
+        category_name: "Alternative",
+        artist_tokens: &["R3461", "R2580", "R4015"],
+    },
+    CatalogGenre {
+        music_token: "G123",
+        station_name: "Today's Encore",
+        category_name: "Pop",
+        artist_tokens: &["R175304", "R271371", "R1119"],
+    },
+    CatalogGenre {
+        music_token: "G210",
+        station_name: "Classical Symphonies",
+        category_name: "Classical",
+        artist_tokens: &["R130360"],
+    },
+];
+
+fn find_artist(music_token: &str) -> Option<&'static CatalogArtist> {
+    ARTISTS.iter().find(|a| a.music_token == music_token)
+}
+
+fn find_song(music_token: &str) -> Option<&'static CatalogSong> {
+    SONGS.iter().find(|s| s.music_token == music_token)
+}
+
+fn find_genre(music_token: &str) -> Option<&'static CatalogGenre> {
+    GENRES.iter().find(|g| g.music_token == music_token)
+}
+
+fn artist_name(artist_token: &str) -> &'static str {
+    find_artist(artist_token)
+        .map(|a| a.artist_name)
+        .unwrap_or("")
+}
+
+fn art_url(music_token: &str) -> String {
+    format!("http://mock.pandora.invalid/images/{music_token}_500W_500H.jpg")
+}
+
+/// Generate a Pandora-style timestamp object for a millisecond epoch time.
+fn timestamp(time_ms: i64) -> Value {
+    let dt = chrono::DateTime::from_timestamp_millis(time_ms).unwrap_or_default();
+    json!({
+        "date": dt.day(),
+        "day": dt.weekday().num_days_from_sunday(),
+        "hours": dt.hour(),
+        "minutes": dt.minute(),
+        "month": dt.month0(),
+        "nanos": dt.timestamp_subsec_nanos(),
+        "seconds": dt.second(),
+        "time": time_ms,
+        "timezoneOffset": 0,
+        "year": dt.year() - 1900
+    })
+}
+
+/// The kind of music item used as a station seed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SeedKind {
+    Artist,
+    Song,
+    Genre,
+}
+
+#[derive(Debug, Clone)]
+struct MockSeed {
+    seed_id: String,
+    kind: SeedKind,
+    music_token: String,
+}
+
+#[derive(Debug, Clone)]
+struct MockFeedback {
+    feedback_id: String,
+    music_token: String,
+    is_positive: bool,
+    date_created: i64,
+}
+
+#[derive(Debug, Clone)]
+struct MockStation {
+    station_token: String,
+    station_name: String,
+    date_created: i64,
+    is_quick_mix: bool,
+    quick_mix_station_ids: Vec<String>,
+    seeds: Vec<MockSeed>,
+    feedback: Vec<MockFeedback>,
+}
+
+#[derive(Debug, Clone)]
+struct MockBookmark {
+    bookmark_token: String,
+    music_token: String,
+    is_song: bool,
+    date_created: i64,
+}
+
+#[derive(Debug, Clone)]
+struct MockAccount {
+    user_id: String,
+    username: String,
+    password: String,
+    stations: Vec<MockStation>,
+    bookmarks: Vec<MockBookmark>,
+    settings: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug)]
+struct MockState {
+    clock_offset: i64,
+    next_id: u64,
+    accounts: Vec<MockAccount>,
+    /// Partner auth tokens, mapped to the index of the partner they were issued to
+    partner_tokens: HashMap<String, usize>,
+    /// User auth tokens, mapped to the partner index and user id they were issued to
+    user_tokens: HashMap<String, (usize, String)>,
+    /// Track tokens issued in playlists, mapped to the song they refer to
+    tracks: HashMap<String, String>,
+    ad_tracking_tokens: HashMap<String, Vec<String>>,
+    /// Failures to inject, optionally restricted to a single method
+    failures: VecDeque<(Option<String>, MockFailure)>,
+    method_calls: Vec<String>,
+    /// The urls returned by partner logins that set includeUrls
+    partner_urls: serde_json::Map<String, Value>,
+}
+
+/// An API error reported by the mock service: the error code and message.
+type MockError = (u32, String);
+
+fn fail<T>(code: u32, message: &str) -> Result<T, MockError> {
+    Err((code, message.to_string()))
+}
+
+/// A local, in-memory stand-in for the Pandora JSON API v5 service.
+pub struct MockPandora {
+    partners: Vec<Partner>,
+    state: Mutex<MockState>,
+}
+
+impl std::fmt::Debug for MockPandora {
+    /// The state is left out, as it holds the issued auth tokens and account
+    /// passwords.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockPandora")
+            .field("partners", &self.partners)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MockPandora {
+    /// Username of the account registered with every new MockPandora.
+    pub const DEFAULT_USERNAME: &'static str = "user@example.com";
+    /// Password of the account registered with every new MockPandora.
+    pub const DEFAULT_PASSWORD: &'static str = "example";
+
+    /// Create a new mock service that accepts all the known partner
+    /// credentials, with a single registered account using
+    /// `DEFAULT_USERNAME` and `DEFAULT_PASSWORD`.
+    pub fn new() -> Self {
+        let mock = Self {
+            partners: vec![
+                Partner::new_android(),
+                Partner::new_ios(),
+                Partner::new_palm(),
+                Partner::new_windows_mobile(),
+                Partner::new_desktop_air(),
+                Partner::new_vista_widget(),
+            ],
+            state: Mutex::new(MockState {
+                clock_offset: 0,
+                next_id: 1,
+                accounts: Vec::new(),
+                partner_tokens: HashMap::new(),
+                user_tokens: HashMap::new(),
+                tracks: HashMap::new(),
+                ad_tracking_tokens: HashMap::new(),
+                failures: VecDeque::new(),
+                method_calls: Vec::new(),
+                partner_urls: serde_json::Map::from_iter([(
+                    "autoComplete".to_string(),
+                    json!("http://autocomplete.pandora.com/search"),
+                )]),
+            }),
+        };
+        mock.add_account(Self::DEFAULT_USERNAME, Self::DEFAULT_PASSWORD);
+        mock
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().expect("MockPandora state lock poisoned")
+    }
+
+    /// Register an additional account with the mock service.  New accounts
+    /// start out with a couple of stations and a QuickMix.
+    pub fn add_account(&self, username: &str, password: &str) {
+        let mut state = self.lock();
+        let now = state.now_ms();
+        let user_id = state.next_token("");
+        let mut stations = Vec::new();
+        for (name, artist, song) in [
+            ("Tori Amos Radio", "R2580", Some("S346672")),
+            ("Radiohead Radio", "R3461", None),
+        ] {
+            let mut seeds = vec![MockSeed {
+                seed_id: state.next_token(""),
+                kind: SeedKind::Artist,
+                music_token: artist.to_string(),
+            }];
+            if let Some(song) = song {
+                seeds.push(MockSeed {
+                    seed_id: state.next_token(""),
+                    kind: SeedKind::Song,
+                    music_token: song.to_string(),
+                });
+            }
+            stations.push(MockStation {
+                station_token: state.next_token(""),
+                station_name: name.to_string(),
+                date_created: now,
+                is_quick_mix: false,
+                quick_mix_station_ids: Vec::new(),
+                seeds,
+                feedback: Vec::new(),
+            });
+        }
+        let quick_mix_station_ids = stations.iter().map(|s| s.station_token.clone()).collect();
+        stations.insert(
+            0,
+            MockStation {
+                station_token: state.next_token(""),
+                station_name: "QuickMix".to_string(),
+                date_created: now,
+                is_quick_mix: true,
+                quick_mix_station_ids,
+                seeds: Vec::new(),
+                feedback: Vec::new(),
+            },
+        );
+
+        let settings = json!({
+            "gender": "Female",
+            "birthYear": 1985,
+            "zipCode": "94612",
+            "isProfilePrivate": false,
+            "enableComments": true,
+            "emailOptIn": false,
+            "emailComments": false,
+            "emailNewFollowers": false,
+            "isExplicitContentFilterEnabled": false,
+            "isExplicitContentFilterPINProtected": false,
+            "facebookAutoShareEnabled": false,
+            "autoShareTrackPlay": false,
+            "autoShareLikes": false,
+            "autoShareFollows": false,
+        });
+        state.accounts.push(MockAccount {
+            user_id,
+            username: username.to_string(),
+            password: password.to_string(),
+            stations,
+            bookmarks: Vec::new(),
+            settings: settings.as_object().cloned().unwrap_or_default(),
+        });
+    }
+
+    /// Skew the mock server clock by `seconds` relative to the local clock.
+    /// Requests carrying a syncTime that strays too far from the server clock
+    /// are rejected with INSUFFICIENT_CONNECTIVITY.
+    pub fn set_clock_offset(&self, seconds: i64) {
+        self.lock().clock_offset = seconds;
+    }
+
+    /// Invalidate every partner and user auth token issued so far, as happens
+    /// when a login session expires.
+    pub fn expire_tokens(&self) {
+        let mut state = self.lock();
+        state.partner_tokens.clear();
+        state.user_tokens.clear();
+    }
+
+    /// Answer the next request received with the given failure, instead of
+    /// processing it.  Multiple failures are used in the order they were added.
+    pub fn fail_next(&self, failure: MockFailure) {
+        self.lock().failures.push_back((None, failure));
+    }
+
+    /// Answer the next request for `method` with the given failure, instead
+    /// of processing it.  Requests for other methods are unaffected.
+    pub fn fail_next_call(&self, method: &str, failure: MockFailure) {
+        self.lock()
+            .failures
+            .push_back((Some(method.to_string()), failure));
+    }
+
+    /// Add a url to those returned by partner logins that set includeUrls.
+    pub fn add_partner_url(&self, name: &str, url: &str) {
+        self.lock()
+            .partner_urls
+            .insert(name.to_string(), json!(url));
+    }
+
+    /// The method names of every request received so far, in order.
+    pub fn method_calls(&self) -> Vec<String> {
+        self.lock().method_calls.clone()
+    }
+
+    /// Process a request for the given url and body, and generate the
+    /// response the service would send.
+    pub fn handle(&self, url: &url::Url, body: &str) -> TransportResponse {
+        let mut state = self.lock();
+        let args: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        if let Some(method) = args.get("method") {
+            state.method_calls.push(method.clone());
+        }
+
+        let failure = state
+            .failures
+            .iter()
+            .position(|(m, _)| m.is_none() || m.as_ref() == args.get("method"))
+            .and_then(|idx| state.failures.remove(idx))
+            .map(|(_, failure)| failure);
+        let result = match failure {
+            Some(MockFailure::Http(status)) => {
+                return TransportResponse::new(status, "Service Unavailable")
+            }
+            Some(MockFailure::Api(code)) => fail(code, "Injected failure"),
+            None => state.dispatch(&self.partners, &args, body),
+        };
+
+        let response = match result {
+            Ok(result) => json!({"stat": "ok", "result": result}),
+            Err((code, message)) => json!({"stat": "fail", "code": code, "message": message}),
+        };
+        TransportResponse::ok(&response.to_string())
+    }
+}
+
+impl Default for MockPandora {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for MockPandora {
+    async fn post(&self, url: url::Url, body: String) -> Result<TransportResponse, Error> {
+        Ok(self.handle(&url, &body))
+    }
+}
+
+impl MockState {
+    /// The server clock, in seconds since the unix epoch.
+    fn now(&self) -> i64 {
+        chrono::Utc::now().timestamp() + self.clock_offset
+    }
+
+    /// The server clock, in milliseconds since the unix epoch.
+    fn now_ms(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis() + self.clock_offset * 1000
+    }
+
+    /// Generate a new unique identifier, with an optional prefix.
+    fn next_token(&mut self, prefix: &str) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+        format!("{prefix}{}", 4_000_000_000_000 + id)
+    }
+
+    fn account(&self, user_id: &str) -> Result<&MockAccount, MockError> {
+        match self.accounts.iter().find(|a| a.user_id == user_id) {
+            Some(account) => Ok(account),
+            None => fail(1001, "Invalid auth token"),
+        }
+    }
+
+    fn account_mut(&mut self, user_id: &str) -> Result<&mut MockAccount, MockError> {
+        match self.accounts.iter_mut().find(|a| a.user_id == user_id) {
+            Some(account) => Ok(account),
+            None => fail(1001, "Invalid auth token"),
+        }
+    }
+
+    fn station_mut(
+        &mut self,
+        user_id: &str,
+        station_token: &str,
+    ) -> Result<&mut MockStation, MockError> {
+        match self
+            .account_mut(user_id)?
+            .stations
+            .iter_mut()
+            .find(|s| s.station_token == station_token)
+        {
+            Some(station) => Ok(station),
+            None => fail(1006, "Station does not exist"),
+        }
+    }
+
+    /// The catalog song a track token issued in a playlist refers to.
+    fn track(&self, track_token: &str) -> Result<&'static CatalogSong, MockError> {
+        match self.tracks.get(track_token).and_then(|t| find_song(t)) {
+            Some(song) => Ok(song),
+            None => fail(10, "Invalid track token"),
+        }
+    }
+
+    fn dispatch(
+        &mut self,
+        partners: &[Partner],
+        args: &HashMap<String, String>,
+        body: &str,
+    ) -> Result<Value, MockError> {
+        let method = match args.get("method") {
+            Some(method) => method.as_str(),
+            None => return fail(2, "Missing method"),
+        };
+
+        match method {
+            "test.checkLicensing" => return Ok(json!({"isAllowed": true})),
+            "auth.partnerLogin" => {
+                let request: Value = serde_json::from_str(body)
+                    .or_else(|_| fail(8, "Unable to parse request body"))?;
+                return self.partner_login(partners, &request);
+            }
+            _ => (),
+        }
+
+        let auth_token = match args.get("auth_token") {
+            Some(auth_token) => auth_token,
+            None => return fail(3, "Missing auth_token"),
+        };
+        let partner_idx = match args.get("partner_id").map(|id| id.parse::<usize>()) {
+            Some(Ok(id)) if (42..42 + partners.len()).contains(&id) => id - 42,
+            Some(_) => return fail(1010, "Partner not authorized"),
+            None => return fail(4, "Missing partner_id"),
+        };
+
+        let request = decode_body(&partners[partner_idx], body)?;
+        match request.get("syncTime").and_then(Value::as_i64) {
+            Some(sync_time) if (sync_time - self.now()).abs() <= SYNC_TIME_TOLERANCE => (),
+            Some(_) => return fail(13, "Insufficient connectivity (bad sync time?)"),
+            None => return fail(9, "Missing syncTime"),
+        }
+
+        if PARTNER_METHODS.contains(&method) {
+            let token_partner = self
+                .partner_tokens
+                .get(auth_token)
+                .copied()
+                .or_else(|| self.user_tokens.get(auth_token).map(|(p, _)| *p));
+            if token_partner != Some(partner_idx) {
+                return fail(1001, "Invalid auth token");
+            }
+            return match method {
+                "auth.userLogin" => self.user_login(partner_idx, &request),
+                "user.createUser" => fail(1027, "Invalid country code."),
+                "user.emailPassword" => self.email_password(&request),
+                "user.validateUsername" => self.validate_username(&request),
+                _ => unreachable!("Unhandled partner method"),
+            };
+        }
+
+        let user_id = match args.get("user_id") {
+            Some(user_id) => user_id.clone(),
+            None => return fail(5, "Missing user_id"),
+        };
+        match self.user_tokens.get(auth_token) {
+            Some((p, u)) if *p == partner_idx && *u == user_id => (),
+            _ => return fail(1001, "Invalid auth token"),
+        }
+        self.user_method(method, &user_id, &request)
+    }
+
+    fn user_method(
+        &mut self,
+        method: &str,
+        user_id: &str,
+        request: &Value,
+    ) -> Result<Value, MockError> {
+        match method {
+            "ad.getAdMetadata" => self.get_ad_metadata(request),
+            "ad.registerAd" => self.register_ad(request),
+            "bookmark.addArtistBookmark" => self.add_bookmark(user_id, request, false),
+            "bookmark.addSongBookmark" => self.add_bookmark(user_id, request, true),
+            "bookmark.deleteArtistBookmark" | "bookmark.deleteSongBookmark" => {
+                self.delete_bookmark(user_id, request)
+            }
+            "music.getTrack" => get_track(request),
+            "music.search" => Ok(search(request)),
+            "station.addFeedback" => self.add_feedback(user_id, request),
+            "station.addMusic" => self.add_music(user_id, request),
+            "station.createStation" => self.create_station(user_id, request),
+            "station.deleteFeedback" => self.delete_feedback(user_id, request),
+            "station.deleteMusic" => self.delete_music(user_id, request),
+            "station.deleteStation" => self.delete_station(user_id, request),
+            "station.getGenreStations" => Ok(genre_stations()),
+            "station.getGenreStationsChecksum" => Ok(json!({
+                "checksum": checksum(GENRES.iter().map(|g| g.music_token))
+            })),
+            "station.getPlaylist" => self.get_playlist(user_id, request),
+            "station.getStation" => self.get_station(user_id, request),
+            "station.renameStation" => self.rename_station(user_id, request),
+            "station.shareStation" => {
+                self.station_mut(user_id, str_param(request, "stationToken")?)?;
+                Ok(json!({}))
+            }
+            "station.transformSharedStation" => {
+                self.station_mut(user_id, str_param(request, "stationToken")?)?;
+                Ok(json!({}))
+            }
+            "track.explainTrack" => self.explain_track(request),
+            "user.canSubscribe" => Ok(json!({"canSubscribe": true, "isSubscriber": false})),
+            "user.changeSettings" => self.change_settings(user_id, request),
+            "user.getBookmarks" => self.get_bookmarks(user_id),
+            "user.getSettings" => {
+                let account = self.account(user_id)?;
+                let mut settings = account.settings.clone();
+                settings.insert("username".to_string(), json!(account.username));
+                Ok(Value::Object(settings))
+            }
+            "user.getStationList" => self.get_station_list(user_id),
+            "user.getStationListChecksum" => Ok(json!({
+                "checksum": self.station_list_checksum(user_id)?
+            })),
+            "user.getUsageInfo" => Ok(json!({
+                "accountMonthlyListening": 12,
+                "deviceMonthlyListening": 3,
+                "monthlyCapHours": 320,
+                "monthlyCapWarningPercent": 85,
+                "monthlyCapWarningRepeatPercent": 10,
+                "isMonthlyPayer": false,
+                "isCapped": false,
+                "listeningTimestamp": 0
+            })),
+            "user.setQuickMix" => self.set_quick_mix(user_id, request),
+            "user.sleepSong" => {
+                self.track(str_param(request, "trackToken")?)?;
+                Ok(json!({}))
+            }
+            "user.startComplimentaryTrial" => fail(1036, "Invalid sponsor"),
+            _ => fail(0, "Service method not found"),
+        }
+    }
+
+    fn partner_login(&mut self, partners: &[Partner], request: &Value) -> Result<Value, MockError> {
+        let username = str_param(request, "username")?;
+        let password = str_param(request, "password")?;
+        let (partner_idx, partner) = match partners
+            .iter()
+            .enumerate()
+            .find(|(_, p)| p.username == username && p.password.expose_secret() == password)
+        {
+            Some(found) => found,
+            None => return fail(1002, "Invalid partner login"),
+        };
+        match request.get("version").and_then(Value::as_str) {
+            Some("5") => (),
+            _ => return fail(11, "API version not supported"),
+        }
+
+        let partner_auth_token = self.next_token("VAzrFQTtsy3BQ3K+");
+        self.partner_tokens
+            .insert(partner_auth_token.clone(), partner_idx);
+        // The first four bytes of the sync time are garbage, and are skipped
+        // by the client when decrypting it.
+        let sync_time = crypt::encrypt(
+            partner.decrypt_password.expose_secret(),
+            &format!("mock{}", self.now()),
+        )
+        .map_err(|e| (0, e.to_string()))?;
+
+        let mut response = json!({
+            "syncTime": sync_time,
+            "deviceProperties": {
+                "videoAdRefreshInterval": 900,
+                "videoAdUniqueInterval": 0,
+                "adRefreshInterval": 5,
+                "videoAdStartInterval": 180
+            },
+            "partnerAuthToken": partner_auth_token,
+            "partnerId": (42 + partner_idx).to_string(),
+            "stationSkipUnit": "hour",
+            "stationSkipLimit": 6
+        });
+        if request.get("includeUrls").and_then(Value::as_bool) == Some(true) {
+            response["urls"] = Value::Object(self.partner_urls.clone());
+        }
+        Ok(response)
+    }
+
+    fn user_login(&mut self, partner_idx: usize, request: &Value) -> Result<Value, MockError> {
+        let username = str_param(request, "username")?;
+        let password = str_param(request, "password")?;
+        let user_id = match self
+            .accounts
+            .iter()
+            .find(|a| a.username == username && a.password == password)
+        {
+            Some(account) => account.user_id.clone(),
+            None => return fail(1002, "Wrong user credentials"),
+        };
+
+        let user_auth_token = self.next_token("XXX");
+        self.user_tokens
+            .insert(user_auth_token.clone(), (partner_idx, user_id.clone()));
+        Ok(json!({
+            "stationCreationAdUrl": "http://ad.doubleclick.net/adx/pand.android/prod.createstation",
+            "hasAudioAds": true,
+            "splashScreenAdUrl": "http://ad.doubleclick.net/pfadx/pand.android/prod.welcome",
+            "videoAdUrl": "http://ad.doubleclick.net/pfadx/pand.android/prod.nowplaying",
+            "username": username,
+            "canListen": true,
+            "nowPlayingAdUrl": "http://ad.doubleclick.net/pfadx/pand.android/prod.nowplaying",
+            "userId": user_id,
+            "listeningTimeoutMinutes": "180",
+            "maxStationsAllowed": 100,
+            "listeningTimeoutAlertMsgUri": "/mobile/still_listening.vm",
+            "userProfileUrl": format!("https://www.pandora.com/login?auth_token={user_auth_token}"),
+            "minimumAdRefreshInterval": 5,
+            "userAuthToken": user_auth_token
+        }))
+    }
+
+    fn email_password(&self, request: &Value) -> Result<Value, MockError> {
+        let username = str_param(request, "username")?;
+        if self.accounts.iter().any(|a| a.username == username) {
+            Ok(json!({}))
+        } else {
+            fail(1011, "Invalid username")
+        }
+    }
+
+    fn validate_username(&self, request: &Value) -> Result<Value, MockError> {
+        let username = str_param(request, "username")?;
+        let is_valid = username
+            .split_once('@')
+            .map(|(user, domain)| !user.is_empty() && domain.contains('.'))
+            .unwrap_or(false);
+        let is_unique = !self.accounts.iter().any(|a| a.username == username);
+        Ok(json!({"isValid": is_valid, "isUnique": is_unique}))
+    }
+
+    fn change_settings(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let current_username = str_param(request, "currentUsername")?;
+        let current_password = str_param(request, "currentPassword")?;
+        let account = self.account_mut(user_id)?;
+        if account.username != current_username || account.password != current_password {
+            return fail(1012, "Invalid password");
+        }
+        match request.get("gender").and_then(Value::as_str) {
+            None | Some("Male") | Some("Female") => (),
+            Some(_) => return fail(1027, "Invalid gender."),
+        }
+        if let Some(request) = request.as_object() {
+            for (key, value) in request {
+                match key.as_str() {
+                    "newUsername" => account.username = value.as_str().unwrap_or("").to_string(),
+                    "newPassword" => account.password = value.as_str().unwrap_or("").to_string(),
+                    k if account.settings.contains_key(k) => {
+                        account.settings.insert(key.clone(), value.clone());
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(json!({}))
+    }
+
+    fn station_json(&self, station: &MockStation) -> Value {
+        json!({
+            "suppressVideoAds": false,
+            "isQuickMix": station.is_quick_mix,
+            "stationId": station.station_token,
+            "stationDetailUrl": format!("https://www.pandora.com/login?target=%2Fstations%2F{}", station.station_token),
+            "isShared": false,
+            "dateCreated": timestamp(station.date_created),
+            "stationToken": station.station_token,
+            "stationName": station.station_name,
+            "stationSharingUrl": format!("https://www.pandora.com/login?target=%2Fshare%2Fstation%2F{}", station.station_token),
+            "requiresCleanAds": false,
+            "allowRename": !station.is_quick_mix,
+            "allowAddMusic": !station.is_quick_mix,
+            "quickMixStationIds": station.quick_mix_station_ids,
+            "allowDelete": !station.is_quick_mix,
+            "allowEditDescription": !station.is_quick_mix
+        })
+    }
+
+    fn get_station_list(&self, user_id: &str) -> Result<Value, MockError> {
+        let stations: Vec<Value> = self
+            .account(user_id)?
+            .stations
+            .iter()
+            .map(|s| self.station_json(s))
+            .collect();
+        Ok(json!({
+            "stations": stations,
+            "checksum": self.station_list_checksum(user_id)?
+        }))
+    }
+
+    fn station_list_checksum(&self, user_id: &str) -> Result<String, MockError> {
+        Ok(checksum(self.account(user_id)?.stations.iter().flat_map(
+            |s| [s.station_token.as_str(), s.station_name.as_str()],
+        )))
+    }
+
+    fn create_station(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let music_token = request
+            .get("musicToken")
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        let (kind, music_token) = if music_token.is_empty() {
+            let song = self.track(str_param(request, "trackToken")?)?;
+            match request.get("musicType").and_then(Value::as_str) {
+                Some("song") => (SeedKind::Song, song.music_token),
+                _ => (SeedKind::Artist, song.artist_token),
+            }
+        } else if let Some(artist) = find_artist(music_token) {
+            (SeedKind::Artist, artist.music_token)
+        } else if let Some(song) = find_song(music_token) {
+            (SeedKind::Song, song.music_token)
+        } else if let Some(genre) = find_genre(music_token) {
+            (SeedKind::Genre, genre.music_token)
+        } else {
+            return fail(10, "Invalid music token");
+        };
+        let station_name = match kind {
+            SeedKind::Artist => format!("{} Radio", artist_name(music_token)),
+            SeedKind::Song => format!(
+                "{} Radio",
+                find_song(music_token).map(|s| s.song_name).unwrap_or("")
+            ),
+            SeedKind::Genre => find_genre(music_token)
+                .map(|g| g.station_name)
+                .unwrap_or("")
+                .to_string(),
+        };
+
+        if self.account(user_id)?.stations.len() >= 100 {
+            return fail(1005, "Station limit reached");
+        }
+        let station = MockStation {
+            station_token: self.next_token(""),
+            station_name,
+            date_created: self.now_ms(),
+            is_quick_mix: false,
+            quick_mix_station_ids: Vec::new(),
+            seeds: vec![MockSeed {
+                seed_id: self.next_token(""),
+                kind,
+                music_token: music_token.to_string(),
+            }],
+            feedback: Vec::new(),
+        };
+        let response = self.station_json(&station);
+        self.account_mut(user_id)?.stations.push(station);
+        Ok(response)
+    }
+
+    fn delete_station(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let station_token = str_param(request, "stationToken")?;
+        let account = self.account_mut(user_id)?;
+        let count = account.stations.len();
+        account
+            .stations
+            .retain(|s| s.is_quick_mix || s.station_token != station_token);
+        if account.stations.len() == count {
+            return fail(1006, "Station does not exist");
+        }
+        for station in account.stations.iter_mut() {
+            station
+                .quick_mix_station_ids
+                .retain(|id| id != station_token);
+        }
+        Ok(json!({}))
+    }
+
+    fn rename_station(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let station_name = str_param(request, "stationName")?.to_string();
+        let station = self.station_mut(user_id, str_param(request, "stationToken")?)?;
+        if station.is_quick_mix {
+            return fail(1008, "Call not allowed");
+        }
+        station.station_name = station_name;
+        Ok(json!({}))
+    }
+
+    fn set_quick_mix(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let station_ids: Vec<String> = request
+            .get("quickMixStationIds")
+            .and_then(Value::as_array)
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| id.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let account = self.account_mut(user_id)?;
+        for station_id in &station_ids {
+            if !account
+                .stations
+                .iter()
+                .any(|s| &s.station_token == station_id)
+            {
+                return fail(1006, "Station does not exist");
+            }
+        }
+        if let Some(quick_mix) = account.stations.iter_mut().find(|s| s.is_quick_mix) {
+            quick_mix.quick_mix_station_ids = station_ids;
+        }
+        Ok(json!({}))
+    }
+
+    fn get_station(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let station = self
+            .station_mut(user_id, str_param(request, "stationToken")?)?
+            .clone();
+        let mut response = self.station_json(&station);
+        response["genre"] = json!(station
+            .seeds
+            .iter()
+            .filter_map(|s| find_genre(&s.music_token))
+            .map(|g| g.category_name)
+            .collect::<Vec<&str>>());
+        response["artUrl"] = json!(art_url(&station.station_token));
+
+        if request
+            .get("includeExtendedAttributes")
+            .and_then(Value::as_bool)
+            == Some(true)
+        {
+            let seeds_of = |kind: SeedKind| station.seeds.iter().filter(move |s| s.kind == kind);
+            let songs: Vec<Value> = seeds_of(SeedKind::Song)
+                .filter_map(|seed| find_song(&seed.music_token).map(|song| (seed, song)))
+                .map(|(seed, song)| {
+                    json!({
+                        "seedId": seed.seed_id,
+                        "musicToken": song.music_token,
+                        "songName": song.song_name,
+                        "artistName": artist_name(song.artist_token),
+                        "pandoraType": "TR",
+                        "pandoraId": format!("TR:{}", &song.music_token[1..]),
+                        "artUrl": art_url(song.music_token)
+                    })
+                })
+                .collect();
+            let artists: Vec<Value> = seeds_of(SeedKind::Artist)
+                .filter_map(|seed| find_artist(&seed.music_token).map(|artist| (seed, artist)))
+                .map(|(seed, artist)| {
+                    json!({
+                        "seedId": seed.seed_id,
+                        "musicToken": artist.music_token,
+                        "artistName": artist.artist_name,
+                        "pandoraType": "AR",
+                        "pandoraId": format!("AR:{}", &artist.music_token[1..]),
+                        "artUrl": art_url(artist.music_token),
+                        "icon": {"dominantColor": "602d30", "artUrl": ""}
+                    })
+                })
+                .collect();
+            let genres: Vec<Value> = seeds_of(SeedKind::Genre)
+                .filter_map(|seed| find_genre(&seed.music_token).map(|genre| (seed, genre)))
+                .map(|(seed, genre)| {
+                    json!({
+                        "seedId": seed.seed_id,
+                        "musicToken": genre.music_token,
+                        "genreName": genre.station_name
+                    })
+                })
+                .collect();
+            response["music"] = json!({"songs": songs, "artists": artists, "genres": genres});
+
+            let feedback_of = |is_positive: bool| -> Vec<Value> {
+                station
+                    .feedback
+                    .iter()
+                    .filter(|f| f.is_positive == is_positive)
+                    .filter_map(|f| find_song(&f.music_token).map(|song| (f, song)))
+                    .map(|(f, song)| {
+                        json!({
+                            "dateCreated": timestamp(f.date_created),
+                            "albumArtUrl": art_url(song.music_token),
+                            "musicToken": song.music_token,
+                            "songName": song.song_name,
+                            "artistName": artist_name(song.artist_token),
+                            "feedbackId": f.feedback_id,
+                            "isPositive": f.is_positive
+                        })
+                    })
+                    .collect()
+            };
+            let thumbs_up = feedback_of(true);
+            let thumbs_down = feedback_of(false);
+            response["feedback"] = json!({
+                "totalThumbsUp": thumbs_up.len(),
+                "totalThumbsDown": thumbs_down.len(),
+                "thumbsUp": thumbs_up,
+                "thumbsDown": thumbs_down
+            });
+        }
+        Ok(response)
+    }
+
+    /// The catalog songs that may be played on a station, based on its seeds.
+    fn station_songs(&self, user_id: &str, station: &MockStation) -> Vec<&'static CatalogSong> {
+        let seeds: Vec<MockSeed> = if station.is_quick_mix {
+            self.account(user_id)
+                .map(|account| {
+                    account
+                        .stations
+                        .iter()
+                        .filter(|s| station.quick_mix_station_ids.contains(&s.station_token))
+                        .flat_map(|s| s.seeds.iter().cloned())
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            station.seeds.clone()
+        };
+
+        let mut artist_tokens: Vec<&str> = Vec::new();
+        let mut songs: Vec<&'static CatalogSong> = Vec::new();
+        for seed in &seeds {
+            match seed.kind {
+                SeedKind::Artist => {
+                    artist_tokens.extend(find_artist(&seed.music_token).map(|a| a.music_token))
+                }
+                SeedKind::Song => {
+                    if let Some(song) = find_song(&seed.music_token) {
+                        songs.push(song);
+                        artist_tokens.push(song.artist_token);
+                    }
+                }
+                SeedKind::Genre => artist_tokens.extend(
+                    find_genre(&seed.music_token)
+                        .iter()
+                        .flat_map(|g| g.artist_tokens.iter().copied()),
+                ),
+            }
+        }
+        for song in SONGS {
+            if artist_tokens.contains(&song.artist_token)
+                && !songs.iter().any(|s| s.music_token == song.music_token)
+            {
+                songs.push(song);
+            }
+        }
+        // Banned songs are never played again on the station
+        songs.retain(|song| {
+            !station
+                .feedback
+                .iter()
+                .any(|f| !f.is_positive && f.music_token == song.music_token)
+        });
+        songs
+    }
+
+    fn get_playlist(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let station = self
+            .station_mut(user_id, str_param(request, "stationToken")?)?
+            .clone();
+        let songs = self.station_songs(user_id, &station);
+        let flag = |name: &str| request.get(name).and_then(Value::as_bool) == Some(true);
+        let additional_formats: Vec<String> = request
+            .get("additionalAudioUrl")
+            .and_then(Value::as_str)
+            .map(|formats| {
+                formats
+                    .split(',')
+                    .filter(|f| !f.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut items = Vec::new();
+        for i in 0..songs.len().min(4) {
+            let song = songs[(self.next_id as usize + i) % songs.len()];
+            let track_token = self.next_token("tt");
+            self.tracks
+                .insert(track_token.clone(), song.music_token.to_string());
+            let rating = station
+                .feedback
+                .iter()
+                .any(|f| f.is_positive && f.music_token == song.music_token);
+            let audio_stream = |bitrate: &str, encoding: &str| {
+                json!({
+                    "bitrate": bitrate,
+                    "encoding": encoding,
+                    "audioUrl": format!("http://mock.pandora.invalid/access/{track_token}-{bitrate}.mp4"),
+                    "protocol": "http"
+                })
+            };
+            let additional_audio_urls: Vec<String> = additional_formats
+                .iter()
+                .map(|format| format!("http://mock.pandora.invalid/access/{track_token}-{format}"))
+                .collect();
+
+            let mut track = json!({
+                "trackToken": track_token,
+                "musicId": song.music_token,
+                "stationId": station.station_token,
+                "artistName": artist_name(song.artist_token),
+                "albumName": song.album_name,
+                "songName": song.song_name,
+                "songRating": u32::from(rating),
+                "audioUrlMap": {
+                    "highQuality": audio_stream("64", "aacplus"),
+                    "mediumQuality": audio_stream("64", "aacplus"),
+                    "lowQuality": audio_stream("32", "aacplus")
+                },
+                "albumArtUrl": art_url(song.music_token),
+                "trackGain": song.track_gain,
+                "allowFeedback": true,
+                "songDetailUrl": format!("http://www.pandora.com/song/{}", song.music_token),
+                "artistDetailUrl": format!("http://www.pandora.com/artist/{}", song.artist_token),
+                "albumDetailUrl": format!("http://www.pandora.com/album/{}", song.music_token),
+                "amazonAlbumAsin": format!("B00{}", &song.music_token[1..]),
+            });
+            match additional_audio_urls.len() {
+                0 => (),
+                // A single requested format is returned as a plain string
+                1 => track["additionalAudioUrl"] = json!(additional_audio_urls[0]),
+                _ => track["additionalAudioUrl"] = json!(additional_audio_urls),
+            }
+            if flag("includeTrackLength") {
+                track["trackLength"] = json!(song.track_length);
+            }
+            if flag("includeAudioToken") {
+                track["audioToken"] = json!(format!("at{track_token}"));
+            }
+            items.push(track);
+        }
+
+        let ad_token = format!("{}-{}", station.station_token, self.next_token("ad"));
+        let ad_tracking_tokens = vec![
+            format!("ADU-{ad_token}-pod/1/1/0--0"),
+            format!("ADU-{ad_token}-pod/1/1/0--1"),
+        ];
+        self.ad_tracking_tokens
+            .insert(ad_token.clone(), ad_tracking_tokens);
+        items.push(json!({"adToken": ad_token}));
+        Ok(json!({"items": items}))
+    }
+
+    fn add_feedback(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let station_token = str_param(request, "stationToken")?;
+        let song = self.track(str_param(request, "trackToken")?)?;
+        let is_positive = request
+            .get("isPositive")
+            .and_then(Value::as_bool)
+            .unwrap_or(true);
+        let feedback_id = self.next_token("-");
+        let now = self.now_ms();
+        let station = self.station_mut(user_id, station_token)?;
+        station
+            .feedback
+            .retain(|f| f.music_token != song.music_token);
+        station.feedback.push(MockFeedback {
+            feedback_id: feedback_id.clone(),
+            music_token: song.music_token.to_string(),
+            is_positive,
+            date_created: now,
+        });
+        let total_thumbs_up = station.feedback.iter().filter(|f| f.is_positive).count();
+        let total_thumbs_down = station.feedback.len() - total_thumbs_up;
+        Ok(json!({
+            "totalThumbsDown": total_thumbs_down,
+            "stationPersonalizationPercent": (station.feedback.len() * 10).min(100),
+            "dateCreated": timestamp(now),
+            "albumArtUrl": art_url(song.music_token),
+            "musicToken": song.music_token,
+            "songName": song.song_name,
+            "artistName": artist_name(song.artist_token),
+            "totalThumbsUp": total_thumbs_up,
+            "feedbackId": feedback_id,
+            "isPositive": is_positive
+        }))
+    }
+
+    fn delete_feedback(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let feedback_id = str_param(request, "feedbackId")?;
+        let account = self.account_mut(user_id)?;
+        for station in account.stations.iter_mut() {
+            if let Some(idx) = station
+                .feedback
+                .iter()
+                .position(|f| f.feedback_id == feedback_id)
+            {
+                station.feedback.remove(idx);
+                return Ok(json!({}));
+            }
+        }
+        fail(10, "Invalid feedback id")
+    }
+
+    fn add_music(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let music_token = str_param(request, "musicToken")?;
+        let (kind, response) = if let Some(artist) = find_artist(music_token) {
+            (SeedKind::Artist, json!({"artistName": artist.artist_name}))
+        } else if let Some(song) = find_song(music_token) {
+            (
+                SeedKind::Song,
+                json!({"artistName": artist_name(song.artist_token), "songName": song.song_name}),
+            )
+        } else if let Some(genre) = find_genre(music_token) {
+            (SeedKind::Genre, json!({"genreName": genre.station_name}))
+        } else {
+            return fail(10, "Invalid music token");
+        };
+        let seed_id = self.next_token("");
+        let station = self.station_mut(user_id, str_param(request, "stationToken")?)?;
+        if station.is_quick_mix {
+            return fail(1008, "Call not allowed");
+        }
+        station.seeds.push(MockSeed {
+            seed_id: seed_id.clone(),
+            kind,
+            music_token: music_token.to_string(),
+        });
+
+        let mut response = response;
+        response["musicToken"] = json!(music_token);
+        response["seedId"] = json!(seed_id);
+        response["artUrl"] = json!(art_url(music_token));
+        Ok(response)
+    }
+
+    fn delete_music(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let seed_id = str_param(request, "seedId")?;
+        let account = self.account_mut(user_id)?;
+        for station in account.stations.iter_mut() {
+            if let Some(idx) = station.seeds.iter().position(|s| s.seed_id == seed_id) {
+                if station.seeds.len() == 1 {
+                    return fail(10, "Cannot remove the last seed from a station");
+                }
+                station.seeds.remove(idx);
+                return Ok(json!({}));
+            }
+        }
+        fail(10, "Invalid seed id")
+    }
+
+    fn explain_track(&self, request: &Value) -> Result<Value, MockError> {
+        let song = self.track(str_param(request, "trackToken")?)?;
+        Ok(json!({
+            "explanations": [{
+                "focusTraitName": format!("similarity to {}", artist_name(song.artist_token)),
+                "focusTraitId": "F7524"
+            }, {
+                "focusTraitName": "many other similarities identified in the Music Genome Project",
+                "focusTraitId": "F4797"
+            }]
+        }))
+    }
+
+    fn add_bookmark(
+        &mut self,
+        user_id: &str,
+        request: &Value,
+        is_song: bool,
+    ) -> Result<Value, MockError> {
+        let song = self.track(str_param(request, "trackToken")?)?;
+        let bookmark = MockBookmark {
+            bookmark_token: self.next_token(""),
+            music_token: if is_song {
+                song.music_token.to_string()
+            } else {
+                song.artist_token.to_string()
+            },
+            is_song,
+            date_created: self.now_ms(),
+        };
+        let response = bookmark_json(&bookmark);
+        self.account_mut(user_id)?.bookmarks.push(bookmark);
+        Ok(response)
+    }
+
+    fn delete_bookmark(&mut self, user_id: &str, request: &Value) -> Result<Value, MockError> {
+        let bookmark_token = str_param(request, "bookmarkToken")?;
+        let account = self.account_mut(user_id)?;
+        let count = account.bookmarks.len();
+        account
+            .bookmarks
+            .retain(|b| b.bookmark_token != bookmark_token);
+        if account.bookmarks.len() == count {
+            return fail(10, "Invalid bookmark token");
+        }
+        Ok(json!({}))
+    }
+
+    fn get_bookmarks(&self, user_id: &str) -> Result<Value, MockError> {
+        let bookmarks = &self.account(user_id)?.bookmarks;
+        let artists: Vec<Value> = bookmarks
+            .iter()
+            .filter(|b| !b.is_song)
+            .map(bookmark_json)
+            .collect();
+        let songs: Vec<Value> = bookmarks
+            .iter()
+            .filter(|b| b.is_song)
+            .map(bookmark_json)
+            .collect();
+        Ok(json!({"artists": artists, "songs": songs}))
+    }
+
+    fn get_ad_metadata(&self, request: &Value) -> Result<Value, MockError> {
+        let ad_token = str_param(request, "adToken")?;
+        let tracking_tokens = match self.ad_tracking_tokens.get(ad_token) {
+            Some(tracking_tokens) => tracking_tokens,
+            None => return fail(10, "Invalid ad token"),
+        };
+        let flag = |name: &str| request.get(name).and_then(Value::as_bool) == Some(true);
+
+        let mut response = json!({
+            "clickThroughUrl": "http://adclick.g.doubleclick.net/aclk",
+            "imageUrl": format!("http://mock.pandora.invalid/images/{ad_token}_500W_500H.jpg"),
+            "companyName": "",
+            "trackGain": "0.0",
+            "title": "",
+            "adTrackingTokens": if flag("returnAdTrackingTokens") {
+                json!(tracking_tokens)
+            } else {
+                json!([])
+            }
+        });
+        if flag("supportAudioAds") {
+            let audio_stream = |bitrate: &str| {
+                json!({
+                    "bitrate": bitrate,
+                    "encoding": "aacplus",
+                    "audioUrl": format!("http://mock.pandora.invalid/access/{ad_token}-{bitrate}.mp4"),
+                    "protocol": "http"
+                })
+            };
+            response["audioUrlMap"] = json!({
+                "highQuality": audio_stream("64"),
+                "mediumQuality": audio_stream("64"),
+                "lowQuality": audio_stream("32")
+            });
+        }
+        if flag("includeBannerAd") {
+            response["bannerAdMap"] = json!({"html": "<body></body>"});
+        }
+        Ok(response)
+    }
+
+    fn register_ad(&self, request: &Value) -> Result<Value, MockError> {
+        let known = |token: &str| {
+            self.ad_tracking_tokens
+                .values()
+                .any(|tokens| tokens.iter().any(|t| t == token))
+        };
+        match request.get("adTrackingTokens").and_then(Value::as_array) {
+            Some(tokens) if tokens.iter().all(|t| t.as_str().map(known) == Some(true)) => {
+                Ok(json!({}))
+            }
+            Some(_) => fail(10, "Invalid ad tracking token"),
+            None => fail(9, "Missing adTrackingTokens"),
+        }
+    }
+}
+
+/// Decode the body of a request by decrypting it with the partner encryption
+/// key.  Only partner login and licensing checks may be sent unencrypted, so
+/// plain json is rejected like any other body that can't be decrypted.
+fn decode_body(partner: &Partner, body: &str) -> Result<Value, MockError> {
+    crypt::decrypt(partner.encrypt_password.expose_secret(), body)
+        .ok()
+        .and_then(|plaintext| String::from_utf8(plaintext).ok())
+        .and_then(|plaintext| serde_json::from_str(&plaintext).ok())
+        .map_or_else(|| fail(8, "Unable to decrypt request body"), Ok)
+}
+
+/// Retrieve a required string parameter from the request body.
+fn str_param<'a>(request: &'a Value, name: &str) -> Result<&'a str, MockError> {
+    match request.get(name).and_then(Value::as_str) {
+        Some(value) => Ok(value),
+        None => Err((9, format!("Missing parameter {name}"))),
+    }
+}
+
+/// Generate a checksum over a list of values.
+fn checksum<'a, I: Iterator<Item = &'a str>>(values: I) -> String {
+    let mut hasher = DefaultHasher::new();
+    for value in values {
+        value.hash(&mut hasher);
+    }
+    let hash = hasher.finish();
+    format!("{hash:016x}{:016x}", hash.rotate_left(32))
+}
+
+fn bookmark_json(bookmark: &MockBookmark) -> Value {
+    let mut response = json!({
+        "bookmarkToken": bookmark.bookmark_token,
+        "musicToken": bookmark.music_token,
+        "artUrl": art_url(&bookmark.music_token),
+        "dateCreated": timestamp(bookmark.date_created)
+    });
+    if bookmark.is_song {
+        let song = find_song(&bookmark.music_token).expect("Bookmarked song not in catalog");
+        response["songName"] = json!(song.song_name);
+        response["artistName"] = json!(artist_name(song.artist_token));
+        response["albumName"] = json!(song.album_name);
+        response["sampleUrl"] = json!(format!(
+            "http://www.pandora.com/favorites/getSample.jsp?token={}",
+            song.music_token
+        ));
+        response["sampleGain"] = json!(song.track_gain);
+    } else {
+        response["artistName"] = json!(artist_name(&bookmark.music_token));
+    }
+    response
+}
+
+fn get_track(request: &Value) -> Result<Value, MockError> {
+    let music_id = str_param(request, "musicId")?;
+    let song = match find_song(music_id) {
+        Some(song) => song,
+        None => return fail(10, "Invalid music id"),
+    };
+    Ok(json!({
+        "albumName": song.album_name,
+        "trackToken": song.music_token,
+        "artistName": artist_name(song.artist_token),
+        "albumArtUrl": art_url(song.music_token),
+        "score": "",
+        "songName": song.song_name,
+        "musicId": song.music_token,
+        "songDetailUrl": format!("http://www.pandora.com/song/{}", song.music_token),
+        "musicToken": format!("{:016x}", song.music_token.len() as u64 * 0x9e37_79b9)
+    }))
+}
+
+fn search(request: &Value) -> Value {
+    let text = request
+        .get("searchText")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_lowercase();
+    let score = |name: &str| -> Option<u8> {
+        let name = name.to_lowercase();
+        if text.is_empty() {
+            None
+        } else if name == text {
+            Some(100)
+        } else if name.contains(&text) {
+            Some(80)
+        } else {
+            None
+        }
+    };
+
+    let artists: Vec<Value> = ARTISTS
+        .iter()
+        .filter_map(|a| score(a.artist_name).map(|s| (a, s)))
+        .map(|(a, s)| {
+            json!({
+                "artistName": a.artist_name,
+                "musicToken": a.music_token,
+                "likelyMatch": s == 100,
+                "score": s
+            })
+        })
+        .collect();
+    let songs: Vec<Value> = SONGS
+        .iter()
+        .filter_map(|song| score(song.song_name).map(|s| (song, s)))
+        .map(|(song, s)| {
+            json!({
+                "artistName": artist_name(song.artist_token),
+                "musicToken": song.music_token,
+                "songName": song.song_name,
+                "score": s
+            })
+        })
+        .collect();
+    let mut response = json!({
+        "nearMatchesAvailable": false,
+        "explanation": "",
+        "songs": songs,
+        "artists": artists
+    });
+    if request.get("includeGenreStations").and_then(Value::as_bool) == Some(true) {
+        let genre_stations: Vec<Value> = GENRES
+            .iter()
+            .filter_map(|g| {
+                score(g.station_name)
+                    .or_else(|| score(g.category_name))
+                    .map(|s| (g, s))
+            })
+            .map(|(g, s)| {
+                json!({
+                    "musicToken": g.music_token,
+                    "score": s,
+                    "stationName": g.station_name
+                })
+            })
+            .collect();
+        response["genreStations"] = json!(genre_stations);
+    }
+    response
+}
+
+fn genre_stations() -> Value {
+    let mut categories: Vec<(&str, Vec<Value>)> = Vec::new();
+    for genre in GENRES {
+        let station = json!({
+            "stationToken": genre.music_token,
+            "stationName": genre.station_name,
+            "stationId": genre.music_token
+        });
+        match categories
+            .iter_mut()
+            .find(|(c, _)| *c == genre.category_name)
+        {
+            Some((_, stations)) => stations.push(station),
+            None => categories.push((genre.category_name, vec![station])),
+        }
+    }
+    let categories: Vec<Value> = categories
+        .into_iter()
+        .map(|(category_name, stations)| {
+            json!({"categoryName": category_name, "stations": stations})
+        })
+        .collect();
+    json!({"categories": categories})
+}
+
+/// A local HTTP server that answers Pandora JSON API requests using a
+/// [`MockPandora`].  The server stops when this value is dropped.
+///
+/// The server must be started from within a tokio runtime.
+#[derive(Debug)]
+pub struct MockServer {
+    address: std::net::SocketAddr,
+    pandora: Arc<MockPandora>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Start serving `pandora` on an available port on the loopback interface.
+    pub async fn start(pandora: Arc<MockPandora>) -> std::io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await?;
+        let address = listener.local_addr()?;
+        let handler = pandora.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve_connection(stream, address, &handler).await {
+                        log::debug!("Mock server connection error: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            address,
+            pandora,
+            task,
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn address(&self) -> std::net::SocketAddr {
+        self.address
+    }
+
+    /// The url of the JSON API endpoint served by this server, suitable for
+    /// use with `PandoraSession::endpoint()`.
+    pub fn endpoint(&self) -> String {
+        format!("http://{}/services/json", self.address)
+    }
+
+    /// The mock service answering requests to this server.
+    pub fn pandora(&self) -> &Arc<MockPandora> {
+        &self.pandora
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answer HTTP/1.1 requests on a single connection until the client closes it.
+async fn serve_connection(
+    mut stream: tokio::net::TcpStream,
+    address: std::net::SocketAddr,
+    pandora: &MockPandora,
+) -> std::io::Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+    let (reader, mut writer) = stream.split();
+    let mut reader = tokio::io::BufReader::new(reader);
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).await? == 0 {
+            return Ok(());
+        }
+        let target = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or("/")
+            .to_string();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 {
+                return Ok(());
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).await?;
+
+        let response = match url::Url::parse(&format!("http://{address}{target}")) {
+            Ok(url) => pandora.handle(&url, &String::from_utf8_lossy(&body)),
+            Err(_) => TransportResponse::new(400, "Bad Request"),
+        };
+        let reason = if response.is_success() { "OK" } else { "Error" };
+        let head = format!(
+            "HTTP/1.1 {} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            response.status,
+            response.body.len()
+        );
+        writer.write_all(head.as_bytes()).await?;
+        writer.write_all(response.body.as_bytes()).await?;
+        writer.flush().await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::auth::user_login;
+    use crate::json::errors::JsonErrorKind;
+    use crate::json::retry::RetryPolicy;
+    use crate::json::user::{get_station_list, GetStationList};
+    use crate::json::{PandoraJsonApiRequest, PandoraSession};
+    use crate::secret::Secret;
+
+    fn error_kind<T: std::fmt::Debug>(result: Result<T, Error>) -> JsonErrorKind {
+        match result {
+            Err(Error::PandoraJsonRequestError(e)) => e.kind(),
+            res => panic!("Expected an API error, got {:?}", res),
+        }
+    }
+
+    #[tokio::test]
+    async fn mock_protocol_test() {
+        let partner = Partner::default();
+        let mock = Arc::new(MockPandora::new());
+        let mut session = partner.init_session_with_transport(mock.clone());
+        session.set_retry_policy(RetryPolicy::never());
+
+        // No partner login yet, so no auth token or partner id
+        let station_list = get_station_list(&session).await;
+        assert_eq!(
+            error_kind(station_list),
+            JsonErrorKind::UrlParamMissingAuthToken
+        );
+
+        let bad_partner = Partner {
+            password: Secret::from("not the password"),
+            ..Partner::default()
+        };
+        let bad_partner_login = bad_partner.login(&session).await;
+        assert_eq!(
+            error_kind(bad_partner_login),
+            JsonErrorKind::InvalidPartnerLogin
+        );
+
+        partner
+            .login(&session)
+            .await
+            .expect("Failed partner login against mock");
+        let sync_time = session
+            .session_tokens()
+            .get_sync_time()
+            .expect("Partner login did not set the sync time");
+        assert!((sync_time as i64 - chrono::Utc::now().timestamp()).abs() < 5);
+
+        // Partner tokens aren't sufficient for user methods
+        let station_list = get_station_list(&session).await;
+        assert_eq!(
+            error_kind(station_list),
+            JsonErrorKind::UrlParamMissingUserId
+        );
+
+        let bad_user_login = user_login(&session, MockPandora::DEFAULT_USERNAME, "wrong").await;
+        assert_eq!(
+            error_kind(bad_user_login),
+            JsonErrorKind::InvalidPartnerLogin
+        );
+        user_login(
+            &session,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+        .await
+        .expect("Failed user login against mock");
+        let stations = get_station_list(&session)
+            .await
+            .expect("Failed getting station list from mock");
+        assert_eq!(stations.stations.len(), 3);
+
+        // Methods other than partner login and licensing checks must be
+        // encrypted
+        let mut unencrypted = GetStationList::default()
+            .request_session(&session)
+            .expect("Failed building station list request");
+        unencrypted.encrypted = false;
+        let (url, body) = unencrypted
+            .build_parts()
+            .expect("Failed building station list request");
+        let response: Value =
+            serde_json::from_str(&mock.handle(&url, &body).body).expect("Mock response isn't json");
+        assert_eq!(response["stat"], "fail");
+        assert_eq!(response["code"], 8);
+
+        // Sessions that drift too far from the server clock are rejected, and
+        // re-sync their time base through partner login
+        mock.set_clock_offset(3600);
+        let _stations = get_station_list(&session)
+            .await
+            .expect("Failed re-syncing with the mock clock");
+        let calls = mock.method_calls();
+        assert_eq!(
+            calls[calls.len() - 3..],
+            [
+                "user.getStationList",
+                "auth.partnerLogin",
+                "user.getStationList"
+            ]
+        );
+
+        // Restored sessions don't know which partner login to repeat
+        let saved = serde_json::to_string(&*session.session_tokens())
+            .expect("Failed saving session tokens");
+        let mut restored = PandoraSession::restore(
+            None,
+            serde_json::from_str(&saved).expect("Failed loading session tokens"),
+            &partner,
+        );
+        restored
+            .set_transport(mock.clone())
+            .set_retry_policy(RetryPolicy::never());
+        mock.set_clock_offset(0);
+        let station_list = get_station_list(&restored).await;
+        assert_eq!(
+            error_kind(station_list),
+            JsonErrorKind::InsufficientConnectivity
+        );
+
+        let other_session = partner.init_session_with_transport(mock.clone());
+        partner
+            .login(&other_session)
+            .await
+            .expect("Failed partner login against mock");
+        user_login(
+            &other_session,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+        .await
+        .expect("Failed user login against mock");
+        mock.expire_tokens();
+        let station_list = get_station_list(&other_session).await;
+        assert_eq!(error_kind(station_list), JsonErrorKind::InvalidAuthToken);
+
+        assert_eq!(
+            mock.method_calls().first().map(String::as_str),
+            Some("user.getStationList")
+        );
+    }
+
+    #[tokio::test]
+    async fn mock_server_test() {
+        let partner = Partner::default();
+        let server = MockServer::start(Arc::new(MockPandora::new()))
+            .await
+            .expect("Failed starting mock server");
+        let mut session = PandoraSession::new(None, &partner, &server.endpoint());
+        session.set_retry_policy(RetryPolicy::never());
+
+        partner
+            .login(&session)
+            .await
+            .expect("Failed partner login against mock server");
+        user_login(
+            &session,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+        .await
+        .expect("Failed user login against mock server");
+        let stations = get_station_list(&session)
+            .await
+            .expect("Failed getting station list from mock server");
+        assert!(!stations.stations.is_empty());
+
+        server.pandora().fail_next(MockFailure::Http(503));
+        match get_station_list(&session).await {
+            Err(Error::HttpStatusError(503)) => (),
+            res => panic!("Unexpected station list result: {:?}", res),
+        }
+    }
+}
@@ -10,35 +10,69 @@ lowercase letters.
 pub mod accessory;
 pub mod ad;
 pub mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod bookmark;
+pub mod builder;
+pub mod cassette;
 mod crypt;
 pub mod device;
+pub mod drift;
 pub mod errors;
+pub mod failover;
+pub mod managed;
+pub mod middleware;
+#[cfg(any(test, feature = "mock-server"))]
+pub mod mock;
 pub mod music;
+pub mod ratelimit;
+pub mod redact;
+pub mod retry;
 pub mod station;
 pub mod test;
 pub mod track;
+pub mod transport;
 pub mod user;
 
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use serde::{Deserialize, Serialize};
 use serde_json;
 
 use crate::errors::Error;
 use crate::json::auth::{PartnerLogin, PartnerLoginResponse};
+use crate::json::drift::{DriftDetector, DriftReport};
 use crate::json::errors::{JsonError, JsonErrorKind};
+use crate::json::failover::Endpoints;
+use crate::json::middleware::Middleware;
+use crate::json::ratelimit::RateLimiter;
+use crate::json::retry::RetryPolicy;
+use crate::json::transport::{ReqwestTransport, Transport, TransportResponse};
+use crate::secret::Secret;
 
 /// A builder to construct the properties of an http request to Pandora.
-#[derive(Debug, Clone)]
+///
+/// The session tokens are kept in a store shared by every clone of a session,
+/// so that independent requests can be submitted concurrently through clones
+/// of (or shared references to) a single session, and all of them see tokens
+/// that are updated by a login, or cleared when they're no longer valid.
+#[derive(Clone)]
 pub struct PandoraSession {
     client: reqwest::Client,
-    endpoint_url: url::Url,
-    tokens: SessionTokens,
+    transport: Arc<dyn Transport>,
+    retry_policy: RetryPolicy,
+    rate_limiter: RateLimiter,
+    middleware: Vec<Arc<dyn Middleware>>,
+    method_timeouts: HashMap<String, std::time::Duration>,
+    drift_detector: Option<Arc<DriftDetector>>,
+    endpoints: Endpoints,
+    tokens: Arc<RwLock<SessionTokens>>,
     json: serde_json::value::Value,
     args: std::collections::BTreeMap<String, String>,
     encrypted: bool,
+    call_id: u64,
 }
 
 impl PandoraSession {
@@ -48,26 +82,68 @@ impl PandoraSession {
         to_encryption_tokens: &T,
         to_endpoint: &E,
     ) -> Self {
+        let client = client.unwrap_or_default();
         Self {
-            client: client.unwrap_or_default(),
-            endpoint_url: to_endpoint.to_endpoint_url(),
-            tokens: SessionTokens::new(to_encryption_tokens),
+            transport: Arc::new(ReqwestTransport::new(client.clone())),
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: RateLimiter::default(),
+            middleware: Vec::new(),
+            method_timeouts: HashMap::new(),
+            drift_detector: None,
+            client,
+            endpoints: Endpoints::new(to_endpoint.to_endpoint_url()),
+            tokens: Arc::new(RwLock::new(SessionTokens::new(to_encryption_tokens))),
             json: serde_json::value::Value::Object(serde_json::map::Map::new()),
             args: std::collections::BTreeMap::new(),
             encrypted: false,
+            call_id: 0,
         }
     }
 
+    /// Construct a new PandoraSession that resumes a login using previously
+    /// saved session tokens, rather than starting from a partner login.
+    ///
+    /// If the saved tokens have since expired, requests will fail with
+    /// `InvalidAuthToken` and the login will need to be repeated.
+    pub fn restore<E: ToEndpoint>(
+        client: Option<reqwest::Client>,
+        tokens: SessionTokens,
+        to_endpoint: &E,
+    ) -> Self {
+        let mut session = Self::new(client, &tokens, to_endpoint);
+        session.tokens = Arc::new(RwLock::new(tokens));
+        session
+    }
+
+    /// Construct a new PandoraSession that submits requests through the
+    /// provided transport.
+    pub fn new_with_transport<T: ToEncryptionTokens, E: ToEndpoint>(
+        transport: Arc<dyn Transport>,
+        to_encryption_tokens: &T,
+        to_endpoint: &E,
+    ) -> Self {
+        let mut session = Self::new(None, to_encryption_tokens, to_endpoint);
+        session.transport = transport;
+        session
+    }
+
     /// Create a new PandoraSession copying the endpoint and session values into the new
-    /// object.
+    /// object.  The new session shares its session tokens with this one.
     pub fn copy_session(&self) -> Self {
         Self {
             client: self.client.clone(),
-            endpoint_url: self.endpoint_url.clone(),
+            transport: self.transport.clone(),
+            retry_policy: self.retry_policy.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            middleware: self.middleware.clone(),
+            method_timeouts: self.method_timeouts.clone(),
+            drift_detector: self.drift_detector.clone(),
+            endpoints: self.endpoints.clone(),
             tokens: self.tokens.clone(),
             json: serde_json::value::Value::Object(serde_json::map::Map::new()),
             args: std::collections::BTreeMap::new(),
             encrypted: false,
+            call_id: 0,
         }
     }
 
@@ -76,37 +152,201 @@ impl PandoraSession {
         &self.client
     }
 
-    /// Set the Endpoint on this PandoraSession instance.
+    /// Get a reference to the transport used to submit requests.
+    pub fn transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+
+    /// Set the transport used to submit requests on this PandoraSession instance.
+    pub fn set_transport(&mut self, transport: Arc<dyn Transport>) -> &mut Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Get a reference to the policy for retrying requests that fail with
+    /// transient errors.
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Set the policy for retrying requests that fail with transient errors
+    /// on this PandoraSession instance.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Get a reference to the limiter on how often API methods may be called.
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// Set the limiter on how often API methods may be called on this
+    /// PandoraSession instance.
+    pub fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) -> &mut Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Get the time allowed for requests for `method`, if it has been
+    /// limited.
+    pub fn method_timeout(&self, method: &str) -> Option<std::time::Duration> {
+        self.method_timeouts.get(method).copied()
+    }
+
+    /// Limit the time allowed for requests for `method`, given in the form
+    /// returned by `PandoraJsonApiRequest::get_method()`, e.g.
+    /// `"station.getPlaylist"`, on this PandoraSession instance.  Requests
+    /// that take longer fail with `Error::RequestTimeout`.
+    pub fn set_method_timeout(&mut self, method: &str, timeout: std::time::Duration) -> &mut Self {
+        self.method_timeouts.insert(method.to_string(), timeout);
+        self
+    }
+
+    /// Whether strict mode is enabled, so that responses are checked against
+    /// their response types.
+    pub fn strict_mode(&self) -> bool {
+        self.drift_detector.is_some()
+    }
+
+    /// Enable or disable strict mode on this PandoraSession instance.  In
+    /// strict mode, the result of each successful request is checked against
+    /// the response type of the request, and any unknown, missing or invalid
+    /// fields are recorded in the drift report, which is shared by clones of
+    /// the session.  Disabling strict mode discards the report.
+    pub fn set_strict_mode(&mut self, strict: bool) -> &mut Self {
+        match (strict, &self.drift_detector) {
+            (true, None) => self.drift_detector = Some(Arc::new(DriftDetector::new())),
+            (false, _) => self.drift_detector = None,
+            (true, Some(_)) => (),
+        }
+        self
+    }
+
+    /// Get a snapshot of the differences from the response types recorded so
+    /// far, if strict mode is enabled.
+    pub fn drift_report(&self) -> Option<DriftReport> {
+        self.drift_detector
+            .as_ref()
+            .map(|detector| detector.report())
+    }
+
+    /// Get the middleware called for each request made through this session,
+    /// in the order it was registered.
+    pub fn middleware(&self) -> &[Arc<dyn Middleware>] {
+        &self.middleware
+    }
+
+    /// Register middleware to be called for each request made through this
+    /// PandoraSession instance, after any middleware already registered.
+    pub fn add_middleware(&mut self, middleware: Arc<dyn Middleware>) -> &mut Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Remove all middleware registered on this PandoraSession instance.
+    pub fn clear_middleware(&mut self) -> &mut Self {
+        self.middleware.clear();
+        self
+    }
+
+    /// Set the Endpoint on this PandoraSession instance.  This forgets any
+    /// endpoints discovered by partner login, and any failover.
     pub fn endpoint<E: ToEndpoint>(&mut self, to_endpoint: E) -> &mut Self {
-        self.endpoint_url = to_endpoint.to_endpoint_url();
+        *self.endpoints.primary_mut() = to_endpoint.to_endpoint_url();
         self
     }
 
     /// Get a mutable reference to the endpoint url::Url to update or make calls on it.
     pub fn endpoint_mut<E: ToEndpoint>(&mut self) -> &mut url::Url {
-        &mut self.endpoint_url
+        self.endpoints.primary_mut()
+    }
+
+    /// Add an endpoint to fail over to when the endpoints ahead of it can't
+    /// be reached.
+    pub fn add_fallback_endpoint<E: ToEndpoint>(&mut self, to_endpoint: E) -> &mut Self {
+        self.endpoints.add_fallback(to_endpoint.to_endpoint_url());
+        self
+    }
+
+    /// Get a reference to the endpoints requests may be submitted to.
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    /// Whether API endpoints are discovered through partner login.
+    pub fn endpoint_discovery(&self) -> bool {
+        self.endpoints.discovery()
+    }
+
+    /// Enable or disable endpoint discovery on this PandoraSession instance.
+    /// When enabled, partner login asks for the service urls, and any API
+    /// endpoints among them on a Pandora host are tried after the primary
+    /// endpoint when it can't be reached.  Disabled by default.
+    pub fn set_endpoint_discovery(&mut self, discovery: bool) -> &mut Self {
+        self.endpoints.set_discovery(discovery);
+        self
     }
 
     /// Update the session partner tokens from type implementing ToPartnerTokens.
-    pub fn update_partner_tokens<T: ToPartnerTokens>(&mut self, to_partner_tokens: &T) {
-        self.tokens.update_partner_tokens(to_partner_tokens);
+    /// This fails if the sync time can't be decrypted.
+    pub fn update_partner_tokens<T: ToPartnerTokens>(
+        &self,
+        to_partner_tokens: &T,
+    ) -> Result<(), Error> {
+        self.session_tokens_mut()
+            .update_partner_tokens(to_partner_tokens)
+    }
+
+    /// Re-sync the session time base with the server clock, by repeating the
+    /// partner login that issued the current partner tokens and taking the
+    /// sync time from its response.  The partner and user tokens are kept.
+    ///
+    /// Returns false, without making a request, if the session doesn't know
+    /// which partner login to repeat, e.g. because it was restored from
+    /// saved tokens.
+    pub async fn resync_time(&self) -> Result<bool, Error> {
+        let (partner_login, tokens) = {
+            let tokens = self.session_tokens();
+            match tokens.get_partner_login() {
+                Some(partner_login) => (partner_login.clone(), SessionTokens::new(&*tokens)),
+                None => return Ok(false),
+            }
+        };
+        // Log in on a copy of the session without tokens, so the stale sync
+        // time isn't submitted, and the current tokens aren't replaced
+        let mut sync_session = self.clone();
+        sync_session.tokens = Arc::new(RwLock::new(tokens));
+        let response = partner_login.response(&sync_session).await?;
+        self.session_tokens_mut().update_sync_time(&response)?;
+        Ok(true)
     }
 
     /// Update the session partner tokens from type implementing ToPartnerTokens.
-    pub fn update_user_tokens<T: ToUserTokens>(&mut self, to_user_tokens: &T) {
-        self.tokens.update_user_tokens(to_user_tokens);
+    pub fn update_user_tokens<T: ToUserTokens>(&self, to_user_tokens: &T) {
+        self.session_tokens_mut().update_user_tokens(to_user_tokens);
     }
 
-    /// Get a reference to the session tokens to check the state or make calls
-    /// on it.
-    pub fn session_tokens(&self) -> &SessionTokens {
-        &self.tokens
+    /// Lock the session tokens for reading, to check the state or make calls
+    /// on them.
+    ///
+    /// The tokens are shared with every clone of this session, so the guard
+    /// should be dropped promptly, and never held across an await point.
+    pub fn session_tokens(&self) -> RwLockReadGuard<'_, SessionTokens> {
+        self.tokens
+            .read()
+            .expect("PandoraSession token lock poisoned")
     }
 
-    /// Get a mutable reference to the session tokens to modify the state or
-    /// make calls on it.
-    pub fn session_tokens_mut(&mut self) -> &mut SessionTokens {
-        &mut self.tokens
+    /// Lock the session tokens for writing, to modify the state or make calls
+    /// on them.
+    ///
+    /// The tokens are shared with every clone of this session, so the guard
+    /// should be dropped promptly, and never held across an await point.
+    pub fn session_tokens_mut(&self) -> RwLockWriteGuard<'_, SessionTokens> {
+        self.tokens
+            .write()
+            .expect("PandoraSession token lock poisoned")
     }
 
     /// Set the json object on this PandoraSession instance.
@@ -138,74 +378,115 @@ impl PandoraSession {
 
     /// Merge necessary values from the session instance into the query arguments
     fn add_session_tokens_to_args(&mut self) {
-        // auth_token arg should be set to user_token, if available, otherwise partner_token
-        if let Some(auth_token) = self
-            .tokens
-            .user_token
-            .clone()
-            .or_else(|| self.tokens.partner_token.clone())
-        {
-            self.arg("auth_token", &auth_token);
+        // Lock through a separate handle on the store, so that the args can
+        // be updated while the tokens are borrowed
+        let store = self.tokens.clone();
+        let tokens = store.read().expect("PandoraSession token lock poisoned");
+        if let Some(auth_token) = tokens.auth_token() {
+            self.arg("auth_token", auth_token.expose_secret());
         }
-        if let Some(partner_id) = self.tokens.partner_id.clone() {
-            self.arg("partner_id", &partner_id);
+        if let Some(partner_id) = &tokens.partner_id {
+            self.arg("partner_id", partner_id);
         }
-        if let Some(user_id) = self.tokens.user_id.clone() {
-            self.arg("user_id", &user_id);
+        if let Some(user_id) = &tokens.user_id {
+            self.arg("user_id", user_id);
         }
     }
 
     /// Merge necessary values from the session instance into the json body
     fn add_session_tokens_to_json(&mut self) {
+        let store = self.tokens.clone();
+        let tokens = store.read().expect("PandoraSession token lock poisoned");
+        let sync_time = tokens.get_sync_time();
         let json_obj = self
             .json
             .as_object_mut()
             .expect("Programming Error accessing API request json for modification.");
-        if let Some(partner_auth_token) = self.tokens.partner_token.clone() {
+        if let Some(partner_auth_token) = &tokens.partner_token {
             json_obj.insert(
                 "partnerAuthToken".to_string(),
-                serde_json::Value::String(partner_auth_token),
+                serde_json::Value::from(partner_auth_token.expose_secret()),
             );
         }
-        if let Some(user_auth_token) = self.tokens.user_token.clone() {
+        if let Some(user_auth_token) = &tokens.user_token {
             json_obj.insert(
                 "userAuthToken".to_string(),
-                serde_json::Value::String(user_auth_token),
+                serde_json::Value::from(user_auth_token.expose_secret()),
             );
         }
 
-        if let Some(sync_time) = self.tokens.sync_time {
+        if let Some(sync_time) = sync_time {
             json_obj.insert("syncTime".to_string(), serde_json::Value::from(sync_time));
         }
     }
 
     /// Build a reqwest::Request, which can be inspected, modified, and executed with
-    /// reqwest::Client::execute().
-    pub fn build(&mut self) -> reqwest::RequestBuilder {
-        self.add_session_tokens_to_args();
-        let mut url: url::Url = self.endpoint_url.clone();
-        url.query_pairs_mut().extend_pairs(&self.args);
+    /// reqwest::Client::execute().  This fails if the body has to be
+    /// encrypted, and the session encryption key is invalid.
+    pub fn build(&mut self) -> Result<reqwest::RequestBuilder, Error> {
+        let (url, body) = self.build_parts()?;
+        Ok(self.client.post(url).body(body))
+    }
+
+    /// Build the url and body of the http request, which can be submitted
+    /// through a Transport.  This fails if the body has to be encrypted, and
+    /// the session encryption key is invalid.
+    pub fn build_parts(&mut self) -> Result<(url::Url, String), Error> {
+        let method = self.args.get("method").cloned().unwrap_or_default();
+        self.call_id = middleware::next_call_id();
 
         self.add_session_tokens_to_json();
-        let mut body: String = self.json.to_string();
-        if cfg!(test) {
-            log::debug!("Request body: {:?}", body);
+        for middleware in &self.middleware {
+            middleware.on_request_json(&method, self.call_id, &mut self.json);
         }
+        let mut body: String = self.json.to_string();
         if self.encrypted {
-            body = self.tokens.encrypt(&body);
-            if cfg!(test) {
-                log::debug!("Encrypted body: {:?}", body);
-            }
+            body = self.session_tokens().encrypt(&body)?;
+        }
+
+        self.add_session_tokens_to_args();
+        let mut url: url::Url = self.endpoints.active();
+        url.query_pairs_mut().extend_pairs(&self.args);
+        for middleware in &self.middleware {
+            middleware.on_request_url(&method, self.call_id, &mut url);
         }
 
-        self.client.post(url).body(body)
+        Ok((url, body))
+    }
+}
+
+impl Debug for PandoraSession {
+    /// The request json and query arguments may hold passwords and auth
+    /// tokens, so only their keys are shown.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let json_keys: Vec<&String> = self
+            .json
+            .as_object()
+            .map(|json| json.keys().collect())
+            .unwrap_or_default();
+        let arg_keys: Vec<&String> = self.args.keys().collect();
+        f.debug_struct("PandoraSession")
+            .field("client", &self.client)
+            .field("transport", &self.transport)
+            .field("retry_policy", &self.retry_policy)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("middleware", &self.middleware)
+            .field("method_timeouts", &self.method_timeouts)
+            .field("drift_detector", &self.drift_detector)
+            .field("endpoints", &self.endpoints)
+            .field("tokens", &self.tokens)
+            .field("json_keys", &json_keys)
+            .field("arg_keys", &arg_keys)
+            .field("encrypted", &self.encrypted)
+            .field("call_id", &self.call_id)
+            .finish()
     }
 }
 
 /// A generic type to aid in converting the returned Json document from a
 /// Pandora API call into a custom struct T that deserializes the content of
 /// the API call result.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PandoraResponse<T> {
     /// The reported status of the call
@@ -243,7 +524,7 @@ impl<T: serde::de::DeserializeOwned> From<PandoraResponse<T>>
 }
 
 /// The status string returned by the Pandora JSON API call.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum PandoraStatus {
     /// The API method call succeeded
@@ -266,6 +547,7 @@ pub trait PandoraJsonApiRequest: serde::ser::Serialize {
         + From<serde_json::error::Error>
         + From<reqwest::Error>
         + From<JsonError>
+        + From<Error>
         + Send;
 
     /// Returns the name of the Pandora JSON API call in the form that it must
@@ -284,80 +566,309 @@ pub trait PandoraJsonApiRequest: serde::ser::Serialize {
         false
     }
 
+    /// Decode an error code returned by the API for this request, along with
+    /// the message accompanying it, into the kind of error.  By default this
+    /// is `JsonErrorKind::for_method()`, which knows the codes whose meaning
+    /// depends on the method.
+    fn error_kind(&self, code: u32, message: Option<&str>) -> JsonErrorKind {
+        JsonErrorKind::for_method(&self.get_method(), code, message)
+    }
+
+    /// Whether submitting the request more than once has the same effect as
+    /// submitting it once, so that it may be retried after a transient
+    /// failure.  By default only logins and requests that read state are
+    /// idempotent; a request that changes the account or its stations may
+    /// have taken effect even though it was reported as failed, so it's
+    /// attempted only once.
+    fn idempotent(&self) -> bool {
+        is_idempotent_method(&self.get_method())
+    }
+
     /// Generate an HTTP request that, when send() is called on it, will submit
     /// the built request.
     fn request(
         &self,
-        session: &mut PandoraSession,
+        session: &PandoraSession,
     ) -> std::result::Result<reqwest::RequestBuilder, Self::Error> {
-        let mut tmp_session = session.clone();
-        tmp_session
-            .arg("method", &self.get_method())
-            .json(self.get_json()?);
-        if self.encrypt_request() {
-            tmp_session.encrypted();
-        }
-        Ok(tmp_session.build())
+        Ok(self.request_session(session)?.build()?)
+    }
+
+    /// Generate the url and body that, when posted through the session
+    /// transport, will submit the built request.
+    fn request_parts(
+        &self,
+        session: &PandoraSession,
+    ) -> std::result::Result<(url::Url, String), Self::Error> {
+        Ok(self.request_session(session)?.build_parts()?)
+    }
+
+    /// Generate a copy of the session configured with the method, json body
+    /// and encryption requirements of this request.
+    fn request_session(
+        &self,
+        session: &PandoraSession,
+    ) -> std::result::Result<PandoraSession, Self::Error> {
+        method_session(
+            self,
+            session,
+            &middleware_method(session, self.get_method()),
+        )
     }
 
     /// Build the request, submit it, and extract the response content from the
     /// body json, and deserialize it into the Self::Response type.
+    ///
+    /// Requests are held back or rejected as needed to stay within the
+    /// session rate limits, and idempotent requests that fail with transient
+    /// errors are retried according to the session retry policy.  When Pandora rejects
+    /// the session sync time, the session time base is re-synced with
+    /// `PandoraSession::resync_time()` and the request is resubmitted once.
+    ///
+    /// The method name is passed through the session middleware once, and
+    /// the resulting name determines the rate limit, retries, timeout and
+    /// decoding of errors for every attempt.
     async fn response(
         &self,
-        session: &mut PandoraSession,
+        session: &PandoraSession,
     ) -> std::result::Result<Self::Response, Self::Error> {
-        let response = self
-            .request(session)?
-            .send()
-            .await
-            .map_err(Self::Error::from)?;
-
-        let response_obj: PandoraResponse<Self::Response> = if cfg!(test) {
-            // Debugging support - output full response text before attempting
-            // deserialization
-            let response_body = response.text().await?;
-            if cfg!(test) {
-                log::debug!("Full response: {:?}", response_body);
-            }
-            serde_json::from_slice(response_body.as_bytes())?
+        let original_method = self.get_method();
+        let method = middleware_method(session, original_method.clone());
+        // A renamed method behaves like the method it was renamed to
+        let renamed = method != original_method;
+        let idempotent = if renamed {
+            is_idempotent_method(&method)
         } else {
-            // Regular builds just grab the json directly.
-            response.json().await?
+            self.idempotent()
+        };
+        let error_kind = |code, message: Option<&str>| {
+            if renamed {
+                JsonErrorKind::for_method(&method, code, message)
+            } else {
+                self.error_kind(code, message)
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        let (span, start) = (
+            tracing::info_span!(
+                "pandora_api_request",
+                method = %method,
+                encrypted = self.encrypt_request(),
+                attempts = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+                http_status = tracing::field::Empty,
+                stat = tracing::field::Empty,
+                error_code = tracing::field::Empty,
+            ),
+            std::time::Instant::now(),
+        );
+
+        let retry_policy = session.retry_policy().clone();
+        let request = async {
+            let mut attempt = 1;
+            // Each attempt may try every endpoint once
+            let max_failovers = session.endpoints().candidates().len() - 1;
+            let mut failovers = 0;
+            // The time base is re-synced at most once per request
+            let mut resynced = false;
+            let (result, auth_token) = loop {
+                #[cfg(feature = "tracing")]
+                tracing::Span::current().record("attempts", attempt);
+                let delay = session.rate_limiter().acquire(&method)?;
+                if !delay.is_zero() {
+                    log::debug!("{} rate limited, delaying {:?}", method, delay);
+                    tokio::time::sleep(delay).await;
+                }
+
+                let mut request = method_session(self, session, &method)?;
+                let endpoint = request.endpoints.active();
+                let (url, body) = request.build_parts()?;
+                let submission = submit_request(&request, &method, url, body, &error_kind);
+                let error = match submission.await {
+                    Err(e)
+                        if failovers < max_failovers
+                            && (failover::is_connect_failure(&e)
+                                || (idempotent && failover::is_unreachable(&e))) =>
+                    {
+                        let next = session.endpoints().fail_over(&endpoint);
+                        log::warn!(
+                            "{} couldn't reach {}, failing over to {}: {}",
+                            method,
+                            endpoint,
+                            next,
+                            e
+                        );
+                        failovers += 1;
+                        continue;
+                    }
+                    Err(e) if !resynced && is_bad_sync_time(&e) => e,
+                    Err(e) if idempotent && retry_policy.should_retry(&e, attempt) => e,
+                    res => break (res, request.args.get("auth_token").cloned()),
+                };
+                if !resynced && is_bad_sync_time(&error) {
+                    resynced = true;
+                    match session.resync_time().await {
+                        Ok(true) => {
+                            log::info!(
+                                "{} rejected the session sync time, re-synced with the server clock",
+                                method
+                            );
+                            continue;
+                        }
+                        Ok(false) => (),
+                        Err(e) => log::warn!("Failed re-syncing the session time base: {}", e),
+                    }
+                    if !(idempotent && retry_policy.should_retry(&error, attempt)) {
+                        break (Err(error), request.args.get("auth_token").cloned());
+                    }
+                }
+                let backoff = retry_policy.backoff(attempt);
+                log::warn!(
+                    "{} failed on attempt {} of {}, retrying in {:?}: {}",
+                    method,
+                    attempt,
+                    retry_policy.get_max_attempts(),
+                    backoff,
+                    error
+                );
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+                failovers = 0;
+            };
+
+            // Detect errors that indicate that our session tokens aren't valid, and clear them,
+            // unless a concurrent request has already replaced them.  Sync time errors only get
+            // here if re-syncing the time base didn't help.
+            if let Err(Error::PandoraJsonRequestError(e)) = &result {
+                if e.kind().requires_reauth() {
+                    let mut tokens = session.session_tokens_mut();
+                    if tokens.auth_token().map(Secret::expose_secret) == auth_token.as_deref() {
+                        tokens.clear_partner_tokens();
+                        tokens.clear_user_tokens();
+                    }
+                }
+            }
+            result.map_err(Self::Error::from)
         };
 
-        if cfg!(test) {
-            log::debug!("Json response: {:?}", response_obj);
-        }
-
-        let result: std::result::Result<Self::Response, JsonError> = response_obj.into();
-        // Detect errors that indicate that our session tokens aren't valid, and clear them
-        match result {
-            Err(JsonError {
-                kind: JsonErrorKind::InvalidAuthToken,
-                message,
-            }) => {
-                session.session_tokens_mut().clear_partner_tokens();
-                session.session_tokens_mut().clear_user_tokens();
-                Err(JsonError {
-                    kind: JsonErrorKind::InvalidAuthToken,
-                    message,
-                })
+        #[cfg(feature = "tracing")]
+        let request = tracing::Instrument::instrument(request, span.clone());
+        let result = request.await;
+        #[cfg(feature = "tracing")]
+        {
+            span.record("latency_ms", start.elapsed().as_millis() as u64);
+            match &result {
+                Ok(_) => tracing::debug!(parent: &span, "Pandora API request succeeded"),
+                Err(e) => tracing::debug!(parent: &span, error = ?e, "Pandora API request failed"),
             }
-            Err(JsonError {
-                kind: JsonErrorKind::InsufficientConnectivity,
-                message,
-            }) => {
-                session.session_tokens_mut().clear_partner_tokens();
-                session.session_tokens_mut().clear_user_tokens();
-                Err(JsonError {
-                    kind: JsonErrorKind::InsufficientConnectivity,
-                    message,
-                })
+        }
+        result
+    }
+}
+
+/// The name of `method` after it is passed through the session middleware.
+fn middleware_method(session: &PandoraSession, mut method: String) -> String {
+    for middleware in session.middleware() {
+        middleware.on_method(&mut method);
+    }
+    method
+}
+
+/// Generate a copy of the session configured to submit `request` as
+/// `method`.
+fn method_session<R: PandoraJsonApiRequest + ?Sized>(
+    request: &R,
+    session: &PandoraSession,
+    method: &str,
+) -> std::result::Result<PandoraSession, R::Error> {
+    let mut tmp_session = session.clone();
+    tmp_session.arg("method", method).json(request.get_json()?);
+    if request.encrypt_request() {
+        tmp_session.encrypted();
+    }
+    Ok(tmp_session)
+}
+
+/// Whether the API method only reads state, or logs in, so that submitting
+/// it repeatedly has the same effect as submitting it once.
+fn is_idempotent_method(method: &str) -> bool {
+    let name = method.rsplit('.').next().unwrap_or_default();
+    const READ_PREFIXES: [&str; 6] = ["get", "search", "can", "validate", "explain", "check"];
+    name.ends_with("Login") || READ_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// Whether Pandora rejected the request because the submitted sync time
+/// strayed too far from the server clock.
+fn is_bad_sync_time(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::PandoraJsonRequestError(e) if e.kind() == JsonErrorKind::InsufficientConnectivity
+    )
+}
+
+/// Post a request through the session transport and deserialize the API
+/// response, passing it through the session middleware along the way.  API
+/// error codes are decoded with `error_kind`.
+async fn submit_request<T: Debug + serde::de::DeserializeOwned>(
+    session: &PandoraSession,
+    method: &str,
+    url: url::Url,
+    body: String,
+    error_kind: &(dyn Fn(u32, Option<&str>) -> JsonErrorKind + Sync),
+) -> std::result::Result<T, Error> {
+    let submission = session.transport().post(url, body);
+    let mut response: TransportResponse = match session.method_timeout(method) {
+        Some(timeout) => tokio::time::timeout(timeout, submission)
+            .await
+            .map_err(|_| Error::RequestTimeout(method.to_string(), timeout))??,
+        None => submission.await?,
+    };
+    for middleware in session.middleware().iter().rev() {
+        middleware.on_response_body(method, session.call_id, &mut response);
+    }
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("http_status", response.status);
+
+    let mut response_obj: PandoraResponse<serde_json::Value> =
+        match serde_json::from_str(&response.body) {
+            Ok(response_obj) => response_obj,
+            // Only blame the http status if the body wasn't an API response
+            Err(_) if !response.is_success() => {
+                return Err(Error::HttpStatusError(response.status))
             }
-            res => res,
+            Err(e) => return Err(e.into()),
+        };
+    for middleware in session.middleware().iter().rev() {
+        middleware.on_response(method, session.call_id, &mut response_obj);
+    }
+    if let Some(detector) = &session.drift_detector {
+        detector.check::<T>(method, &response_obj);
+    }
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("stat", tracing::field::debug(response_obj.stat));
+        if let Some(code) = response_obj.code {
+            span.record("error_code", code);
         }
-        .map_err(Self::Error::from)
     }
+
+    let response_obj = PandoraResponse::<T> {
+        stat: response_obj.stat,
+        result: response_obj
+            .result
+            .map(serde_json::from_value)
+            .transpose()?,
+        message: response_obj.message,
+        code: response_obj.code,
+    };
+    let result: std::result::Result<T, JsonError> = response_obj.into();
+    Ok(result.map_err(|mut e| {
+        if let Some(code) = e.code {
+            e.kind = error_kind(code, e.message());
+        }
+        e
+    })?)
 }
 
 /// Trait for getting the JSON API endpoint specific to the partner account
@@ -382,16 +893,18 @@ impl ToEndpoint for String {
 /// a partnerLogin.
 pub trait ToEncryptionTokens {
     /// Returns the encryption key to be used for this session.
-    fn to_encrypt_key(&self) -> String;
-    /// Encrypt the provided data using the session encryption key.
-    fn encrypt(&self, data: &str) -> String {
-        crypt::encrypt(&self.to_encrypt_key(), data)
+    fn to_encrypt_key(&self) -> &Secret;
+    /// Encrypt the provided data using the session encryption key.  This
+    /// fails if the key has an unsupported length.
+    fn encrypt(&self, data: &str) -> Result<String, Error> {
+        crypt::encrypt(self.to_encrypt_key().expose_secret(), data)
     }
     /// Returns the decryption key to be used for this session.
-    fn to_decrypt_key(&self) -> String;
-    /// Decrypt the provided data using the session decryption key.
-    fn decrypt(&self, hex_data: &str) -> Vec<u8> {
-        crypt::decrypt(&self.to_decrypt_key(), hex_data)
+    fn to_decrypt_key(&self) -> &Secret;
+    /// Decrypt the provided data using the session decryption key.  This
+    /// fails if the key has an unsupported length, or the data is malformed.
+    fn decrypt(&self, hex_data: &str) -> Result<Vec<u8>, Error> {
+        crypt::decrypt(self.to_decrypt_key().expose_secret(), hex_data)
     }
 }
 
@@ -474,29 +987,45 @@ impl ToTrackingToken for &str {
 
 /// A convenience type that can produce valid PartnerLogin instances for a
 /// number of different endpoints and device types.
-#[derive(Debug, Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
+///
+/// Besides the built-in partners, a Partner can be deserialized from a
+/// configuration file or loaded from environment variables, so that partner
+/// credentials can be changed without rebuilding.  Field names are accepted
+/// in either camelCase or snake_case; `version` defaults to `"5"` and
+/// `endpointHost` to `"tuner.pandora.com"`.  Deserialized partners are
+/// checked with `validate()`.
+///
+/// ``` toml
+/// username = "android"
+/// password = "AC7IBG09A3DTSYM4R41UJWL07VLN8JI7"
+/// device_model = "android-generic"
+/// encrypt_password = "6#26FRL$ZWD"
+/// decrypt_password = "R=U!LH$O2B#"
+/// endpoint_host = "tuner.pandora.com"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", try_from = "PartnerConfig")]
 pub struct Partner {
     /// The partner login name (not the account-holder's username)
     /// used to authenticate the application with the Pandora service.
     pub username: String,
     /// The partner login password (not the account-holder's username)
     /// used to authenticate the application with the Pandora service.
-    pub password: String,
+    pub password: Secret,
     /// The partner device model name.
     pub device_model: String,
     /// The Pandora JSON API version
     pub version: String,
     /// The encryption key associated with this partner login
-    #[serde(skip)]
-    pub encrypt_password: String,
+    #[serde(skip_serializing)]
+    pub encrypt_password: Secret,
     /// The decryption key associated with this partner login
-    #[serde(skip)]
-    pub decrypt_password: String,
+    #[serde(skip_serializing)]
+    pub decrypt_password: Secret,
     /// The hostname for the endpoint used to communicate with the Pandora API.
     /// This is a bare hostname, without scheme/protocol.  This value will later
     /// be combined with a scheme and path to create a complete, valid URL.
-    #[serde(skip)]
+    #[serde(skip_serializing)]
     pub endpoint_host: String,
 }
 
@@ -505,11 +1034,11 @@ impl Partner {
     pub fn new_android() -> Self {
         Self {
             username: "android".to_string(),
-            password: "AC7IBG09A3DTSYM4R41UJWL07VLN8JI7".to_string(),
+            password: Secret::from("AC7IBG09A3DTSYM4R41UJWL07VLN8JI7"),
             device_model: "android-generic".to_string(),
             version: "5".to_string(),
-            decrypt_password: "R=U!LH$O2B#".to_string(),
-            encrypt_password: "6#26FRL$ZWD".to_string(),
+            decrypt_password: Secret::from("R=U!LH$O2B#"),
+            encrypt_password: Secret::from("6#26FRL$ZWD"),
             endpoint_host: "tuner.pandora.com".to_string(),
         }
     }
@@ -518,11 +1047,11 @@ impl Partner {
     pub fn new_ios() -> Self {
         Self {
             username: "iphone".to_string(),
-            password: "P2E4FC0EAD3*878N92B2CDp34I0B1@388137C".to_string(),
+            password: Secret::from("P2E4FC0EAD3*878N92B2CDp34I0B1@388137C"),
             device_model: "IP01".to_string(),
             version: "5".to_string(),
-            decrypt_password: "20zE1E47BE57$51".to_string(),
-            encrypt_password: "721^26xE22776".to_string(),
+            decrypt_password: Secret::from("20zE1E47BE57$51"),
+            encrypt_password: Secret::from("721^26xE22776"),
             endpoint_host: "tuner.pandora.com".to_string(),
         }
     }
@@ -531,11 +1060,11 @@ impl Partner {
     pub fn new_palm() -> Self {
         Self {
             username: "palm".to_string(),
-            password: "IUC7IBG09A3JTSYM4N11UJWL07VLH8JP0".to_string(),
+            password: Secret::from("IUC7IBG09A3JTSYM4N11UJWL07VLH8JP0"),
             device_model: "pre".to_string(),
             version: "5".to_string(),
-            decrypt_password: "E#U$MY$O2B=".to_string(),
-            encrypt_password: "%526CBL$ZU3".to_string(),
+            decrypt_password: Secret::from("E#U$MY$O2B="),
+            encrypt_password: Secret::from("%526CBL$ZU3"),
             endpoint_host: "tuner.pandora.com".to_string(),
         }
     }
@@ -544,11 +1073,11 @@ impl Partner {
     pub fn new_windows_mobile() -> Self {
         Self {
             username: "winmo".to_string(),
-            password: "ED227E10a628EB0E8Pm825Dw7114AC39".to_string(),
+            password: Secret::from("ED227E10a628EB0E8Pm825Dw7114AC39"),
             device_model: "VERIZON_MOTOQ9C".to_string(),
             version: "5".to_string(),
-            decrypt_password: "7D671jt0C5E5d251".to_string(),
-            encrypt_password: "v93C8C2s12E0EBD".to_string(),
+            decrypt_password: Secret::from("7D671jt0C5E5d251"),
+            encrypt_password: Secret::from("v93C8C2s12E0EBD"),
             endpoint_host: "tuner.pandora.com".to_string(),
         }
     }
@@ -557,11 +1086,11 @@ impl Partner {
     pub fn new_desktop_air() -> Self {
         Self {
             username: "pandora one".to_string(),
-            password: "TVCKIBGS9AO9TSYLNNFUML0743LH82D".to_string(),
+            password: Secret::from("TVCKIBGS9AO9TSYLNNFUML0743LH82D"),
             device_model: "D01".to_string(),
             version: "5".to_string(),
-            decrypt_password: "U#IO$RZPAB%VX2".to_string(),
-            encrypt_password: "2%3WCL*JU$MP]4".to_string(),
+            decrypt_password: Secret::from("U#IO$RZPAB%VX2"),
+            encrypt_password: Secret::from("2%3WCL*JU$MP]4"),
             endpoint_host: "internal-tuner.pandora.com".to_string(),
         }
     }
@@ -570,19 +1099,125 @@ impl Partner {
     pub fn new_vista_widget() -> Self {
         Self {
             username: "windowsgadget".to_string(),
-            password: "EVCCIBGS9AOJTSYMNNFUML07VLH8JYP0".to_string(),
+            password: Secret::from("EVCCIBGS9AOJTSYMNNFUML07VLH8JYP0"),
             device_model: "WG01".to_string(),
             version: "5".to_string(),
-            decrypt_password: "E#IO$MYZOAB%FVR2".to_string(),
-            encrypt_password: "%22CML*ZU$8YXP[1".to_string(),
+            decrypt_password: Secret::from("E#IO$MYZOAB%FVR2"),
+            encrypt_password: Secret::from("%22CML*ZU$8YXP[1"),
             endpoint_host: "internal-tuner.pandora.com".to_string(),
         }
     }
 
+    /// Load a Partner from the json `config`.
+    pub fn from_json(config: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(config)?)
+    }
+
+    /// Load a Partner from the toml `config`.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(config: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(config)?)
+    }
+
+    /// Load a Partner from the configuration file at `path`.  Files with a
+    /// `.toml` extension are parsed as toml (which requires the `toml`
+    /// feature), and all others as json.
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            #[cfg(feature = "toml")]
+            return Self::from_toml(&config);
+            #[cfg(not(feature = "toml"))]
+            return Err(Error::InvalidPartner(format!(
+                "{} is toml, which requires the toml feature",
+                path.display()
+            )));
+        }
+        Self::from_json(&config)
+    }
+
+    /// Load a Partner from the environment variables `PANDORA_PARTNER_USERNAME`,
+    /// `PANDORA_PARTNER_PASSWORD`, `PANDORA_PARTNER_DEVICE_MODEL`,
+    /// `PANDORA_PARTNER_ENCRYPT_PASSWORD`, `PANDORA_PARTNER_DECRYPT_PASSWORD`,
+    /// and optionally `PANDORA_PARTNER_VERSION` and
+    /// `PANDORA_PARTNER_ENDPOINT_HOST`.
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_env_prefix("PANDORA_PARTNER_")
+    }
+
+    /// Load a Partner from environment variables named as for `from_env()`,
+    /// but with `prefix` in place of `PANDORA_PARTNER_`.
+    pub fn from_env_prefix(prefix: &str) -> Result<Self, Error> {
+        let optional = |name: &str| std::env::var(format!("{prefix}{name}")).ok();
+        let required = |name: &str| {
+            optional(name)
+                .ok_or_else(|| Error::InvalidPartner(format!("{prefix}{name} is not set")))
+        };
+        Self::try_from(PartnerConfig {
+            username: required("USERNAME")?,
+            password: required("PASSWORD")?.into(),
+            device_model: required("DEVICE_MODEL")?,
+            version: optional("VERSION").unwrap_or_else(default_partner_version),
+            encrypt_password: required("ENCRYPT_PASSWORD")?.into(),
+            decrypt_password: required("DECRYPT_PASSWORD")?.into(),
+            endpoint_host: optional("ENDPOINT_HOST").unwrap_or_else(default_endpoint_host),
+        })
+    }
+
+    /// Check that the encryption keys have a length supported by Blowfish (4
+    /// to 56 bytes), and that the endpoint host forms a valid url.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (name, key) in [
+            ("encrypt", &self.encrypt_password),
+            ("decrypt", &self.decrypt_password),
+        ] {
+            let key = key.expose_secret();
+            if !crypt::is_valid_key(key) {
+                return Err(Error::InvalidPartner(format!(
+                    "{} key must be {} to {} bytes long, not {}",
+                    name,
+                    crypt::MIN_KEY_LEN,
+                    crypt::MAX_KEY_LEN,
+                    key.len()
+                )));
+            }
+        }
+        if self.endpoint_host.is_empty() {
+            return Err(Error::InvalidPartner(
+                "endpoint host must not be empty".to_string(),
+            ));
+        }
+        url::Url::parse(&self.to_endpoint())?;
+        Ok(())
+    }
+
+    /// The User-Agent identifying this library and the partner device model,
+    /// e.g. `pandora-api/0.6.2 (android-generic)`.
+    pub fn user_agent(&self) -> String {
+        format!(
+            "{}/{} ({})",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            self.device_model
+        )
+    }
+
     /// Initialize a PandoraSession using the corresponding Partner
-    /// tokens and endpoint.
+    /// tokens and endpoint.  For the well-known endpoint hosts, the session
+    /// fails over to the other host when this one can't be reached.
     pub fn init_session(&self) -> PandoraSession {
-        PandoraSession::new(None, self, self)
+        let mut session = PandoraSession::new(None, self, self);
+        if let Some(host) = self.alternate_endpoint_host() {
+            session.add_fallback_endpoint(format!("https://{}/services/json", host));
+        }
+        session
+    }
+
+    /// Initialize a PandoraSession using the corresponding Partner
+    /// tokens and endpoint, submitting requests through the provided transport.
+    pub fn init_session_with_transport(&self, transport: Arc<dyn Transport>) -> PandoraSession {
+        PandoraSession::new_with_transport(transport, self, self)
     }
 
     /// Generate a PartnerLogin instance from this object that can be
@@ -598,11 +1233,24 @@ impl Partner {
     }
 
     /// Convenience method for submitting the partner login request for this
-    /// partner.
-    pub async fn login(&self, session: &mut PandoraSession) -> Result<PartnerLoginResponse, Error> {
-        let response = self.to_partner_login().response(session).await?;
-        session.update_partner_tokens(&response);
-        Ok(response)
+    /// partner.  When endpoint discovery is enabled on the session, the
+    /// service urls are requested too, and any API endpoints among them are
+    /// added to the session endpoints.
+    pub async fn login(&self, session: &PandoraSession) -> Result<PartnerLoginResponse, Error> {
+        self.to_partner_login()
+            .include_urls(session.endpoint_discovery())
+            .merge_response(session)
+            .await
+    }
+
+    /// The other well-known API host, to fail over to if this partner's
+    /// endpoint host can't be reached.
+    pub(crate) fn alternate_endpoint_host(&self) -> Option<&'static str> {
+        match self.endpoint_host.as_str() {
+            "tuner.pandora.com" => Some("internal-tuner.pandora.com"),
+            "internal-tuner.pandora.com" => Some("tuner.pandora.com"),
+            _ => None,
+        }
     }
 }
 
@@ -614,12 +1262,12 @@ impl Default for Partner {
 }
 
 impl ToEncryptionTokens for Partner {
-    fn to_encrypt_key(&self) -> String {
-        self.encrypt_password.clone()
+    fn to_encrypt_key(&self) -> &Secret {
+        &self.encrypt_password
     }
 
-    fn to_decrypt_key(&self) -> String {
-        self.decrypt_password.clone()
+    fn to_decrypt_key(&self) -> &Secret {
+        &self.decrypt_password
     }
 }
 
@@ -630,19 +1278,72 @@ impl ToEndpoint for Partner {
     }
 }
 
+fn default_partner_version() -> String {
+    "5".to_string()
+}
+
+fn default_endpoint_host() -> String {
+    "tuner.pandora.com".to_string()
+}
+
+/// The deserialized form of Partner, before validation.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PartnerConfig {
+    username: String,
+    password: Secret,
+    #[serde(alias = "device_model")]
+    device_model: String,
+    #[serde(default = "default_partner_version")]
+    version: String,
+    #[serde(alias = "encrypt_password")]
+    encrypt_password: Secret,
+    #[serde(alias = "decrypt_password")]
+    decrypt_password: Secret,
+    #[serde(alias = "endpoint_host", default = "default_endpoint_host")]
+    endpoint_host: String,
+}
+
+impl TryFrom<PartnerConfig> for Partner {
+    type Error = Error;
+
+    fn try_from(config: PartnerConfig) -> std::result::Result<Self, Self::Error> {
+        let partner = Self {
+            username: config.username,
+            password: config.password,
+            device_model: config.device_model,
+            version: config.version,
+            encrypt_password: config.encrypt_password,
+            decrypt_password: config.decrypt_password,
+            endpoint_host: config.endpoint_host,
+        };
+        partner.validate()?;
+        Ok(partner)
+    }
+}
+
 /// A convenience type that holds all the values necessary to maintain an active
 /// session with the Pandora service.
-#[derive(Debug, Clone)]
+///
+/// SessionTokens can be serialized, so that a login can be saved and then
+/// reused by a later process with `PandoraSession::restore()`.  Because
+/// `Instant` values can't be saved, the serialized form records the sync time
+/// along with the wall-clock time it was saved at, and the sync time is
+/// advanced by the wall-clock time elapsed when it is loaded again.  Note that
+/// the serialized form includes the encryption keys and auth tokens, and
+/// should be stored accordingly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "SavedSessionTokens", try_from = "SavedSessionTokens")]
 pub struct SessionTokens {
     /// The key used to encrypt the body of certain API requests.
-    pub encrypt_key: String,
+    pub encrypt_key: Secret,
     /// The key used to decrypt certain values from the body of certain API
     /// responses.
-    pub decrypt_key: String,
+    pub decrypt_key: Secret,
     /// The partner id token returned by the partner login request
     pub partner_id: Option<String>,
     /// The partner auth token returned by the partner login request
-    pub partner_token: Option<String>,
+    pub partner_token: Option<Secret>,
     /// The sync time token returned by the partner login request.  This is
     /// private so that it will be updated/read by accessor methods that
     /// correctly adjust for the time offset that needs to be added on.
@@ -657,46 +1358,103 @@ pub struct SessionTokens {
     /// The user id token returned by the user login request
     pub user_id: Option<String>,
     /// The user auth token returned by the user login request
-    pub user_token: Option<String>,
+    pub user_token: Option<Secret>,
+    /// The partner login request that issued the partner tokens, which is
+    /// repeated to re-sync the time base.  This isn't saved.
+    partner_login: Option<PartnerLogin>,
+    /// The cipher initialized with encrypt_key, shared between clones, so
+    /// that the key schedule isn't recomputed for every request.
+    encrypt_cipher: Option<Arc<crypt::Cipher>>,
+    /// The cipher initialized with decrypt_key.
+    decrypt_cipher: Option<Arc<crypt::Cipher>>,
 }
 
 impl SessionTokens {
     /// Initialize a SessionTokens object with only the encryption keys,
     /// as those are needed even before authentication begins
     pub fn new<T: ToEncryptionTokens>(to_encryption_tokens: &T) -> Self {
+        let encrypt_key = to_encryption_tokens.to_encrypt_key().clone();
+        let decrypt_key = to_encryption_tokens.to_decrypt_key().clone();
         Self {
-            encrypt_key: to_encryption_tokens.to_encrypt_key(),
-            decrypt_key: to_encryption_tokens.to_decrypt_key(),
+            encrypt_cipher: cached_cipher(&encrypt_key),
+            decrypt_cipher: cached_cipher(&decrypt_key),
+            encrypt_key,
+            decrypt_key,
             partner_id: None,
             partner_token: None,
             sync_time: None,
             local_time_base: None,
             user_id: None,
             user_token: None,
+            partner_login: None,
         }
     }
 
     /// Update the current SessionTokens instance using values from the
-    /// response to the PartnerLogin request.
-    pub fn update_partner_tokens<T: ToPartnerTokens>(&mut self, to_partner_tokens: &T) {
+    /// response to the PartnerLogin request.  This fails if the sync time
+    /// can't be decrypted, in which case the partner tokens are still
+    /// updated, but the sync time is cleared.
+    pub fn update_partner_tokens<T: ToPartnerTokens>(
+        &mut self,
+        to_partner_tokens: &T,
+    ) -> Result<(), Error> {
         self.partner_id = to_partner_tokens.to_partner_id();
-        self.partner_token = to_partner_tokens.to_partner_token();
+        self.partner_token = to_partner_tokens.to_partner_token().map(Secret::from);
+        self.update_sync_time(to_partner_tokens).inspect_err(|_| {
+            self.clear_sync_time();
+        })
+    }
+
+    /// Update the sync time alone using the value from the response to the
+    /// PartnerLogin request, leaving the partner tokens unchanged.  This fails
+    /// if the sync time can't be decrypted, in which case the current sync
+    /// time is kept.
+    pub fn update_sync_time<T: ToPartnerTokens>(
+        &mut self,
+        to_partner_tokens: &T,
+    ) -> Result<(), Error> {
+        if let Some(sync_time) = to_partner_tokens.to_sync_time() {
+            let sync_time = self.decrypt_sync_time(&sync_time)?;
+            self.set_sync_time(sync_time);
+        }
+        Ok(())
+    }
+
+    fn decrypt_sync_time(&self, encrypted: &str) -> Result<u64, Error> {
         // The first four bytes are, reportedly, garbage, but I suspect it's
         // actually supposed to function as a salt that was intended to make it
         // difficult to recover the decryption keys.
-        if let Some(sync_time) = to_partner_tokens.to_sync_time() {
-            let sync_time_bytes: Vec<u8> =
-                self.decrypt(&sync_time).iter().skip(4).cloned().collect();
-            let sync_time_str = std::str::from_utf8(&sync_time_bytes).unwrap_or("0");
-            self.set_sync_time(sync_time_str.parse::<u64>().unwrap_or(0));
-        }
+        let sync_time_bytes = self
+            .decrypt(encrypted)
+            .map_err(|e| Error::InvalidSyncTime(e.to_string()))?;
+        let sync_time_bytes = sync_time_bytes
+            .get(4..)
+            .filter(|bytes| !bytes.is_empty())
+            .ok_or_else(|| Error::InvalidSyncTime(String::from("too short")))?;
+        let sync_time_str = std::str::from_utf8(sync_time_bytes)
+            .map_err(|_| Error::InvalidSyncTime(String::from("not valid UTF-8")))?;
+        sync_time_str
+            .parse::<u64>()
+            .map_err(|_| Error::InvalidSyncTime(format!("{:?} is not a number", sync_time_str)))
+    }
+
+    /// The partner login request that issued the current partner tokens, if
+    /// known.
+    pub fn get_partner_login(&self) -> Option<&PartnerLogin> {
+        self.partner_login.as_ref()
+    }
+
+    /// Record the partner login request that issued the current partner
+    /// tokens.
+    pub(crate) fn set_partner_login(&mut self, partner_login: PartnerLogin) {
+        self.partner_login = Some(partner_login);
     }
 
     /// Update the current SessionTokens instance using values from the
     /// response to the UserLogin request.
     pub fn update_user_tokens<T: ToUserTokens>(&mut self, to_user_tokens: &T) {
         self.user_id = to_user_tokens.to_user_id();
-        self.user_token = to_user_tokens.to_user_token();
+        self.user_token = to_user_tokens.to_user_token().map(Secret::from);
     }
 
     /// The current server time as of the last request.  Submitted requests must
@@ -731,17 +1489,122 @@ impl SessionTokens {
         self.user_id = None;
         self.user_token = None;
     }
+
+    /// The token passed as the auth_token query argument: the user token, if
+    /// available, otherwise the partner token.
+    pub fn auth_token(&self) -> Option<&Secret> {
+        self.user_token.as_ref().or(self.partner_token.as_ref())
+    }
+}
+
+/// The version of the serialized form of SessionTokens.
+const SAVED_SESSION_TOKENS_VERSION: u32 = 1;
+
+/// The serialized form of SessionTokens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedSessionTokens {
+    version: u32,
+    encrypt_key: Secret,
+    decrypt_key: Secret,
+    partner_id: Option<String>,
+    partner_token: Option<Secret>,
+    /// The sync time as of `saved_at`
+    sync_time: Option<u64>,
+    /// Seconds since the unix epoch, by the local wall clock, when the
+    /// tokens were saved
+    saved_at: u64,
+    user_id: Option<String>,
+    user_token: Option<Secret>,
+}
+
+/// The current wall-clock time, in seconds since the unix epoch.
+fn wall_clock_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl From<SessionTokens> for SavedSessionTokens {
+    fn from(tokens: SessionTokens) -> Self {
+        Self {
+            version: SAVED_SESSION_TOKENS_VERSION,
+            sync_time: tokens.get_sync_time(),
+            saved_at: wall_clock_secs(),
+            encrypt_key: tokens.encrypt_key,
+            decrypt_key: tokens.decrypt_key,
+            partner_id: tokens.partner_id,
+            partner_token: tokens.partner_token,
+            user_id: tokens.user_id,
+            user_token: tokens.user_token,
+        }
+    }
+}
+
+impl TryFrom<SavedSessionTokens> for SessionTokens {
+    type Error = String;
+
+    fn try_from(saved: SavedSessionTokens) -> std::result::Result<Self, Self::Error> {
+        if saved.version != SAVED_SESSION_TOKENS_VERSION {
+            return Err(format!(
+                "Unsupported session tokens version {}",
+                saved.version
+            ));
+        }
+        let mut tokens = Self {
+            encrypt_cipher: cached_cipher(&saved.encrypt_key),
+            decrypt_cipher: cached_cipher(&saved.decrypt_key),
+            encrypt_key: saved.encrypt_key,
+            decrypt_key: saved.decrypt_key,
+            partner_id: saved.partner_id,
+            partner_token: saved.partner_token,
+            sync_time: None,
+            local_time_base: None,
+            user_id: saved.user_id,
+            user_token: saved.user_token,
+            partner_login: None,
+        };
+        if let Some(sync_time) = saved.sync_time {
+            // If the wall clock went backwards, assume no time has passed
+            let elapsed = wall_clock_secs().saturating_sub(saved.saved_at);
+            tokens.set_sync_time(sync_time + elapsed);
+        }
+        Ok(tokens)
+    }
 }
 
 impl ToEncryptionTokens for SessionTokens {
     /// Retrieve the encryption key for this session
-    fn to_encrypt_key(&self) -> String {
-        self.encrypt_key.clone()
+    fn to_encrypt_key(&self) -> &Secret {
+        &self.encrypt_key
     }
     /// Retrieve the decryption key for this session
-    fn to_decrypt_key(&self) -> String {
-        self.decrypt_key.clone()
+    fn to_decrypt_key(&self) -> &Secret {
+        &self.decrypt_key
+    }
+    /// Encrypt the provided data using the cached session encryption cipher
+    fn encrypt(&self, data: &str) -> Result<String, Error> {
+        match &self.encrypt_cipher {
+            Some(cipher) if cipher.key() == &self.encrypt_key => Ok(cipher.encrypt(data)),
+            // The key was replaced, or isn't valid
+            _ => crypt::encrypt(self.encrypt_key.expose_secret(), data),
+        }
     }
+    /// Decrypt the provided data using the cached session decryption cipher
+    fn decrypt(&self, hex_data: &str) -> Result<Vec<u8>, Error> {
+        match &self.decrypt_cipher {
+            Some(cipher) if cipher.key() == &self.decrypt_key => cipher.decrypt(hex_data),
+            // The key was replaced, or isn't valid
+            _ => crypt::decrypt(self.decrypt_key.expose_secret(), hex_data),
+        }
+    }
+}
+
+/// Initialize a cipher for `key` to cache on SessionTokens, unless the key
+/// isn't valid, in which case encryption reports the error when attempted.
+fn cached_cipher(key: &Secret) -> Option<Arc<crypt::Cipher>> {
+    crypt::Cipher::new(key.expose_secret()).ok().map(Arc::new)
 }
 
 impl<T: ToEncryptionTokens> From<&T> for SessionTokens {
@@ -750,41 +1613,291 @@ impl<T: ToEncryptionTokens> From<&T> for SessionTokens {
     }
 }
 
-/// A representation of a moment in time.
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "camelCase")]
+/// A representation of a moment in time, as returned by Pandora: a
+/// serialized Java `Date`, giving the epoch time along with its calendar
+/// fields in the local time of the server.
+///
+/// Timestamps are also accepted as a bare epoch time, and serialize back to
+/// the same form they were read in.  Epoch times are usually in milliseconds,
+/// but some responses give them in seconds; `get_epoch_millis()` normalizes
+/// both.  Timestamps are ordered and compared by the moment they refer to,
+/// regardless of the time zone or the form they were read in.
+#[derive(Debug, Clone, Copy)]
 pub struct Timestamp {
-    /// The offset from UTC in minutes
-    _timezone_offset: u32,
-    /// Unix epoch time for the timezone offset
-    time: i64,
-    /// Year, adjusted for timezone offset
-    _year: u32,
-    /// Month, adjusted for timezone offset
-    _month: u8,
     /// Day of month, adjusted for timezone offset
-    _day: u8,
+    date: u32,
+    /// Day of the week, from 0 for Sunday, adjusted for timezone offset
+    day: u32,
     /// Hour, adjusted for timezone offset
-    _hours: u8,
+    hours: u32,
     /// Minute, adjusted for timezone offset
-    _minutes: u8,
+    minutes: u32,
+    /// Month, from 0 for January, adjusted for timezone offset
+    month: u32,
+    /// Nanoseconds within the second
+    nanos: u32,
     /// Seconds, adjusted for timezone offset
-    _seconds: u8,
-    /// Unknown
-    _date: u8,
+    seconds: u32,
+    /// Unix epoch time, as returned
+    time: i64,
+    /// The offset of local time from UTC in minutes, positive west of UTC
+    timezone_offset: i32,
+    /// Years since 1900, adjusted for timezone offset
+    year: i32,
+    /// Whether the timestamp was read from a bare epoch time
+    bare_epoch: bool,
 }
 
-impl From<Timestamp> for chrono::DateTime<chrono::Utc> {
-    fn from(ts: Timestamp) -> chrono::DateTime<chrono::Utc> {
-        // TODO: Figure out proper handling of timezoneOffset
-        // e.g. is it signed? is the provided time Utc (and offset is applied
-        // to get local) or is it local (and tells the offset used to determine
-        // local)? is it the local time of the user, or the local time for the
-        // system that generated the timestamp?
-        let dt = chrono::DateTime::from_timestamp(ts.time, 0).expect("Invalid date/time timestamp");
-        let naive_utc = dt.naive_utc();
-        let offset = *dt.offset();
-        chrono::DateTime::from_naive_utc_and_offset(naive_utc, offset)
+/// Epoch times with a smaller magnitude than this are taken to be in
+/// seconds, as in milliseconds they would be within a few years of 1970.
+const MIN_EPOCH_MILLIS: i64 = 100_000_000_000;
+
+/// Normalize an epoch time in either seconds or milliseconds to milliseconds.
+fn epoch_millis(time: i64) -> i64 {
+    if time.abs() < MIN_EPOCH_MILLIS {
+        time.saturating_mul(1000)
+    } else {
+        time
+    }
+}
+
+impl Timestamp {
+    /// Create a new Timestamp for the millisecond epoch time `millis`, with
+    /// its calendar fields in UTC.
+    pub fn from_epoch_millis(millis: i64) -> Self {
+        let mut timestamp = Self {
+            date: 0,
+            day: 0,
+            hours: 0,
+            minutes: 0,
+            month: 0,
+            nanos: 0,
+            seconds: 0,
+            time: millis,
+            timezone_offset: 0,
+            year: 0,
+            bare_epoch: false,
+        };
+        if let Some(dt) = chrono::DateTime::from_timestamp_millis(millis) {
+            use chrono::{Datelike, Timelike};
+            timestamp.date = dt.day();
+            timestamp.day = dt.weekday().num_days_from_sunday();
+            timestamp.hours = dt.hour();
+            timestamp.minutes = dt.minute();
+            timestamp.month = dt.month0();
+            timestamp.nanos = dt.nanosecond();
+            timestamp.seconds = dt.second();
+            timestamp.year = dt.year() - 1900;
+        }
+        timestamp
+    }
+
+    /// Get the epoch time as it was returned, in either seconds or
+    /// milliseconds.
+    pub fn get_time(&self) -> i64 {
+        self.time
+    }
+
+    /// Get the epoch time in milliseconds.
+    pub fn get_epoch_millis(&self) -> i64 {
+        epoch_millis(self.time)
+    }
+
+    /// Get the offset of local time from UTC in minutes.  Like Java's
+    /// `Date.getTimezoneOffset()`, this is positive for time zones west of
+    /// UTC, e.g. 420 for UTC-07:00.
+    pub fn get_timezone_offset(&self) -> i32 {
+        self.timezone_offset
+    }
+
+    /// Get the local time zone as an offset from UTC, if the timezone offset
+    /// is valid.
+    pub fn get_utc_offset(&self) -> Option<chrono::FixedOffset> {
+        self.timezone_offset
+            .checked_mul(60)
+            .and_then(chrono::FixedOffset::west_opt)
+    }
+
+    /// Get the year, in local time.
+    pub fn get_year(&self) -> i32 {
+        self.year + 1900
+    }
+
+    /// Get the month, from 1 for January, in local time.
+    pub fn get_month(&self) -> u32 {
+        self.month + 1
+    }
+
+    /// Get the day of the month, in local time.
+    pub fn get_day_of_month(&self) -> u32 {
+        self.date
+    }
+
+    /// Get the day of the week, from 0 for Sunday, in local time.
+    pub fn get_weekday(&self) -> u32 {
+        self.day
+    }
+
+    /// Get the hour, in local time.
+    pub fn get_hours(&self) -> u32 {
+        self.hours
+    }
+
+    /// Get the minute, in local time.
+    pub fn get_minutes(&self) -> u32 {
+        self.minutes
+    }
+
+    /// Get the second.
+    pub fn get_seconds(&self) -> u32 {
+        self.seconds
+    }
+
+    /// Get the nanoseconds within the second.
+    pub fn get_nanos(&self) -> u32 {
+        self.nanos
+    }
+}
+
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_epoch_millis() == other.get_epoch_millis()
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.get_epoch_millis().cmp(&other.get_epoch_millis())
+    }
+}
+
+impl std::hash::Hash for Timestamp {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.get_epoch_millis().hash(state);
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// The calendar fields are taken from the epoch time if they're
+        /// missing.
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct JavaDate {
+            time: i64,
+            #[serde(default)]
+            timezone_offset: i32,
+            date: Option<u32>,
+            day: Option<u32>,
+            hours: Option<u32>,
+            minutes: Option<u32>,
+            month: Option<u32>,
+            nanos: Option<u32>,
+            seconds: Option<u32>,
+            year: Option<i32>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            EpochTime(i64),
+            Object(JavaDate),
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::EpochTime(time) => Self {
+                time,
+                bare_epoch: true,
+                ..Self::from_epoch_millis(epoch_millis(time))
+            },
+            Repr::Object(java) => {
+                let utc = Self::from_epoch_millis(epoch_millis(java.time));
+                Self {
+                    date: java.date.unwrap_or(utc.date),
+                    day: java.day.unwrap_or(utc.day),
+                    hours: java.hours.unwrap_or(utc.hours),
+                    minutes: java.minutes.unwrap_or(utc.minutes),
+                    month: java.month.unwrap_or(utc.month),
+                    nanos: java.nanos.unwrap_or(utc.nanos),
+                    seconds: java.seconds.unwrap_or(utc.seconds),
+                    time: java.time,
+                    timezone_offset: java.timezone_offset,
+                    year: java.year.unwrap_or(utc.year),
+                    bare_epoch: false,
+                }
+            }
+        })
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        if self.bare_epoch {
+            return serializer.serialize_i64(self.time);
+        }
+        let mut date = serializer.serialize_struct("Timestamp", 10)?;
+        date.serialize_field("date", &self.date)?;
+        date.serialize_field("day", &self.day)?;
+        date.serialize_field("hours", &self.hours)?;
+        date.serialize_field("minutes", &self.minutes)?;
+        date.serialize_field("month", &self.month)?;
+        date.serialize_field("nanos", &self.nanos)?;
+        date.serialize_field("seconds", &self.seconds)?;
+        date.serialize_field("time", &self.time)?;
+        date.serialize_field("timezoneOffset", &self.timezone_offset)?;
+        date.serialize_field("year", &self.year)?;
+        date.end()
+    }
+}
+
+impl TryFrom<&Timestamp> for chrono::DateTime<chrono::FixedOffset> {
+    type Error = Error;
+
+    /// Convert to the moment in the local time of the timestamp.  This fails
+    /// if the epoch time or timezone offset is out of range.
+    fn try_from(ts: &Timestamp) -> Result<Self, Self::Error> {
+        let offset = ts.get_utc_offset().ok_or_else(|| {
+            Error::InvalidTimestamp(format!("timezone offset {}", ts.timezone_offset))
+        })?;
+        let utc = chrono::DateTime::<chrono::Utc>::try_from(ts)?;
+        Ok(utc.with_timezone(&offset))
+    }
+}
+
+impl TryFrom<Timestamp> for chrono::DateTime<chrono::FixedOffset> {
+    type Error = Error;
+
+    fn try_from(ts: Timestamp) -> Result<Self, Self::Error> {
+        Self::try_from(&ts)
+    }
+}
+
+impl TryFrom<&Timestamp> for chrono::DateTime<chrono::Utc> {
+    type Error = Error;
+
+    /// Convert to the moment in UTC.  This fails if the epoch time is out of
+    /// range.
+    fn try_from(ts: &Timestamp) -> Result<Self, Self::Error> {
+        chrono::DateTime::from_timestamp_millis(ts.get_epoch_millis())
+            .ok_or_else(|| Error::InvalidTimestamp(format!("epoch time {}", ts.time)))
+    }
+}
+
+impl TryFrom<Timestamp> for chrono::DateTime<chrono::Utc> {
+    type Error = Error;
+
+    fn try_from(ts: Timestamp) -> Result<Self, Self::Error> {
+        Self::try_from(&ts)
     }
 }
 
@@ -794,34 +1907,569 @@ mod tests {
 
     use crate::errors::Error;
     use crate::json::auth::user_login;
+    use crate::json::mock::MockPandora;
+    use crate::json::music::get_track;
+    use crate::json::station::{get_playlist, get_station};
+    use crate::json::track::explain_track;
+    use crate::json::user::get_station_list;
 
-    // TODO: lazy_static create a single session and return a RcRefCell to
-    // it instead.  I suspect that some of the transient
-    // InsufficientConnectivity errors are resulting from simultaneously
-    // creating a large number of sessions, creating race conditions or
-    // invalidating tokens.
     pub async fn session_login(partner: &Partner) -> Result<PandoraSession, Error> {
-        let mut session = partner.init_session();
-        let _partner_login = partner.login(&mut session).await?;
-
-        let test_username_raw = include_str!("../../test_username.txt");
-        let test_username = test_username_raw.trim();
-        let test_password_raw = include_str!("../../test_password.txt");
-        let test_password = test_password_raw.trim();
-
-        let user_login = user_login(&mut session, &test_username, &test_password).await?;
+        let session = partner.init_session_with_transport(Arc::new(MockPandora::new()));
+        let _partner_login = partner.login(&session).await?;
+
+        let user_login = user_login(
+            &session,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+        .await?;
         session.update_user_tokens(&user_login);
         Ok(session)
     }
 
+    /// Log in to a session on `transport` with the default partner and the
+    /// mock's user credentials, using `retry_policy`.
+    pub async fn session_login_with(
+        transport: Arc<dyn Transport>,
+        retry_policy: RetryPolicy,
+    ) -> Result<PandoraSession, Error> {
+        let partner = Partner::default();
+        let mut session = partner.init_session_with_transport(transport);
+        session.set_retry_policy(retry_policy);
+        partner.login(&session).await?;
+        user_login(
+            &session,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+        .await?;
+        Ok(session)
+    }
+
+    #[test]
+    fn timestamp_test() {
+        // Pandora's example: 2014-03-28 10:43:30.637 PDT
+        let ts: Timestamp = serde_json::from_value(serde_json::json!({
+            "date": 28,
+            "day": 5,
+            "hours": 10,
+            "minutes": 43,
+            "month": 2,
+            "nanos": 637000000,
+            "seconds": 30,
+            "time": 1396028610637i64,
+            "timezoneOffset": 420,
+            "year": 114
+        }))
+        .expect("Failed deserializing timestamp");
+        assert_eq!(
+            (ts.get_year(), ts.get_month(), ts.get_day_of_month()),
+            (2014, 3, 28)
+        );
+        assert_eq!(ts.get_weekday(), 5);
+        assert_eq!(ts.get_timezone_offset(), 420);
+
+        let local = chrono::DateTime::<chrono::FixedOffset>::try_from(&ts)
+            .expect("Failed converting timestamp");
+        assert_eq!(local.to_rfc3339(), "2014-03-28T10:43:30.637-07:00");
+        let utc =
+            chrono::DateTime::<chrono::Utc>::try_from(ts).expect("Failed converting timestamp");
+        assert_eq!(utc, local);
+
+        let json = serde_json::to_value(ts).expect("Failed serializing timestamp");
+        assert_eq!(json["timezoneOffset"], 420);
+        assert_eq!(json["time"], 1396028610637i64);
+        let round_trip: Timestamp = serde_json::from_value(json).expect("Failed deserializing");
+        assert_eq!(round_trip.get_hours(), 10);
+
+        // Bare epoch times, in either milliseconds or seconds
+        let millis: Timestamp =
+            serde_json::from_str("1396028610637").expect("Failed deserializing");
+        let seconds: Timestamp = serde_json::from_str("1396028610").expect("Failed deserializing");
+        assert_eq!(millis.get_hours(), 17);
+        assert_eq!(seconds.get_epoch_millis(), 1396028610000);
+        assert!(seconds < millis);
+        assert_eq!(ts, millis);
+
+        // Bare epoch times serialize back to the same json, unlike the full
+        // object they compare equal to
+        for bare in [1396028610637i64, 1396028610] {
+            let json = serde_json::json!(bare);
+            let ts: Timestamp = serde_json::from_value(json.clone()).expect("Failed deserializing");
+            assert_eq!(
+                serde_json::to_value(ts).expect("Failed serializing timestamp"),
+                json
+            );
+        }
+        assert!(
+            serde_json::to_value(Timestamp::from_epoch_millis(1396028610637))
+                .expect("Failed serializing timestamp")
+                .is_object()
+        );
+
+        let invalid: Timestamp = serde_json::from_str(r#"{"time": 0, "timezoneOffset": 1500}"#)
+            .expect("Failed deserializing");
+        assert!(matches!(
+            chrono::DateTime::<chrono::FixedOffset>::try_from(invalid),
+            Err(Error::InvalidTimestamp(_))
+        ));
+        let invalid = Timestamp::from_epoch_millis(i64::MAX);
+        assert!(matches!(
+            chrono::DateTime::<chrono::Utc>::try_from(invalid),
+            Err(Error::InvalidTimestamp(_))
+        ));
+    }
+
     #[tokio::test]
     async fn partner_test() {
         let partner = Partner::default();
-        let mut session = partner.init_session();
+        let session = partner.init_session_with_transport(Arc::new(MockPandora::new()));
         let partner_login = partner
-            .login(&mut session)
+            .login(&session)
             .await
             .expect("Failed while performing partner login");
-        session.update_partner_tokens(&partner_login);
+        session
+            .update_partner_tokens(&partner_login)
+            .expect("Failed decrypting partner login sync time");
+    }
+
+    #[test]
+    fn partner_config_test() {
+        let partner = Partner::from_json(
+            r#"{"username": "android", "password": "AC7IBG09A3DTSYM4R41UJWL07VLN8JI7",
+                "deviceModel": "android-generic", "encryptPassword": "6#26FRL$ZWD",
+                "decryptPassword": "R=U!LH$O2B#"}"#,
+        )
+        .expect("Failed loading json partner config");
+        assert_eq!(partner.version, "5");
+        assert_eq!(partner.to_endpoint(), Partner::new_android().to_endpoint());
+        assert_eq!(
+            partner.encrypt_password,
+            Partner::new_android().encrypt_password
+        );
+
+        #[cfg(feature = "toml")]
+        {
+            let partner = Partner::from_toml(
+                r#"
+                username = "pandora one"
+                password = "TVCKIBGS9AO9TSYLNNFUML0743LH82D"
+                device_model = "D01"
+                version = "5"
+                encrypt_password = "2%3WCL*JU$MP]4"
+                decrypt_password = "U#IO$RZPAB%VX2"
+                endpoint_host = "internal-tuner.pandora.com"
+                "#,
+            )
+            .expect("Failed loading toml partner config");
+            assert_eq!(partner.device_model, "D01");
+            assert_eq!(partner.endpoint_host, "internal-tuner.pandora.com");
+        }
+
+        // Keys must have a legal Blowfish length
+        let short_key = r#"{"username": "a", "password": "b", "deviceModel": "c",
+            "encryptPassword": "abc", "decryptPassword": "R=U!LH$O2B#"}"#;
+        match Partner::from_json(short_key) {
+            Err(Error::JsonSerializationError(e)) => assert!(e.to_string().contains("encrypt key")),
+            res => panic!("Unexpected partner config result: {:?}", res),
+        }
+        let mut partner = Partner::new_android();
+        partner.decrypt_password = Secret::from("x".repeat(57));
+        assert!(matches!(partner.validate(), Err(Error::InvalidPartner(_))));
+
+        std::env::set_var("PARTNER_CONFIG_TEST_USERNAME", "iphone");
+        std::env::set_var(
+            "PARTNER_CONFIG_TEST_PASSWORD",
+            "P2E4FC0EAD3*878N92B2CDp34I0B1@388137C",
+        );
+        std::env::set_var("PARTNER_CONFIG_TEST_DEVICE_MODEL", "IP01");
+        std::env::set_var("PARTNER_CONFIG_TEST_ENCRYPT_PASSWORD", "721^26xE22776");
+        match Partner::from_env_prefix("PARTNER_CONFIG_TEST_") {
+            Err(Error::InvalidPartner(e)) => {
+                assert_eq!(e, "PARTNER_CONFIG_TEST_DECRYPT_PASSWORD is not set")
+            }
+            res => panic!("Unexpected partner config result: {:?}", res),
+        }
+        std::env::set_var("PARTNER_CONFIG_TEST_DECRYPT_PASSWORD", "20zE1E47BE57$51");
+        let partner = Partner::from_env_prefix("PARTNER_CONFIG_TEST_")
+            .expect("Failed loading partner config from environment");
+        assert_eq!(
+            partner.decrypt_password,
+            Partner::new_ios().decrypt_password
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_session_test() {
+        let partner = Partner::default();
+        let session = session_login(&partner)
+            .await
+            .expect("Failed initializing login session");
+        let mock = session.transport().clone();
+
+        let saved = serde_json::to_string(&*session.session_tokens())
+            .expect("Failed serializing session tokens");
+        let tokens: SessionTokens =
+            serde_json::from_str(&saved).expect("Failed deserializing session tokens");
+        assert_eq!(tokens.user_token, session.session_tokens().user_token);
+
+        let mut restored = PandoraSession::restore(None, tokens, &partner);
+        restored.set_transport(mock);
+        let _stations = get_station_list(&restored)
+            .await
+            .expect("Failed getting station list with restored session");
+    }
+
+    #[tokio::test]
+    async fn concurrent_session_test() {
+        let mock = Arc::new(MockPandora::new());
+        let session = session_login_with(mock.clone(), RetryPolicy::never())
+            .await
+            .expect("Failed login against mock");
+
+        let station_token = get_station_list(&session)
+            .await
+            .expect("Failed getting station list")
+            .stations[0]
+            .station_token
+            .clone();
+        let track = get_playlist(&session, &station_token)
+            .await
+            .expect("Failed getting playlist")
+            .items
+            .iter()
+            .find_map(|item| item.get_track())
+            .expect("Playlist contained no tracks");
+
+        // Independent requests share a single login
+        let (station, music, explanation) = tokio::join!(
+            get_station(&session, &station_token),
+            get_track(&session, &track.music_id),
+            explain_track(&session, &track.track_token),
+        );
+        station.expect("Failed getting station concurrently");
+        music.expect("Failed getting track concurrently");
+        explanation.expect("Failed explaining track concurrently");
+
+        // Token invalidation is seen by every clone
+        let clone = session.clone();
+        mock.expire_tokens();
+        let (first, second) = tokio::join!(
+            get_station(&session, &station_token),
+            get_station(&clone, &station_token),
+        );
+        assert!(first.is_err() && second.is_err());
+        assert!(session.session_tokens().user_token.is_none());
+        assert!(clone.session_tokens().user_token.is_none());
+    }
+
+    /// Records the untyped result of the latest response to each method.
+    #[derive(Debug, Default)]
+    struct ResultRecorder(std::sync::Mutex<HashMap<String, serde_json::Value>>);
+
+    impl ResultRecorder {
+        fn result(&self, method: &str) -> serde_json::Value {
+            self.0.lock().unwrap()[method].clone()
+        }
+    }
+
+    impl Middleware for ResultRecorder {
+        fn on_response(
+            &self,
+            method: &str,
+            _call_id: u64,
+            response: &mut PandoraResponse<serde_json::Value>,
+        ) {
+            if let Some(result) = &response.result {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .insert(method.to_string(), result.clone());
+            }
+        }
+    }
+
+    /// Serialize `response`, check that it deserializes to an equal value,
+    /// and return the serialized json.
+    fn round_trip<T>(response: &T) -> serde_json::Value
+    where
+        T: Debug + PartialEq + Serialize + serde::de::DeserializeOwned,
+    {
+        let json = serde_json::to_value(response).expect("Failed serializing response");
+        let copy: T = serde_json::from_value(json.clone()).expect("Failed deserializing response");
+        assert_eq!(&copy, response);
+        json
+    }
+
+    #[tokio::test]
+    async fn response_round_trip_test() {
+        let partner = Partner::default();
+        let mut session = session_login(&partner)
+            .await
+            .expect("Failed initializing login session");
+        let recorder = Arc::new(ResultRecorder::default());
+        session.add_middleware(recorder.clone());
+
+        // Responses without optional fields serialize to their wire format
+        let stations = get_station_list(&session)
+            .await
+            .expect("Failed getting station list");
+        assert_eq!(
+            round_trip(&stations),
+            recorder.result("user.getStationList")
+        );
+        let station_token = &stations.stations[0].station_token;
+
+        let playlist = station::GetPlaylist::from(station_token)
+            .additional_audio_url("HTTP_32_AACPLUS_ADTS")
+            .include_track_length(true)
+            .response(&session)
+            .await
+            .expect("Failed getting playlist");
+        assert_eq!(
+            round_trip(&playlist),
+            recorder.result("station.getPlaylist")
+        );
+
+        // Including the optional maps, which are flattened back into the
+        // response
+        let track = playlist
+            .items
+            .iter()
+            .find_map(|item| item.get_track())
+            .expect("Playlist contained no tracks");
+        let music = get_track(&session, &track.music_id)
+            .await
+            .expect("Failed getting track");
+        assert!(!music.optional.is_empty());
+        let json = round_trip(&music);
+        for key in music.optional.keys() {
+            assert_eq!(json[key], recorder.result("music.getTrack")[key]);
+        }
+
+        let station = station::GetStation::from(station_token)
+            .include_extended_attributes(true)
+            .response(&session)
+            .await
+            .expect("Failed getting station");
+        let json = round_trip(&station);
+        assert_eq!(json["stationToken"], station_token.as_str());
+        assert!(json["dateCreated"]["timezoneOffset"].is_number());
+
+        let search = music::search(&session, "Radiohead")
+            .await
+            .expect("Failed searching");
+        assert!(round_trip(&search)["artists"].is_array());
+        let bookmarks = user::get_bookmarks(&session)
+            .await
+            .expect("Failed getting bookmarks");
+        round_trip(&bookmarks);
+        let explanation = explain_track(&session, &track.track_token)
+            .await
+            .expect("Failed explaining track");
+        round_trip(&explanation);
+    }
+
+    #[test]
+    fn saved_sync_time_test() {
+        let saved = serde_json::json!({
+            "version": 1,
+            "encryptKey": "6#26FRL$ZWD",
+            "decryptKey": "R=U!LH$O2B#",
+            "partnerId": "42",
+            "partnerToken": "VAzrFQTtsy3BQ3K+3iqFi0WF5HA63B1nFA",
+            "syncTime": 1_700_000_000u64,
+            "savedAt": wall_clock_secs() - 100,
+            "userId": "4267243",
+            "userToken": "XXXVAzrFQTtsy3BQ3K+3iqFi0WF5HA63B1nFA"
+        });
+        let tokens: SessionTokens =
+            serde_json::from_value(saved.clone()).expect("Failed deserializing session tokens");
+        let sync_time = tokens.get_sync_time().expect("Sync time wasn't restored");
+        assert!((1_700_000_100..1_700_000_105).contains(&sync_time));
+
+        let mut unsupported = saved;
+        unsupported["version"] = serde_json::json!(2);
+        assert!(serde_json::from_value::<SessionTokens>(unsupported).is_err());
+    }
+
+    #[test]
+    fn idempotent_method_test() {
+        for method in [
+            "auth.partnerLogin",
+            "auth.userLogin",
+            "user.getStationList",
+            "music.search",
+            "user.canSubscribe",
+            "user.validateUsername",
+            "track.explainTrack",
+            "test.checkLicensing",
+        ] {
+            assert!(is_idempotent_method(method), "{}", method);
+        }
+        for method in [
+            "station.addFeedback",
+            "station.createStation",
+            "station.addMusic",
+            "bookmark.addArtistBookmark",
+            "user.changeSettings",
+            "user.createUser",
+        ] {
+            assert!(!is_idempotent_method(method), "{}", method);
+        }
+    }
+
+    #[test]
+    fn invalid_sync_time_test() {
+        let partner = Partner::default();
+        let mut tokens = SessionTokens::new(&partner);
+        let mut response = PartnerLoginResponse {
+            partner_id: String::from("42"),
+            partner_auth_token: Secret::from("VAzrFQTtsy3BQ3K+3iqFi0WF5HA63B1nFA"),
+            sync_time: crypt::encrypt(partner.decrypt_password.expose_secret(), "junk1700000000")
+                .expect("Failed encrypting sync time"),
+            station_skip_unit: String::from("hour"),
+            station_skip_limit: 6,
+            urls: None,
+            optional: HashMap::new(),
+        };
+        tokens
+            .update_partner_tokens(&response)
+            .expect("Failed decrypting sync time");
+        assert!(tokens.get_sync_time().is_some());
+
+        for sync_time in ["junkNaN", "junk", ""] {
+            response.sync_time =
+                crypt::encrypt(partner.decrypt_password.expose_secret(), sync_time)
+                    .expect("Failed encrypting sync time");
+            assert!(matches!(
+                tokens.update_sync_time(&response),
+                Err(Error::InvalidSyncTime(_))
+            ));
+            // A failed re-sync keeps the current time base
+            assert!(tokens.get_sync_time().is_some());
+        }
+        assert!(matches!(
+            tokens.update_partner_tokens(&response),
+            Err(Error::InvalidSyncTime(_))
+        ));
+        assert_eq!(tokens.partner_id.as_deref(), Some("42"));
+        assert_eq!(tokens.get_sync_time(), None);
+    }
+
+    #[tokio::test]
+    async fn resync_time_test() {
+        let mock = Arc::new(MockPandora::new());
+        let session = session_login_with(mock.clone(), RetryPolicy::never())
+            .await
+            .expect("Failed login against mock");
+        let user_token = session.session_tokens().user_token.clone();
+
+        mock.set_clock_offset(3600);
+        let _stations = get_station_list(&session)
+            .await
+            .expect("Failed getting station list after server clock drift");
+        assert_eq!(
+            mock.method_calls()[2..],
+            [
+                "user.getStationList",
+                "auth.partnerLogin",
+                "user.getStationList"
+            ]
+        );
+        // Only the time base changed
+        let tokens = session.session_tokens();
+        assert_eq!(tokens.user_token, user_token);
+        let sync_time = tokens.get_sync_time().expect("Sync time was cleared");
+        assert!((sync_time as i64 - chrono::Utc::now().timestamp() - 3600).abs() < 5);
+    }
+
+    /// The fields recorded on a span.
+    #[cfg(feature = "tracing")]
+    #[derive(Debug, Default)]
+    struct SpanFields(std::collections::BTreeMap<String, String>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for SpanFields {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    /// A layer that keeps the fields of every span created while it's the
+    /// default, in the order the spans were created.
+    #[cfg(feature = "tracing")]
+    #[derive(Debug, Clone, Default)]
+    struct SpanRecorder {
+        spans: Arc<std::sync::Mutex<Vec<(tracing::span::Id, SpanFields)>>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanRecorder {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut fields = SpanFields::default();
+            attrs.record(&mut fields);
+            self.spans.lock().unwrap().push((id.clone(), fields));
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut spans = self.spans.lock().unwrap();
+            if let Some((_, fields)) = spans.iter_mut().rev().find(|(span, _)| span == id) {
+                values.record(fields);
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn tracing_span_test() {
+        use crate::json::mock::MockFailure;
+
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let recorder = SpanRecorder::default();
+        let _default =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+        let mock = Arc::new(MockPandora::new());
+        mock.fail_next_call("auth.userLogin", MockFailure::Api(1001));
+        assert!(session_login_with(mock.clone(), RetryPolicy::never())
+            .await
+            .is_err());
+
+        let spans: Vec<_> = recorder
+            .spans
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, fields)| fields.0.clone())
+            .collect();
+        assert_eq!(spans.len(), 2);
+        let partner_login = &spans[0];
+        assert_eq!(partner_login["method"], "auth.partnerLogin");
+        assert_eq!(partner_login["encrypted"], "false");
+        assert_eq!(partner_login["attempts"], "1");
+        assert_eq!(partner_login["http_status"], "200");
+        assert_eq!(partner_login["stat"], "Ok");
+        assert!(partner_login.contains_key("latency_ms"));
+        assert!(!partner_login.contains_key("error_code"));
+
+        let user_login = &spans[1];
+        assert_eq!(user_login["method"], "auth.userLogin");
+        assert_eq!(user_login["encrypted"], "true");
+        assert_eq!(user_login["stat"], "Fail");
+        assert_eq!(user_login["error_code"], "1001");
+        assert!(user_login.contains_key("latency_ms"));
     }
 }
@@ -67,7 +67,7 @@ impl<TS: ToString> From<&TS> for GetTrack {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetTrackResponse {
     /// The name of the song for the provided token.
@@ -90,7 +90,7 @@ pub struct GetTrackResponse {
 
 /// Convenience function to do a basic getTrack call.
 pub async fn get_track(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     track_token: &str,
 ) -> Result<GetTrackResponse, Error> {
     GetTrack::from(&track_token).response(session).await
@@ -154,10 +154,7 @@ impl<TS: ToString> From<&TS> for Search {
 }
 
 /// Convenience function to do a basic addSongBookmark call.
-pub async fn search(
-    session: &mut PandoraSession,
-    search_text: &str,
-) -> Result<SearchResponse, Error> {
+pub async fn search(session: &PandoraSession, search_text: &str) -> Result<SearchResponse, Error> {
     Search::from(&search_text)
         .include_near_matches(false)
         .include_genre_stations(false)
@@ -197,7 +194,7 @@ pub async fn search(
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchResponse {
     /// Songs matching the search.
@@ -216,7 +213,7 @@ pub struct SearchResponse {
 
 /// Structure collecting the song information returned
 /// by searches.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SongMatch {
     /// Name of the matched song.
@@ -232,7 +229,7 @@ pub struct SongMatch {
 
 /// Structure collecting the artist information returned
 /// by searches.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ArtistMatch {
     /// The name of the artist found in the search.
@@ -248,7 +245,7 @@ pub struct ArtistMatch {
 
 /// Structure collecting the genre-station information returned
 /// by searches.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GenreMatch {
     /// The unique id (token) for the song. Artist tokens start with 'R',
@@ -275,16 +272,16 @@ mod tests {
     #[tokio::test]
     async fn search_test() {
         let partner = Partner::default();
-        let mut session = session_login(&partner)
+        let session = session_login(&partner)
             .await
             .expect("Failed initializing login session");
 
-        let _search_response = search(&mut session, "INXS")
+        let _search_response = search(&session, "INXS")
             .await
             .expect("Failed completing search request");
         let _search_response: SearchResponse = Search::from(&"Alternative")
             .include_genre_stations(true)
-            .response(&mut session)
+            .response(&session)
             .await
             .expect("Failed completing search request");
     }
@@ -292,29 +289,25 @@ mod tests {
     #[tokio::test]
     async fn get_track_test() {
         let partner = Partner::default();
-        let mut session = session_login(&partner)
+        let session = session_login(&partner)
             .await
             .expect("Failed initializing login session");
 
-        for station in get_station_list(&mut session)
+        for station in get_station_list(&session)
             .await
             .expect("Failed getting station list to look up a track to bookmark")
             .stations
         {
-            for track in get_playlist(&mut session, &station.station_token)
+            for track in get_playlist(&session, &station.station_token)
                 .await
                 .expect("Failed completing request for playlist")
                 .items
                 .iter()
                 .flat_map(|p| p.get_track())
             {
-                if let Some(serde_json::value::Value::String(music_id)) =
-                    track.optional.get("musicId")
-                {
-                    let _response = get_track(&mut session, &music_id)
-                        .await
-                        .expect("Failed getting track information");
-                }
+                let _response = get_track(&session, &track.music_id)
+                    .await
+                    .expect("Failed getting track information");
             }
         }
     }
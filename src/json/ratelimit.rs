@@ -0,0 +1,301 @@
+/*!
+Client-side rate limiting of API calls.
+
+Pandora temporarily blocks accounts that call some methods too often, most
+notably `station.getPlaylist`, reporting either an `InternalError` (code 0) or
+`PlaylistExceeded` (code 1039).  The [`RateLimiter`] attached to a
+[`PandoraSession`](crate::json::PandoraSession) keeps a token bucket for each
+limited method, so that excess calls are delayed or rejected locally, before
+the server ever sees them.
+
+By default only `station.getPlaylist` is limited, to a burst of 5 calls
+followed by one call every 30 seconds.
+*/
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::errors::Error;
+
+/// The method name of the call limited by default.
+const GET_PLAYLIST_METHOD: &str = "station.getPlaylist";
+
+/// The rate at which calls to a method are allowed: up to `capacity` calls
+/// in a burst, after which one call is allowed per `refill_interval`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    capacity: u32,
+    refill_interval: Duration,
+}
+
+impl RateLimit {
+    /// Create a new RateLimit allowing bursts of up to `capacity` calls,
+    /// with one more call allowed each `refill_interval`.  A capacity of 0 is
+    /// treated as 1.
+    pub fn new(capacity: u32, refill_interval: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            refill_interval,
+        }
+    }
+
+    /// The number of calls allowed in a burst.
+    pub fn get_capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// The interval after which one more call is allowed.
+    pub fn get_refill_interval(&self) -> Duration {
+        self.refill_interval
+    }
+}
+
+/// What to do with a call that exceeds the rate limit for its method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Wait until the call is allowed, then submit it.
+    Delay,
+    /// Fail the call with `Error::RateLimitExceeded`.
+    Reject,
+}
+
+/// The state of the token bucket for a single method.
+#[derive(Debug, Clone)]
+struct Bucket {
+    /// Calls currently available.  Negative when delayed calls have reserved
+    /// calls that haven't been refilled yet.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        let interval = limit.refill_interval.as_secs_f64();
+        self.tokens = if interval > 0.0 {
+            (self.tokens + elapsed / interval).min(limit.capacity as f64)
+        } else {
+            limit.capacity as f64
+        };
+        self.last_refill = now;
+    }
+}
+
+/// A per-method token bucket rate limiter.
+///
+/// Clones of a RateLimiter share the same buckets, so all copies of a session
+/// (including those made while building each request) count against the same
+/// limits.  Changing the limits with [`limit()`](RateLimiter::limit) or
+/// [`remove_limit()`](RateLimiter::remove_limit) gives the changed copy its own
+/// buckets, leaving the other copies and their limits untouched.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    limits: HashMap<String, RateLimit>,
+    action: RateLimitAction,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    /// Create a new RateLimiter with the default limit on
+    /// `station.getPlaylist`, delaying calls that exceed it.
+    pub fn new() -> Self {
+        Self::unlimited().limit(
+            GET_PLAYLIST_METHOD,
+            RateLimit::new(5, Duration::from_secs(30)),
+        )
+    }
+
+    /// Create a new RateLimiter that doesn't limit any methods.
+    pub fn unlimited() -> Self {
+        Self {
+            limits: HashMap::new(),
+            action: RateLimitAction::Delay,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Limit calls to `method`, given in the form returned by
+    /// `PandoraJsonApiRequest::get_method()`, e.g. `"station.getPlaylist"`.
+    /// (Chaining call)
+    pub fn limit(mut self, method: &str, limit: RateLimit) -> Self {
+        self.detach_buckets(method);
+        self.limits.insert(method.to_string(), limit);
+        self
+    }
+
+    /// Remove the limit on calls to `method`. (Chaining call)
+    pub fn remove_limit(mut self, method: &str) -> Self {
+        self.detach_buckets(method);
+        self.limits.remove(method);
+        self
+    }
+
+    /// Set what to do with calls that exceed their limit. (Chaining call)
+    pub fn on_limit(mut self, action: RateLimitAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// The limit on calls to `method`, if any.
+    pub fn get_limit(&self, method: &str) -> Option<&RateLimit> {
+        self.limits.get(method)
+    }
+
+    /// Replace the shared buckets with a copy of their current state, less the
+    /// bucket for `method`, so that changing the limit on `method` doesn't
+    /// affect other clones.
+    fn detach_buckets(&mut self, method: &str) {
+        let mut buckets = self.lock_buckets().clone();
+        buckets.remove(method);
+        self.buckets = Arc::new(Mutex::new(buckets));
+    }
+
+    fn lock_buckets(&self) -> std::sync::MutexGuard<'_, HashMap<String, Bucket>> {
+        self.buckets
+            .lock()
+            .expect("RateLimiter bucket lock poisoned")
+    }
+
+    /// Take a call to `method` from its bucket, returning how long the
+    /// caller has to wait before submitting it.  When the limiter is set to
+    /// reject calls, a call that would have to wait fails with
+    /// `Error::RateLimitExceeded` instead, and isn't counted.
+    pub fn acquire(&self, method: &str) -> Result<Duration, Error> {
+        let limit = match self.limits.get(method) {
+            Some(limit) => limit,
+            None => return Ok(Duration::ZERO),
+        };
+        let now = Instant::now();
+        let mut buckets = self.lock_buckets();
+        let bucket = buckets.entry(method.to_string()).or_insert(Bucket {
+            tokens: limit.capacity as f64,
+            last_refill: now,
+        });
+        bucket.refill(limit, now);
+
+        let wait = if bucket.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            limit.refill_interval.mul_f64(1.0 - bucket.tokens)
+        };
+        if !wait.is_zero() && self.action == RateLimitAction::Reject {
+            return Err(Error::RateLimitExceeded(method.to_string(), wait));
+        }
+        bucket.tokens -= 1.0;
+        Ok(wait)
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::json::mock::MockPandora;
+    use crate::json::retry::RetryPolicy;
+    use crate::json::{
+        station::get_playlist, tests::session_login_with, user::get_station_list, PandoraSession,
+    };
+
+    #[test]
+    fn token_bucket_test() {
+        let limiter = RateLimiter::new()
+            .limit("test.method", RateLimit::new(2, Duration::from_secs(60)))
+            .on_limit(RateLimitAction::Reject);
+        assert_eq!(limiter.acquire("test.method").ok(), Some(Duration::ZERO));
+        assert_eq!(limiter.acquire("test.method").ok(), Some(Duration::ZERO));
+        match limiter.acquire("test.method") {
+            Err(Error::RateLimitExceeded(method, wait)) => {
+                assert_eq!(method, "test.method");
+                assert!(wait > Duration::from_secs(59));
+            }
+            res => panic!("Unexpected rate limit result: {:?}", res),
+        }
+        // Unlimited methods are never held up
+        for _ in 0..10 {
+            assert_eq!(limiter.acquire("other.method").ok(), Some(Duration::ZERO));
+        }
+
+        // Delayed calls reserve their place in line
+        let limiter = limiter.clone().on_limit(RateLimitAction::Delay);
+        let first = limiter
+            .acquire("test.method")
+            .expect("Delay mode rejected call");
+        let second = limiter
+            .acquire("test.method")
+            .expect("Delay mode rejected call");
+        assert!(first > Duration::from_secs(59));
+        assert!(second > first + Duration::from_secs(59));
+
+        // Changing the limits of a copy doesn't affect the original
+        let relaxed = limiter.clone().remove_limit("test.method");
+        assert_eq!(relaxed.acquire("test.method").ok(), Some(Duration::ZERO));
+        assert!(limiter.get_limit("test.method").is_some());
+        assert!(
+            limiter
+                .acquire("test.method")
+                .expect("Delay mode rejected call")
+                > second
+        );
+        let tightened = limiter
+            .clone()
+            .limit("other.method", RateLimit::new(1, Duration::from_secs(60)));
+        assert_eq!(tightened.acquire("other.method").ok(), Some(Duration::ZERO));
+        assert!(
+            tightened
+                .acquire("other.method")
+                .expect("Delay mode rejected call")
+                > Duration::ZERO
+        );
+        assert_eq!(limiter.acquire("other.method").ok(), Some(Duration::ZERO));
+    }
+
+    async fn mock_session() -> PandoraSession {
+        session_login_with(Arc::new(MockPandora::new()), RetryPolicy::new())
+            .await
+            .expect("Failed login against mock")
+    }
+
+    #[tokio::test]
+    async fn rate_limited_playlist_test() {
+        let mut session = mock_session().await;
+        session.set_rate_limiter(
+            RateLimiter::new()
+                .limit(
+                    "station.getPlaylist",
+                    RateLimit::new(1, Duration::from_millis(100)),
+                )
+                .on_limit(RateLimitAction::Reject),
+        );
+        let station_token = get_station_list(&session)
+            .await
+            .expect("Failed getting station list")
+            .stations[0]
+            .station_token
+            .clone();
+
+        let _playlist = get_playlist(&session, &station_token)
+            .await
+            .expect("Failed getting playlist");
+        match get_playlist(&session, &station_token).await {
+            Err(Error::RateLimitExceeded(_, _)) => (),
+            res => panic!("Unexpected playlist result: {:?}", res),
+        }
+
+        let limiter = session.rate_limiter().clone();
+        session.set_rate_limiter(limiter.on_limit(RateLimitAction::Delay));
+        let start = Instant::now();
+        let _playlist = get_playlist(&session, &station_token)
+            .await
+            .expect("Failed getting delayed playlist");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
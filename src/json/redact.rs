@@ -0,0 +1,111 @@
+/*!
+Redaction of credentials and auth tokens from request and response json, and
+from request urls, before they are logged or recorded.
+*/
+// SPDX-License-Identifier: MIT
+
+/// The value substituted for redacted json values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Substrings of keys whose values are redacted wherever they appear in
+/// request or response json, matched case-insensitively, so that e.g.
+/// `currentPassword` and `newUsername` are covered as well.
+const SECRET_KEY_PARTS: &[&str] = &["username", "password"];
+
+/// Url query arguments whose values are redacted.
+const SECRET_ARGS: &[&str] = &["auth_token"];
+
+/// Keys holding auth tokens, whose values are redacted wherever they appear in
+/// request or response json, and also from any other response values that
+/// embed them, such as urls.
+const TOKEN_KEYS: &[&str] = &["partnerAuthToken", "userAuthToken"];
+
+/// Replace the values of any keys matching `is_redacted` found anywhere in
+/// `json`.
+pub(crate) fn redact(json: &mut serde_json::Value, is_redacted: &impl Fn(&str) -> bool) {
+    match json {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_redacted(key) {
+                    *value = serde_json::Value::from(REDACTED);
+                } else {
+                    redact(value, is_redacted);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(|v| redact(v, is_redacted)),
+        _ => (),
+    }
+}
+
+/// Whether the values of `key` hold credentials or auth tokens.
+fn is_secret_key(key: &str) -> bool {
+    let key_lower = key.to_lowercase();
+    TOKEN_KEYS.contains(&key) || SECRET_KEY_PARTS.iter().any(|part| key_lower.contains(part))
+}
+
+/// Redact credentials and auth tokens from request or response json.
+pub fn redact_secrets(json: &mut serde_json::Value) {
+    redact(json, &is_secret_key);
+}
+
+/// Get a copy of a request url with the auth token query argument redacted.
+pub fn redact_url(url: &url::Url) -> url::Url {
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| {
+            let value = if SECRET_ARGS.contains(&key.as_ref()) {
+                REDACTED.to_string()
+            } else {
+                value.into_owned()
+            };
+            (key.into_owned(), value)
+        })
+        .collect();
+    let mut redacted = url.clone();
+    if !pairs.is_empty() {
+        redacted.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    redacted
+}
+
+/// Gather the values of any of the `TOKEN_KEYS` found anywhere in `json`.
+pub(crate) fn collect_tokens(json: &serde_json::Value, tokens: &mut Vec<String>) {
+    match json {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                match value.as_str() {
+                    Some(token) if TOKEN_KEYS.contains(&key.as_str()) => {
+                        if !token.is_empty() && !tokens.iter().any(|t| t == token) {
+                            tokens.push(token.to_string());
+                        }
+                    }
+                    _ => collect_tokens(value, tokens),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter().for_each(|v| collect_tokens(v, tokens)),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn redact_url_test() {
+        let url = url::Url::parse(
+            "https://tuner.pandora.com/services/json/?method=user.getStationList&auth_token=abc%2Fdef&partner_id=42",
+        )
+        .expect("Failed parsing url");
+        let redacted = redact_url(&url);
+        assert!(!redacted.as_str().contains("abc"));
+        let args: HashMap<String, String> = redacted.query_pairs().into_owned().collect();
+        assert_eq!(args["auth_token"], REDACTED);
+        assert_eq!(args["method"], "user.getStationList");
+        assert_eq!(args["partner_id"], "42");
+    }
+}
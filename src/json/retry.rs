@@ -0,0 +1,311 @@
+/*!
+Retry policy for requests that fail with transient errors.
+
+Pandora occasionally answers requests with errors that say more about the
+state of the service than about the request: an `InternalError` (code 0),
+`MaintenanceMode`, `InsufficientConnectivity`, an HTTP 5xx status, or a
+dropped connection.  The [`RetryPolicy`] attached to a
+[`PandoraSession`](crate::json::PandoraSession) decides which of those are
+worth another attempt, and how long to wait between attempts.
+
+Only requests that are
+[idempotent](crate::json::PandoraJsonApiRequest::idempotent()) are retried,
+since a request that changes the account or its stations may have taken
+effect even though it was reported as failed.
+*/
+// SPDX-License-Identifier: MIT
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::errors::Error;
+use crate::json::errors::JsonErrorKind;
+
+/// Controls how many times, and how often, a failed request is retried.
+///
+/// The delay before the retry following attempt `n` is
+/// `initial_backoff * backoff_multiplier^(n - 1)`, capped at `max_backoff`,
+/// then reduced by a random amount of up to `jitter` (a fraction of the delay)
+/// so that clients that failed together don't retry together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    backoff_multiplier: f64,
+    jitter: f64,
+    retryable_kinds: Vec<JsonErrorKind>,
+    retry_http_errors: bool,
+}
+
+impl RetryPolicy {
+    /// Create a new RetryPolicy with the default settings: up to 3 attempts,
+    /// starting with a 500ms delay that doubles for each retry, up to 10s,
+    /// with 20% jitter.  Every error that `Error::is_retryable()` considers
+    /// transient is retried: `InternalError`, `MaintenanceMode` and
+    /// `InsufficientConnectivity` API errors, HTTP 5xx responses, connection
+    /// failures and timeouts.
+    pub fn new() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            backoff_multiplier: 2.0,
+            jitter: 0.2,
+            retryable_kinds: JsonErrorKind::RETRYABLE.to_vec(),
+            retry_http_errors: true,
+        }
+    }
+
+    /// Create a new RetryPolicy that never retries, so that every error is
+    /// reported on the first failure.
+    pub fn never() -> Self {
+        Self::new().max_attempts(1)
+    }
+
+    /// The maximum number of times a request is attempted, including the
+    /// first attempt. Values less than 1 are treated as 1. (Chaining call)
+    pub fn max_attempts(mut self, value: u32) -> Self {
+        self.max_attempts = value.max(1);
+        self
+    }
+
+    /// The delay before the first retry. (Chaining call)
+    pub fn initial_backoff(mut self, value: Duration) -> Self {
+        self.initial_backoff = value;
+        self
+    }
+
+    /// The longest delay between attempts, before jitter is applied.
+    /// (Chaining call)
+    pub fn max_backoff(mut self, value: Duration) -> Self {
+        self.max_backoff = value;
+        self
+    }
+
+    /// The factor the delay grows by after each retry. (Chaining call)
+    pub fn backoff_multiplier(mut self, value: f64) -> Self {
+        self.backoff_multiplier = value.max(1.0);
+        self
+    }
+
+    /// The largest fraction of each delay that may be randomly subtracted
+    /// from it, between 0.0 (no jitter) and 1.0. (Chaining call)
+    pub fn jitter(mut self, value: f64) -> Self {
+        self.jitter = value.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Retry requests that fail with the API error `kind`. (Chaining call)
+    pub fn retry_on(mut self, kind: JsonErrorKind) -> Self {
+        if !self.retryable_kinds.contains(&kind) {
+            self.retryable_kinds.push(kind);
+        }
+        self
+    }
+
+    /// Don't retry requests that fail with the API error `kind`.
+    /// (Chaining call)
+    pub fn no_retry_on(mut self, kind: JsonErrorKind) -> Self {
+        self.retryable_kinds.retain(|k| *k != kind);
+        self
+    }
+
+    /// Whether to retry requests that fail with an HTTP 5xx status, that
+    /// couldn't connect to the server, or that timed out. (Chaining call)
+    pub fn retry_http_errors(mut self, value: bool) -> Self {
+        self.retry_http_errors = value;
+        self
+    }
+
+    /// The maximum number of times a request is attempted, including the
+    /// first attempt.
+    pub fn get_max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// The API error kinds that are retried.
+    pub fn get_retryable_kinds(&self) -> &[JsonErrorKind] {
+        &self.retryable_kinds
+    }
+
+    /// Whether a request that failed with `error` on attempt number `attempt`
+    /// (starting from 1) should be attempted again.  API errors are retried
+    /// if their kind is one of the retryable kinds, which by default are the
+    /// transient kinds; other errors are retried if `Error::is_retryable()`
+    /// considers them transient, unless HTTP errors aren't retried.
+    pub fn should_retry(&self, error: &Error, attempt: u32) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        match error {
+            Error::PandoraJsonRequestError(e) => self.retryable_kinds.contains(&e.kind()),
+            _ => self.retry_http_errors && error.is_retryable(),
+        }
+    }
+
+    /// The delay before the retry following attempt number `attempt`
+    /// (starting from 1), without jitter applied.
+    pub fn base_backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.backoff_multiplier.powi(exponent);
+        if backoff < self.max_backoff.as_secs_f64() {
+            Duration::from_secs_f64(backoff)
+        } else {
+            self.max_backoff
+        }
+    }
+
+    /// The delay before the retry following attempt number `attempt`
+    /// (starting from 1), with jitter applied.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self.base_backoff(attempt);
+        if self.jitter == 0.0 {
+            return backoff;
+        }
+        // A freshly-seeded hasher is a cheap source of randomness that
+        // doesn't warrant an additional dependency
+        let random = RandomState::new().build_hasher().finish();
+        let fraction = (random as f64 / u64::MAX as f64) * self.jitter;
+        backoff.mul_f64(1.0 - fraction)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::json::errors::JsonError;
+    use crate::json::mock::{MockFailure, MockPandora};
+    use crate::json::station::rename_station;
+    use crate::json::{auth::user_login, user::get_station_list, Partner};
+
+    #[test]
+    fn backoff_test() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(350))
+            .jitter(0.0);
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(350));
+
+        let policy = policy.jitter(0.5);
+        for attempt in 1..5 {
+            let backoff = policy.backoff(attempt);
+            assert!(backoff <= policy.base_backoff(attempt));
+            assert!(backoff >= policy.base_backoff(attempt) / 2);
+        }
+    }
+
+    #[test]
+    fn should_retry_test() {
+        let policy = RetryPolicy::new().no_retry_on(JsonErrorKind::MaintenanceMode);
+        let api_error = |code| Error::from(JsonError::new(Some(code), None));
+
+        assert!(policy.should_retry(&api_error(0), 1));
+        assert!(policy.should_retry(&api_error(13), 2));
+        assert!(!policy.should_retry(&api_error(13), 3));
+        assert!(!policy.should_retry(&api_error(1), 1));
+        assert!(!policy.should_retry(&api_error(1001), 1));
+        assert!(policy.should_retry(&Error::HttpStatusError(503), 1));
+        assert!(!policy.should_retry(&Error::HttpStatusError(404), 1));
+        let timeout = Error::RequestTimeout(String::from("user.getStationList"), Duration::ZERO);
+        assert!(policy.should_retry(&timeout, 1));
+        let policy = policy.retry_http_errors(false);
+        assert!(!policy.should_retry(&Error::HttpStatusError(503), 1));
+        assert!(!policy.should_retry(&timeout, 1));
+        assert!(!RetryPolicy::never().should_retry(&api_error(0), 1));
+
+        // By default, exactly the transient errors are retried
+        let policy = RetryPolicy::new();
+        for code in (0..=15).chain(1000..=1039) {
+            let error = api_error(code);
+            assert_eq!(
+                policy.should_retry(&error, 1),
+                error.is_retryable(),
+                "{}",
+                code
+            );
+        }
+        let policy = policy.retry_on(JsonErrorKind::PlaylistExceeded);
+        assert!(policy.should_retry(&api_error(1039), 1));
+    }
+
+    #[tokio::test]
+    async fn retry_response_test() {
+        let partner = Partner::default();
+        let mock = Arc::new(MockPandora::new());
+        let mut session = partner.init_session_with_transport(mock.clone());
+        session.set_retry_policy(
+            RetryPolicy::new()
+                .initial_backoff(Duration::from_millis(1))
+                .max_attempts(3),
+        );
+        partner
+            .login(&session)
+            .await
+            .expect("Failed partner login against mock");
+        user_login(
+            &session,
+            MockPandora::DEFAULT_USERNAME,
+            MockPandora::DEFAULT_PASSWORD,
+        )
+        .await
+        .expect("Failed user login against mock");
+
+        // Transient failures are retried until the request succeeds
+        mock.fail_next(MockFailure::Http(503));
+        mock.fail_next(MockFailure::Api(0));
+        let _stations = get_station_list(&session)
+            .await
+            .expect("Failed retrying station list request");
+
+        // Until the attempts run out
+        mock.fail_next(MockFailure::Api(1));
+        mock.fail_next(MockFailure::Api(1));
+        mock.fail_next(MockFailure::Api(0));
+        match get_station_list(&session).await {
+            Err(Error::PandoraJsonRequestError(e)) => {
+                assert_eq!(e.kind(), JsonErrorKind::InternalError)
+            }
+            res => panic!("Unexpected station list result: {:?}", res),
+        }
+
+        // Other errors fail immediately
+        mock.fail_next(MockFailure::Api(1006));
+        match get_station_list(&session).await {
+            Err(Error::PandoraJsonRequestError(e)) => {
+                assert_eq!(e.kind(), JsonErrorKind::StationDoesNotExist)
+            }
+            res => panic!("Unexpected station list result: {:?}", res),
+        }
+
+        // Requests that change state aren't retried
+        let stations = get_station_list(&session)
+            .await
+            .expect("Failed getting station list");
+        let station = &stations.stations[0];
+        mock.fail_next(MockFailure::Api(0));
+        match rename_station(&session, &station.station_token, "Renamed").await {
+            Err(Error::PandoraJsonRequestError(e)) => {
+                assert_eq!(e.kind(), JsonErrorKind::InternalError)
+            }
+            res => panic!("Unexpected rename station result: {:?}", res),
+        }
+
+        let calls = mock.method_calls();
+        assert_eq!(calls.len(), 2 + 3 + 3 + 1 + 1 + 1);
+        assert!(calls[2..10].iter().all(|m| m == "user.getStationList"));
+        assert_eq!(calls[10], "station.renameStation");
+    }
+}
@@ -104,7 +104,7 @@ impl AddFeedback {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AddFeedbackResponse {
     /// Timestamp for when the bookmark was created.
@@ -132,7 +132,7 @@ pub struct AddFeedbackResponse {
 
 /// Convenience function to do a basic addFeedback call.
 pub async fn add_feedback(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_token: &str,
     track_token: &str,
     is_positive: bool,
@@ -191,7 +191,7 @@ impl AddMusic {
 ///         }
 ///     }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AddMusicResponse {
     /// The name of the artist being rated.
@@ -207,7 +207,7 @@ pub struct AddMusicResponse {
 
 /// Convenience function to do a basic addMusic call.
 pub async fn add_music(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_token: &str,
     music_token: &str,
 ) -> Result<AddMusicResponse, Error> {
@@ -282,7 +282,7 @@ pub enum MusicType {
 }
 
 /// station.createStation has no known response
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateStationResponse {
     /// The unique id (token) for the just-created station.
@@ -294,7 +294,7 @@ pub struct CreateStationResponse {
 
 /// Convenience function to do a basic createStation call.
 pub async fn create_station_from_track_song(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     track_token: &str,
 ) -> Result<CreateStationResponse, Error> {
     CreateStation::new_from_track_song(track_token)
@@ -304,7 +304,7 @@ pub async fn create_station_from_track_song(
 
 /// Convenience function to do a basic createStation call.
 pub async fn create_station_from_artist(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     track_token: &str,
 ) -> Result<CreateStationResponse, Error> {
     CreateStation::new_from_track_artist(track_token)
@@ -314,7 +314,7 @@ pub async fn create_station_from_artist(
 
 /// Convenience function to do a basic createStation call.
 pub async fn create_station_from_music_token(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     music_token: &str,
 ) -> Result<CreateStationResponse, Error> {
     CreateStation::new_from_music_token(music_token)
@@ -350,7 +350,7 @@ impl<TS: ToString> From<&TS> for DeleteFeedback {
 }
 
 /// This method does not return data.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteFeedbackResponse {
     /// The fields of the deleteFeedback response are unknown.
@@ -360,7 +360,7 @@ pub struct DeleteFeedbackResponse {
 
 /// Convenience function to do a basic deleteFeedback call.
 pub async fn delete_feedback(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     feedback_id: &str,
 ) -> Result<DeleteFeedbackResponse, Error> {
     DeleteFeedback::from(&feedback_id).response(session).await
@@ -395,7 +395,7 @@ impl<TS: ToString> From<&TS> for DeleteMusic {
 }
 
 /// This method does not return data.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteMusicResponse {
     /// The fields of the deleteMusic response are unknown.
@@ -405,7 +405,7 @@ pub struct DeleteMusicResponse {
 
 /// Convenience function to do a basic deleteMusic call.
 pub async fn delete_music(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     seed_id: &str,
 ) -> Result<DeleteMusicResponse, Error> {
     DeleteMusic::from(&seed_id).response(session).await
@@ -437,7 +437,7 @@ impl<TS: ToString> From<&TS> for DeleteStation {
 }
 
 /// No data is returned in response.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteStationResponse {
     /// The fields of the deleteStation response are unknown.
@@ -447,7 +447,7 @@ pub struct DeleteStationResponse {
 
 /// Convenience function to do a basic deleteStation call.
 pub async fn delete_station(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_token: &str,
 ) -> Result<DeleteStationResponse, Error> {
     DeleteStation::from(&station_token).response(session).await
@@ -487,7 +487,7 @@ impl GetGenreStationsChecksum {
 
 /// | Name   | Type  |  Description |
 /// | checksum  |  string | |
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetGenreStationsChecksumResponse {
     /// The checksum for the list of genre stations. This is useful to detect
@@ -499,7 +499,7 @@ pub struct GetGenreStationsChecksumResponse {
 
 /// Convenience function to do a basic getGenreStationsChecksum call.
 pub async fn get_genre_stations_checksum(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
 ) -> Result<GetGenreStationsChecksumResponse, Error> {
     GetGenreStationsChecksum::default()
         .include_genre_category_ad_url(false)
@@ -544,7 +544,7 @@ impl GetGenreStations {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetGenreStationsResponse {
     /// The checksum for the list of genre stations. This is useful to detect
@@ -555,7 +555,7 @@ pub struct GetGenreStationsResponse {
 }
 
 /// A collection of stations that fall in a broad genre category
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GenreCategory {
     /// Genre/music category name
@@ -565,7 +565,7 @@ pub struct GenreCategory {
 }
 
 /// A specific genre station
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GenreStation {
     /// Actually a musicToken, which can be used with station.createStation.
@@ -578,7 +578,7 @@ pub struct GenreStation {
 
 /// Convenience function to do a basic getGenreStations call.
 pub async fn get_genre_stations(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
 ) -> Result<GetGenreStationsResponse, Error> {
     GetGenreStations::default().response(session).await
 }
@@ -941,7 +941,7 @@ impl TryFrom<String> for AudioFormat {
 ///      }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetPlaylistResponse {
     /// Contains a list of playlist entries, each being either a song/track or
@@ -952,7 +952,7 @@ pub struct GetPlaylistResponse {
 /// Responses can be either a track or an ad.
 /// The responses don't have a standard tag identifying which type it is,
 /// but ads have only one value: adToken: String.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", untagged)]
 pub enum PlaylistEntry {
     /// Playlist entry representing an ad.
@@ -990,7 +990,7 @@ impl PlaylistEntry {
 }
 
 /// Represents an ad entry in a playlist.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PlaylistAd {
     /// The unique id (token) for the ad which should be played.
@@ -1001,7 +1001,7 @@ pub struct PlaylistAd {
 }
 
 /// Represents a track (song) entry in a playlist.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PlaylistTrack {
     /// The unique id (token) for the track to be played.
@@ -1022,18 +1022,155 @@ pub struct PlaylistTrack {
     pub song_name: String,
     /// The rating of the song for this track.
     pub song_rating: u32,
+    /// The replay gain for this track, as it was sent, so that it serializes
+    /// back unchanged.  See `get_track_gain()`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    track_gain: Option<NumberOrString>,
+    /// The url of the album art for this track.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_art_url: Option<String>,
+    /// Whether feedback may be submitted for this track.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_feedback: Option<bool>,
+    /// The url for additional information about the song.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub song_detail_url: Option<String>,
+    /// The url for additional information about the artist.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist_detail_url: Option<String>,
+    /// The url for additional information about the album.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_detail_url: Option<String>,
+    /// The urls of the additional audio formats requested with
+    /// `GetPlaylist::additional_audio_url()`, in the order they were
+    /// requested.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_additional_audio_url",
+        serialize_with = "serialize_additional_audio_url",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub additional_audio_url: Vec<String>,
+    /// The Amazon ASIN of the album for this track.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amazon_album_asin: Option<String>,
+    /// The length of this track, if requested with
+    /// `GetPlaylist::include_track_length()`.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_track_length",
+        serialize_with = "serialize_track_length",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub track_length: Option<std::time::Duration>,
+    /// The audio token for this track, if requested with
+    /// `GetPlaylist::include_audio_token()`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_token: Option<String>,
     /// Additional, optional fields in the response
     #[serde(flatten)]
     pub optional: HashMap<String, serde_json::value::Value>,
 }
 
+impl PlaylistTrack {
+    /// The replay gain for this track, in decibels.  A gain that isn't a
+    /// number, e.g. `""`, is treated as missing, rather than failing the
+    /// whole playlist.
+    pub fn get_track_gain(&self) -> Option<f32> {
+        self.track_gain
+            .as_ref()
+            .and_then(|gain| gain.to_f64().ok())
+            .map(|gain| gain as f32)
+    }
+}
+
+/// A number that may be sent either as a json number or as a string, e.g.
+/// `"10.09"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(f64),
+    String(String),
+}
+
+impl NumberOrString {
+    fn to_f64(&self) -> Result<f64, std::num::ParseFloatError> {
+        match self {
+            Self::Number(n) => Ok(*n),
+            Self::String(s) => s.trim().parse(),
+        }
+    }
+}
+
+/// Serialize the track length in seconds, the way it is sent.
+fn serialize_track_length<S>(
+    length: &Option<std::time::Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match length {
+        Some(length) if length.subsec_nanos() == 0 => serializer.serialize_u64(length.as_secs()),
+        Some(length) => serializer.serialize_f64(length.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Deserialize the track length, which is sent in seconds.
+fn deserialize_track_length<'de, D>(
+    deserializer: D,
+) -> Result<Option<std::time::Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<NumberOrString>::deserialize(deserializer)?
+        .map(|length| {
+            let seconds = length.to_f64().map_err(serde::de::Error::custom)?;
+            std::time::Duration::try_from_secs_f64(seconds).map_err(serde::de::Error::custom)
+        })
+        .transpose()
+}
+
+/// Serialize the additional audio urls the way they are sent, as a single
+/// string when there is only one.
+fn serialize_additional_audio_url<S>(urls: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match urls {
+        [url] => serializer.serialize_str(url),
+        urls => urls.serialize(serializer),
+    }
+}
+
+/// Deserialize the additional audio urls, which are sent as a single string
+/// when only one format was requested, and as an array otherwise.
+fn deserialize_additional_audio_url<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        None => Vec::new(),
+        Some(OneOrMany::One(url)) => vec![url],
+        Some(OneOrMany::Many(urls)) => urls,
+    })
+}
+
 ///                  "lowQuality": {
 ///                      "bitrate": "32",
 ///                      "encoding": "aacplus",
 ///                      "audioUrl": "http://audio-sv5-t1-1.pandora.com/access/3464788359714661029.mp4?...",
 ///                      "protocol": "http"
 ///                  }
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AudioQuality {
     /// Attributes for the high quality audio stream.
@@ -1045,7 +1182,7 @@ pub struct AudioQuality {
 }
 
 /// Playback/decoding attributes of an available audio stream.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AudioStream {
     /// The audio bitrate/quality for this stream.
@@ -1060,7 +1197,7 @@ pub struct AudioStream {
 
 /// Convenience function to do a basic getPlaylist call.
 pub async fn get_playlist(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_token: &str,
 ) -> Result<GetPlaylistResponse, Error> {
     GetPlaylist::from(&station_token)
@@ -1232,7 +1369,7 @@ impl<TS: ToString> From<&TS> for GetStation {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetStationResponse {
     /// The unique id (token) for the station for which information was
@@ -1285,7 +1422,7 @@ pub struct GetStationResponse {
 ///             "genres": []
 ///         },
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StationSeeds {
     /// Songs used as seeds for this station.
@@ -1308,7 +1445,7 @@ pub struct StationSeeds {
 ///                 "artUrl": "http://.../081227857165_130W_130H.jpg",
 ///             }],
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SongSeed {
     /// Unique identifier/handle for this seed.
@@ -1343,7 +1480,7 @@ pub struct SongSeed {
 ///                 "icon": {"dominantColor": "602d30","artUrl": ""},
 ///             ],}
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ArtistSeed {
     /// Unique identifier/handle for this seed.
@@ -1372,7 +1509,7 @@ pub struct ArtistSeed {
 ///                 "seedId": "31525199599467854"
 ///             }]
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GenreSeed {
     /// Unique identifier/handle for this seed.
@@ -1394,7 +1531,7 @@ pub struct GenreSeed {
 ///             "thumbsDown": []
 ///         }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StationFeedback {
     /// A list of positive feedback submitted to a station.
@@ -1429,7 +1566,7 @@ pub struct StationFeedback {
 ///                 "isPositive": false
 ///             }]
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TrackFeedback {
     /// Unique identifier/handle referring to this feedback submission.
@@ -1450,7 +1587,7 @@ pub struct TrackFeedback {
 
 /// Convenience function to do a basic getStation call.
 pub async fn get_station(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_token: &str,
 ) -> Result<GetStationResponse, Error> {
     GetStation::from(&station_token)
@@ -1489,7 +1626,7 @@ impl RenameStation {
 }
 
 /// There's no known response data to this request.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RenameStationResponse {
     /// The fields of the renameStation response, if any, are unknown.
@@ -1499,7 +1636,7 @@ pub struct RenameStationResponse {
 
 /// Convenience function to do a basic renameStation call.
 pub async fn rename_station(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_token: &str,
     station_name: &str,
 ) -> Result<RenameStationResponse, Error> {
@@ -1546,7 +1683,7 @@ impl ShareStation {
 }
 
 /// There's no known response data to this request.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ShareStationResponse {
     /// The fields of the shareStation response, if any, are unknown.
@@ -1556,7 +1693,7 @@ pub struct ShareStationResponse {
 
 /// Convenience function to do a basic shareStation call.
 pub async fn share_station(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_id: &str,
     station_token: &str,
     emails: Vec<String>,
@@ -1591,7 +1728,7 @@ impl<TS: ToString> From<&TS> for TransformSharedStation {
 }
 
 /// There's no known response data to this request.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TransformSharedStationResponse {
     /// The fields of the transformSharedStation response, if any, are unknown.
@@ -1601,7 +1738,7 @@ pub struct TransformSharedStationResponse {
 
 /// Convenience function to do a basic transformSharedStation call.
 pub async fn transform_shared_station(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     station_token: &str,
 ) -> Result<TransformSharedStationResponse, Error> {
     TransformSharedStation::from(&station_token)
@@ -1618,6 +1755,113 @@ mod tests {
         music::search, music::ArtistMatch, tests::session_login, user::get_station_list, Partner,
     };
 
+    #[tokio::test]
+    async fn playlist_track_test() {
+        let partner = Partner::default();
+        let session = session_login(&partner)
+            .await
+            .expect("Failed initializing login session");
+        let station_token = get_station_list(&session)
+            .await
+            .expect("Failed getting station list")
+            .stations[0]
+            .station_token
+            .clone();
+
+        let track = GetPlaylist::from(&station_token)
+            .additional_audio_url(&AudioFormat::AacPlusAdts32.to_string())
+            .include_track_length(true)
+            .include_audio_token(true)
+            .response(&session)
+            .await
+            .expect("Failed getting playlist")
+            .items
+            .iter()
+            .find_map(|p| p.get_track())
+            .expect("Playlist has no tracks");
+        assert!(track.get_track_gain().is_some());
+        assert!(track.track_length.is_some_and(|l| l.as_secs() > 0));
+        assert!(track.audio_token.is_some());
+        assert_eq!(track.allow_feedback, Some(true));
+        assert_eq!(track.additional_audio_url.len(), 2);
+        assert!(track.additional_audio_url[0].ends_with("HTTP_128_MP3"));
+        assert!(track.additional_audio_url[1].ends_with("HTTP_32_AACPLUS_ADTS"));
+        for typed in [
+            "trackGain",
+            "albumArtUrl",
+            "additionalAudioUrl",
+            "trackLength",
+        ] {
+            assert!(!track.optional.contains_key(typed));
+        }
+
+        // A single requested format is sent as a string, and the track gain
+        // may be a number
+        let track: PlaylistTrack = serde_json::from_value(serde_json::json!({
+            "trackToken": "tt",
+            "musicId": "S1",
+            "stationId": "st",
+            "audioUrlMap": {
+                "highQuality": {"bitrate": "64", "encoding": "aacplus", "audioUrl": "h", "protocol": "http"},
+                "mediumQuality": {"bitrate": "64", "encoding": "aacplus", "audioUrl": "m", "protocol": "http"},
+                "lowQuality": {"bitrate": "32", "encoding": "aacplus", "audioUrl": "l", "protocol": "http"}
+            },
+            "artistName": "Artist",
+            "albumName": "Album",
+            "songName": "Song",
+            "songRating": 0,
+            "trackGain": -4.5,
+            "additionalAudioUrl": "http://example.com/a.mp3",
+            "trackLength": "181"
+        }))
+        .expect("Failed deserializing playlist track");
+        assert_eq!(track.get_track_gain(), Some(-4.5));
+        assert_eq!(track.additional_audio_url, vec!["http://example.com/a.mp3"]);
+        assert_eq!(
+            track.track_length,
+            Some(std::time::Duration::from_secs(181))
+        );
+        assert_eq!(track.album_art_url, None);
+
+        let json = serde_json::to_value(&track).expect("Failed serializing playlist track");
+        assert_eq!(json["additionalAudioUrl"], "http://example.com/a.mp3");
+        assert_eq!(json["trackGain"], -4.5);
+        assert_eq!(json["trackLength"], 181);
+        let mut tracks = track.clone();
+        tracks
+            .additional_audio_url
+            .push("http://example.com/b.aac".to_string());
+        let json = serde_json::to_value(&tracks).expect("Failed serializing playlist track");
+        assert_eq!(
+            json["additionalAudioUrl"],
+            serde_json::json!(["http://example.com/a.mp3", "http://example.com/b.aac"])
+        );
+        tracks.additional_audio_url.clear();
+        let json = serde_json::to_value(&tracks).expect("Failed serializing playlist track");
+        assert!(json.get("additionalAudioUrl").is_none());
+        assert_eq!(
+            serde_json::from_value::<PlaylistTrack>(
+                serde_json::to_value(&track).expect("Failed serializing playlist track")
+            )
+            .expect("Failed deserializing serialized playlist track"),
+            track
+        );
+
+        // The track gain is serialized back as it was sent, and one that
+        // can't be parsed is treated as missing
+        for (gain, parsed) in [("10.090", Some(10.09)), ("0.0", Some(0.0)), ("", None)] {
+            let mut json = serde_json::to_value(&track).expect("Failed serializing playlist track");
+            json["trackGain"] = serde_json::Value::from(gain);
+            let track: PlaylistTrack = serde_json::from_value(json.clone())
+                .expect("Failed deserializing playlist track gain");
+            assert_eq!(track.get_track_gain(), parsed);
+            assert_eq!(
+                serde_json::to_value(&track).expect("Failed serializing playlist track"),
+                json
+            );
+        }
+    }
+
     // TODO: share_station, transform_shared_station,
     #[tokio::test]
     async fn station_ops_test() {
@@ -1625,15 +1869,15 @@ mod tests {
         // by a previous, failed test execution, look for stations named either
         // "INXS Radio" or "XSNI Radio"
         let partner = Partner::default();
-        let mut session = session_login(&partner)
+        let session = session_login(&partner)
             .await
             .expect("Failed initializing login session");
 
-        let artist_search = search(&mut session, "INXS")
+        let artist_search = search(&session, "INXS")
             .await
             .expect("Failed completing artist search request");
 
-        let additional_artist_search = search(&mut session, "Panic! At the Disco")
+        let additional_artist_search = search(&session, "Panic! At the Disco")
             .await
             .expect("Failed completing artist search request");
 
@@ -1643,12 +1887,12 @@ mod tests {
             .filter(|am| am.score == 100)
             .next()
         {
-            let created_station = create_station_from_music_token(&mut session, &music_token)
+            let created_station = create_station_from_music_token(&session, music_token)
                 .await
                 .expect("Failed creating station from search result");
 
             let _renamed_station =
-                rename_station(&mut session, &created_station.station_token, "XSNI Radio")
+                rename_station(&session, &created_station.station_token, "XSNI Radio")
                     .await
                     .expect("Failed renaming station");
 
@@ -1658,17 +1902,16 @@ mod tests {
                 .filter(|am| am.score == 100)
                 .next()
             {
-                let added_music =
-                    add_music(&mut session, &created_station.station_token, music_token)
-                        .await
-                        .expect("Failed adding music to station");
+                let added_music = add_music(&session, &created_station.station_token, music_token)
+                    .await
+                    .expect("Failed adding music to station");
 
-                let _del_music = delete_music(&mut session, &added_music.seed_id)
+                let _del_music = delete_music(&session, &added_music.seed_id)
                     .await
                     .expect("Failed deleting music from station");
             }
 
-            let _del_station = delete_station(&mut session, &created_station.station_token)
+            let _del_station = delete_station(&session, &created_station.station_token)
                 .await
                 .expect("Failed deleting station");
         }
@@ -1681,10 +1924,10 @@ mod tests {
         let partner = Partner::default();
         let mut session = session_login(&partner).await.expect("Failed initializing login session");
 
-        let genre_stations = get_genre_stations(&mut session).await
+        let genre_stations = get_genre_stations(&session).await
             .expect("Failed getting genre stations");
 
-        let genre_stations_checksum = get_genre_stations_checksum(&mut session).await
+        let genre_stations_checksum = get_genre_stations_checksum(&session).await
             .expect("Failed getting genre stations checksum");
     }
     */
@@ -1692,11 +1935,11 @@ mod tests {
     #[tokio::test]
     async fn station_feedback_test() {
         let partner = Partner::default();
-        let mut session = session_login(&partner)
+        let session = session_login(&partner)
             .await
             .expect("Failed initializing login session");
 
-        for station in get_station_list(&mut session)
+        for station in get_station_list(&session)
             .await
             .expect("Failed getting station list to look up a track to bookmark")
             .stations
@@ -1706,7 +1949,7 @@ mod tests {
             // ratings during this test.  This also exercises get_station.
             let station = GetStation::from(&station.station_token)
                 .include_extended_attributes(true)
-                .response(&mut session)
+                .response(&session)
                 .await
                 .expect("Failed getting station attributes");
 
@@ -1726,7 +1969,7 @@ mod tests {
                     .map(|tf| tf.song_name.clone()),
             );
 
-            for track in get_playlist(&mut session, &station.station_token)
+            for track in get_playlist(&session, &station.station_token)
                 .await
                 .expect("Failed completing request for playlist")
                 .items
@@ -1738,29 +1981,21 @@ mod tests {
                 }
 
                 // Thumbs-up track
-                let feedback = add_feedback(
-                    &mut session,
-                    &station.station_token,
-                    &track.track_token,
-                    true,
-                )
-                .await
-                .expect("Failed adding positive feedback to track");
+                let feedback =
+                    add_feedback(&session, &station.station_token, &track.track_token, true)
+                        .await
+                        .expect("Failed adding positive feedback to track");
                 // And delete
-                let _del_feedback = delete_feedback(&mut session, &feedback.feedback_id)
+                let _del_feedback = delete_feedback(&session, &feedback.feedback_id)
                     .await
                     .expect("Failed deleting positive feedback from track");
                 // Thumbs-down track
-                let feedback = add_feedback(
-                    &mut session,
-                    &station.station_token,
-                    &track.track_token,
-                    false,
-                )
-                .await
-                .expect("Failed adding negative feedback to track");
+                let feedback =
+                    add_feedback(&session, &station.station_token, &track.track_token, false)
+                        .await
+                        .expect("Failed adding negative feedback to track");
                 // And delete
-                let _del_feedback = delete_feedback(&mut session, &feedback.feedback_id)
+                let _del_feedback = delete_feedback(&session, &feedback.feedback_id)
                     .await
                     .expect("Failed deleting negative feedback from track");
 
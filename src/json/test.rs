@@ -26,7 +26,7 @@ impl CheckLicensing {
 ///
 /// | Name    | Type  |   Description |
 /// | isAllowed |   bool     | |
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CheckLicensingResponse {
     /// Whether the Pandora service is available to the requesting client.
@@ -34,9 +34,7 @@ pub struct CheckLicensingResponse {
 }
 
 /// Convenience function to check geographic licensing restrictions.
-pub async fn check_licensing(
-    session: &mut PandoraSession,
-) -> Result<CheckLicensingResponse, Error> {
+pub async fn check_licensing(session: &PandoraSession) -> Result<CheckLicensingResponse, Error> {
     CheckLicensing::default().response(session).await
 }
 
@@ -60,11 +58,11 @@ mod tests {
         */
 
         let partner = Partner::default();
-        let mut session = session_login(&partner)
+        let session = session_login(&partner)
             .await
             .expect("Failed initializing login session");
 
-        let check_licensing_response = check_licensing(&mut session)
+        let check_licensing_response = check_licensing(&session)
             .await
             .expect("Error making test.checkLicensing request");
         log::debug!("test.checkLicensing() => {:?}", check_licensing_response);
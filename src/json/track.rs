@@ -55,7 +55,7 @@ impl<TS: ToString> From<&TS> for ExplainTrack {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ExplainTrackResponse {
     /// A list of explanations for why the track was chosen.
@@ -63,7 +63,7 @@ pub struct ExplainTrackResponse {
 }
 
 /// Describes traits of a track that would explain why it's recommended.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Explanation {
     /// Text description of the audio trait for which the track was chosen.
@@ -74,7 +74,7 @@ pub struct Explanation {
 
 /// Convenience function to do a basic explainTrack call.
 pub async fn explain_track(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     track_token: &str,
 ) -> Result<ExplainTrackResponse, Error> {
     ExplainTrack::from(&track_token).response(session).await
@@ -102,17 +102,17 @@ mod tests {
         */
 
         let partner = Partner::default();
-        let mut session = session_login(&partner)
+        let session = session_login(&partner)
             .await
             .expect("Failed initializing login session");
 
-        if let Some(station) = get_station_list(&mut session)
+        if let Some(station) = get_station_list(&session)
             .await
             .expect("Failed getting station list to look up a track to bookmark")
             .stations
             .first()
         {
-            if let Some(track) = get_playlist(&mut session, &station.station_token)
+            if let Some(track) = get_playlist(&session, &station.station_token)
                 .await
                 .expect("Failed completing request for playlist")
                 .items
@@ -120,7 +120,7 @@ mod tests {
                 .flat_map(|p| p.get_track())
                 .next()
             {
-                let explain_track = explain_track(&mut session, &track.track_token)
+                let explain_track = explain_track(&session, &track.track_token)
                     .await
                     .expect("Failed submitting track explanation request");
                 log::debug!("Track explanation: {:?}", explain_track);
@@ -0,0 +1,216 @@
+/*!
+Pluggable HTTP transport used by [`PandoraSession`](crate::json::PandoraSession)
+to submit API requests.
+
+A transport accepts the fully-built request url and (possibly encrypted) body,
+and returns the HTTP status and body of the response.  The default transport
+is backed by `reqwest`, while [`MemoryTransport`] answers requests from memory
+so that API calls can be exercised without network access.
+*/
+// SPDX-License-Identifier: MIT
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::errors::Error;
+
+/// The raw response to a request submitted through a [`Transport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportResponse {
+    /// The HTTP status code of the response.
+    pub status: u16,
+    /// The body of the response.
+    pub body: String,
+}
+
+impl TransportResponse {
+    /// Create a new TransportResponse with the given status and body.
+    pub fn new(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            body: body.to_string(),
+        }
+    }
+
+    /// Create a new TransportResponse with the given body and a `200 OK` status.
+    pub fn ok(body: &str) -> Self {
+        Self::new(200, body)
+    }
+
+    /// Whether the status code indicates a successful (2xx) response.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Trait for submitting requests to the Pandora JSON API endpoint.
+#[async_trait::async_trait]
+pub trait Transport: std::fmt::Debug + Send + Sync {
+    /// Submit `body` as an HTTP POST to `url`, and return the status and
+    /// body of the response.
+    async fn post(&self, url: url::Url, body: String) -> Result<TransportResponse, Error>;
+}
+
+/// The default transport, submitting requests using a `reqwest::Client`.
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    /// Create a new ReqwestTransport that submits requests using `client`.
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+
+    /// Get a reference to the http client.
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.client
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for ReqwestTransport {
+    async fn post(&self, url: url::Url, body: String) -> Result<TransportResponse, Error> {
+        let response = self.client.post(url).body(body).send().await?;
+        let status = response.status().as_u16();
+        let body = response.text().await?;
+        Ok(TransportResponse { status, body })
+    }
+}
+
+/// A closure used by [`MemoryTransport`] to answer requests.
+pub type MemoryHandler = dyn Fn(&url::Url, &str) -> Result<TransportResponse, Error> + Send + Sync;
+
+/// A transport that answers requests from memory, without network access.
+///
+/// Responses are either produced by a handler closure, or taken in order from
+/// a queue of canned responses.  Every submitted request is recorded, and can
+/// be inspected with [`MemoryTransport::requests()`].
+pub struct MemoryTransport {
+    handler: Option<Box<MemoryHandler>>,
+    responses: Mutex<VecDeque<TransportResponse>>,
+    requests: Mutex<Vec<(url::Url, String)>>,
+}
+
+impl MemoryTransport {
+    /// Create a new MemoryTransport that answers each request by calling
+    /// `handler` with the request url and body.
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(&url::Url, &str) -> Result<TransportResponse, Error> + Send + Sync + 'static,
+    {
+        Self {
+            handler: Some(Box::new(handler)),
+            responses: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Create a new MemoryTransport that answers requests with `responses`,
+    /// in order.  Once the responses are exhausted, requests fail with
+    /// `Error::TransportError`.
+    pub fn from_responses<I: IntoIterator<Item = TransportResponse>>(responses: I) -> Self {
+        Self {
+            handler: None,
+            responses: Mutex::new(responses.into_iter().collect()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Add a response to the end of the queue of canned responses.
+    pub fn push_response(&self, response: TransportResponse) {
+        self.responses
+            .lock()
+            .expect("MemoryTransport response queue lock poisoned")
+            .push_back(response);
+    }
+
+    /// Returns the url and body of every request submitted so far, in order.
+    pub fn requests(&self) -> Vec<(url::Url, String)> {
+        self.requests
+            .lock()
+            .expect("MemoryTransport request log lock poisoned")
+            .clone()
+    }
+}
+
+impl std::fmt::Debug for MemoryTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("MemoryTransport")
+            .field("handler", &self.handler.as_ref().map(|_| "Fn"))
+            .field("responses", &self.responses)
+            .field("requests", &self.requests)
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for MemoryTransport {
+    async fn post(&self, url: url::Url, body: String) -> Result<TransportResponse, Error> {
+        self.requests
+            .lock()
+            .expect("MemoryTransport request log lock poisoned")
+            .push((url.clone(), body.clone()));
+
+        if let Some(handler) = &self.handler {
+            return handler(&url, &body);
+        }
+        self.responses
+            .lock()
+            .expect("MemoryTransport response queue lock poisoned")
+            .pop_front()
+            .ok_or_else(|| Error::TransportError(String::from("No queued response available")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::json::errors::JsonErrorKind;
+    use crate::json::retry::RetryPolicy;
+    use crate::json::{test::check_licensing, Partner};
+
+    #[tokio::test]
+    async fn memory_transport_test() {
+        let transport = Arc::new(MemoryTransport::from_responses(vec![
+            TransportResponse::ok(r#"{"stat": "ok", "result": {"isAllowed": true}}"#),
+            TransportResponse::ok(r#"{"stat": "fail", "code": 12, "message": "Not here."}"#),
+            TransportResponse::new(503, "Service Unavailable"),
+        ]));
+        let partner = Partner::default();
+        let mut session = partner.init_session_with_transport(transport.clone());
+        session.set_retry_policy(RetryPolicy::never());
+
+        let licensing = check_licensing(&session)
+            .await
+            .expect("Failed checking licensing against canned response");
+        assert!(licensing.is_allowed);
+
+        match check_licensing(&session).await {
+            Err(Error::PandoraJsonRequestError(e)) => {
+                assert_eq!(e.kind(), JsonErrorKind::LicensingRestrictions)
+            }
+            res => panic!("Unexpected licensing check result: {:?}", res),
+        }
+
+        match check_licensing(&session).await {
+            Err(Error::HttpStatusError(503)) => (),
+            res => panic!("Unexpected licensing check result: {:?}", res),
+        }
+
+        match check_licensing(&session).await {
+            Err(Error::TransportError(_)) => (),
+            res => panic!("Unexpected licensing check result: {:?}", res),
+        }
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[0]
+            .0
+            .query_pairs()
+            .any(|(k, v)| k == "method" && v == "test.checkLicensing"));
+    }
+}
@@ -32,6 +32,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::errors::Error;
 use crate::json::{PandoraJsonApiRequest, PandoraSession, Timestamp};
+use crate::secret::Secret;
 
 /// Valid values for the gender is user account settings. The documentation
 /// suggests that the only valid values are "Male", "Female".
@@ -135,7 +136,7 @@ impl CanSubscribe {
 ///      }
 ///  }
 ///  ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CanSubscribeResponse {
     /// Whether it is valid for this account to subscribe to Pandora One.
@@ -145,7 +146,7 @@ pub struct CanSubscribeResponse {
 }
 
 /// Convenience function to do a basic canSubscribe call.
-pub async fn can_subscribe(session: &mut PandoraSession) -> Result<CanSubscribeResponse, Error> {
+pub async fn can_subscribe(session: &PandoraSession) -> Result<CanSubscribeResponse, Error> {
     CanSubscribe::new().response(session).await
 }
 
@@ -162,7 +163,10 @@ pub struct ChangeSettings {
     /// Current credentials must be provided with the request.
     pub current_username: String,
     /// Current credentials must be provided with the request.
-    pub current_password: String,
+    pub current_password: Secret,
+    /// New account password, if it's being changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_password: Option<Secret>,
     /// Optional parameters on the call
     #[serde(flatten)]
     pub optional: HashMap<String, serde_json::value::Value>,
@@ -174,7 +178,8 @@ impl ChangeSettings {
     pub fn new(current_username: &str, current_password: &str) -> Self {
         Self {
             current_username: current_username.to_string(),
-            current_password: current_password.to_string(),
+            current_password: Secret::from(current_password),
+            new_password: None,
             optional: HashMap::new(),
         }
     }
@@ -266,8 +271,9 @@ impl ChangeSettings {
     }
 
     /// New account password. (Chaining call)
-    pub fn new_password(self, value: &str) -> Self {
-        self.and_string_option("newPassword", value)
+    pub fn new_password(mut self, value: &str) -> Self {
+        self.new_password = Some(Secret::from(value));
+        self
     }
 
     /// Whether to auto-share on facebook. (Chaining call)
@@ -297,7 +303,7 @@ impl ChangeSettings {
 }
 
 /// There's no known response to data to this request.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChangeSettingsResponse {
     /// The fields of the changeSettings response are unknown.
@@ -309,7 +315,7 @@ pub struct ChangeSettingsResponse {
 /// is basically useless for actually changing settings, but is useful
 /// to return the current values for user account settings.
 pub async fn change_settings(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     username: &str,
     password: &str,
 ) -> Result<ChangeSettingsResponse, Error> {
@@ -344,7 +350,7 @@ pub struct CreateUser {
     /// Username to associate with the account.
     pub username: String,
     /// Password to set for the account.
-    pub password: String,
+    pub password: Secret,
     /// Account-holder gender, Male or Female.
     pub gender: UserGender,
     /// Account-holder birth year.
@@ -392,7 +398,7 @@ impl CreateUser {
     ) -> Self {
         Self {
             username: username.to_string(),
-            password: password.to_string(),
+            password: Secret::from(password),
             gender,
             birth_year,
             zip_code: zip_code.to_string(),
@@ -414,7 +420,7 @@ impl CreateUser {
 }
 
 /// There's no known response to data to this request.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateUserResponse {
     /// The fields of the createUser response are unknown.
@@ -424,7 +430,7 @@ pub struct CreateUserResponse {
 
 /// Convenience function to do a basic emailPassword call.
 pub async fn create_user(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     username: &str,
     password: &str,
     gender: UserGender,
@@ -452,6 +458,7 @@ pub struct DisconnectFacebookUnsupported {}
 /// | Name  |   Type  |   Description |
 /// | username  |   string  | |
 #[derive(Debug, Clone, Serialize, PandoraJsonRequest)]
+#[pandora_request(encrypted = true)]
 #[serde(rename_all = "camelCase")]
 pub struct EmailPassword {
     /// The e-mail password recovery information to the e-mail associated with
@@ -468,7 +475,7 @@ impl<TS: ToString> From<&TS> for EmailPassword {
 }
 
 /// There's no known response to data to this request.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EmailPasswordResponse {
     /// The fields of the emailPassword response are unknown.
@@ -478,7 +485,7 @@ pub struct EmailPasswordResponse {
 
 /// Convenience function to do a basic emailPassword call.
 pub async fn email_password(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     username: &str,
 ) -> Result<EmailPasswordResponse, Error> {
     EmailPassword::from(&username).response(session).await
@@ -554,7 +561,7 @@ impl GetBookmarks {
 ///
 ///  }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetBookmarksResponse {
     /// A list of bookmarked artists.
@@ -583,7 +590,7 @@ pub struct GetBookmarksResponse {
 ///                 }
 ///             }
 ///         ]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ArtistBookmark {
     /// Unique identifier (token) associated with this bookmark.
@@ -622,7 +629,7 @@ pub struct ArtistBookmark {
 ///                 "songName": "London Mozart Players, Christian Cannabich: Symphonies"
 ///             }
 ///         ]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SongBookmark {
     /// Unique identifier (token) associated with this bookmark.
@@ -646,7 +653,7 @@ pub struct SongBookmark {
 }
 
 /// Convenience function to do a basic getBookmarks call.
-pub async fn get_bookmarks(session: &mut PandoraSession) -> Result<GetBookmarksResponse, Error> {
+pub async fn get_bookmarks(session: &PandoraSession) -> Result<GetBookmarksResponse, Error> {
     GetBookmarks::new().response(session).await
 }
 
@@ -686,7 +693,7 @@ impl GetSettings {
 }
 
 /// See Settings for return values.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetSettingsResponse {
     /// The fields of the setQuickMix response are unknown.
@@ -695,7 +702,7 @@ pub struct GetSettingsResponse {
 }
 
 /// Convenience function to do a basic getSettings call.
-pub async fn get_settings(session: &mut PandoraSession) -> Result<GetSettingsResponse, Error> {
+pub async fn get_settings(session: &PandoraSession) -> Result<GetSettingsResponse, Error> {
     GetSettings::new()
         .include_facebook(false)
         .response(session)
@@ -705,6 +712,7 @@ pub async fn get_settings(session: &mut PandoraSession) -> Result<GetSettingsRes
 /// To check if the station list was modified by another client the checksum
 /// can be fetched. No parameters are required for this request.
 #[derive(Debug, Clone, Default, Serialize, PandoraJsonRequest)]
+#[pandora_request(encrypted = true)]
 #[serde(rename_all = "camelCase")]
 pub struct GetStationListChecksum {}
 
@@ -727,7 +735,7 @@ impl GetStationListChecksum {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetStationListChecksumResponse {
     /// Checksum for station list
@@ -865,7 +873,7 @@ impl GetStationList {
 ///         }
 ///      }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetStationListResponse {
     /// List of user-defined stations.
@@ -909,7 +917,7 @@ pub struct GetStationListResponse {
 ///                  "allowEditDescription":false
 ///               }
 ///            ],
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Station {
     /// Unique identifier (token) for this station. Currently stationId and
@@ -949,9 +957,7 @@ pub struct Station {
 }
 
 /// Convenience function to do a basic getStationList call.
-pub async fn get_station_list(
-    session: &mut PandoraSession,
-) -> Result<GetStationListResponse, Error> {
+pub async fn get_station_list(session: &PandoraSession) -> Result<GetStationListResponse, Error> {
     GetStationList::new()
         .include_station_art_url(false)
         .include_ad_attributes(false)
@@ -1000,7 +1006,7 @@ impl GetUsageInfo {
 ///     }
 /// }
 /// ```
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetUsageInfoResponse {
     /// Number of hours spent listening this month on this account.
@@ -1023,7 +1029,7 @@ pub struct GetUsageInfoResponse {
 }
 
 /// Convenience function to get account usage info.
-pub async fn get_usage_info(session: &mut PandoraSession) -> Result<GetUsageInfoResponse, Error> {
+pub async fn get_usage_info(session: &PandoraSession) -> Result<GetUsageInfoResponse, Error> {
     GetUsageInfo {}.response(session).await
 }
 
@@ -1072,6 +1078,7 @@ pub struct SetExplicitContentFilterUnsupported {}
 /// }
 /// ```
 #[derive(Debug, Clone, Default, Serialize, PandoraJsonRequest)]
+#[pandora_request(encrypted = true)]
 #[serde(rename_all = "camelCase")]
 pub struct SetQuickMix {
     /// The identifiers for stations that should be included in the quickmix.
@@ -1092,7 +1099,7 @@ impl SetQuickMix {
 }
 
 /// The response contains no data.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SetQuickMixResponse {
     /// The fields of the setQuickMix response are unknown.
@@ -1113,6 +1120,7 @@ pub struct SetQuickMixResponse {
 /// }
 /// ```
 #[derive(Debug, Clone, Serialize, PandoraJsonRequest)]
+#[pandora_request(encrypted = true)]
 #[serde(rename_all = "camelCase")]
 pub struct SleepSong {
     /// Temporarily ban the specified track from all stations for one month.
@@ -1128,7 +1136,7 @@ impl<TS: ToString> From<&TS> for SleepSong {
 }
 
 /// The response contains no data.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SleepSongResponse {
     /// The fields of the sleepSong response are unknown.
@@ -1142,6 +1150,7 @@ pub struct SleepSongResponse {
 /// | Name   | Type   | Description |
 /// | complimentarySponsor   | string | The ID of the sponsor providing the complimentary trial. |
 #[derive(Debug, Clone, Serialize, PandoraJsonRequest)]
+#[pandora_request(encrypted = true)]
 #[serde(rename_all = "camelCase")]
 pub struct StartComplimentaryTrial {
     /// The ID of the sponsor providing the complimentary trial.  There are no
@@ -1158,7 +1167,7 @@ impl<TS: ToString> From<&TS> for StartComplimentaryTrial {
 }
 
 /// The response contains no data.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StartComplimentaryTrialResponse {
     /// The fields of the startComplimentaryTrial response are unknown.
@@ -1190,7 +1199,7 @@ impl<TS: ToString> From<&TS> for ValidateUsername {
 /// | Name  |  Type  |  Description |
 /// | isValid | boolean  | |
 /// | isUnique |    boolean  | |
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ValidateUsernameResponse {
     /// Whether the username is valid (registered).
@@ -1201,7 +1210,7 @@ pub struct ValidateUsernameResponse {
 
 /// Convenience function to verify that a username is either valid or unique.
 pub async fn validate_username(
-    session: &mut PandoraSession,
+    session: &PandoraSession,
     username: &str,
 ) -> Result<ValidateUsernameResponse, Error> {
     ValidateUsername {
@@ -1215,33 +1224,78 @@ pub async fn validate_username(
 mod tests {
     use super::*;
     use crate::errors;
-    use crate::json::{errors::JsonErrorKind, tests::session_login, Partner};
+    use crate::json::{errors::JsonErrorKind, mock::MockPandora, tests::session_login, Partner};
 
     #[tokio::test]
     async fn user_test() {
         let partner = Partner::default();
-        let mut session = session_login(&partner)
+        let session = session_login(&partner)
             .await
             .expect("Failed initializing login session");
 
-        let _can_subscribe = can_subscribe(&mut session)
+        let _can_subscribe = can_subscribe(&session)
             .await
             .expect("Failed submitting subscription information request");
 
-        let _get_settings = get_settings(&mut session)
+        let _get_settings = get_settings(&session)
             .await
             .expect("Failed submitting settings info request");
 
-        let test_username_raw = include_str!("../../test_username.txt");
-        let test_username = test_username_raw.trim();
-        let test_password_raw = include_str!("../../test_password.txt");
-        let test_password = test_password_raw.trim();
+        let test_username = MockPandora::DEFAULT_USERNAME;
+        let test_password = MockPandora::DEFAULT_PASSWORD;
 
-        let _change_settings = change_settings(&mut session, &test_username, &test_password)
+        let _change_settings = change_settings(&session, test_username, test_password)
             .await
             .expect("Failed submitting settings change request");
     }
 
+    #[tokio::test]
+    async fn invalid_gender_test() {
+        let partner = Partner::default();
+        let session = session_login(&partner)
+            .await
+            .expect("Failed initializing login session");
+
+        let change_settings =
+            ChangeSettings::new(MockPandora::DEFAULT_USERNAME, MockPandora::DEFAULT_PASSWORD)
+                .and_string_option("gender", "Other")
+                .response(&session)
+                .await;
+        match change_settings {
+            Err(errors::Error::PandoraJsonRequestError(e)) => {
+                assert_eq!(e.kind(), JsonErrorKind::InvalidGender);
+                assert_eq!(e.code(), Some(1027));
+            }
+            res => panic!("Unexpected settings change result: {:?}", res),
+        }
+
+        // Creating a user can fail with either meaning of code 1027
+        let create_user = CreateUser::new(
+            "user@example.com",
+            "hunter2",
+            UserGender::Female,
+            1990,
+            "94612",
+            "US",
+        );
+        assert_eq!(
+            create_user.error_kind(1027, Some("Invalid gender.")),
+            JsonErrorKind::InvalidGender
+        );
+        assert_eq!(
+            create_user.error_kind(1027, Some("Invalid country code.")),
+            JsonErrorKind::InvalidCountryCode
+        );
+        assert_eq!(
+            create_user.error_kind(1027, None),
+            JsonErrorKind::InvalidCountryCode
+        );
+        assert_eq!(
+            create_user.error_kind(1024, Some("Invalid gender.")),
+            JsonErrorKind::ZipCodeInvalid
+        );
+    }
+
     /* This test might trigger e-mail-based account recovery, which we probably
      * don't want to do automatically as a test.
     #[tokio::test]
@@ -1249,7 +1303,7 @@ mod tests {
         let partner = Partner::default();
         let mut session = session_login(&partner).await.expect("Failed initializing login session");
 
-        let email_password = email_password(&mut session).await.expect("Failed submitting settings change request");
+        let email_password = email_password(&session).await.expect("Failed submitting settings change request");
     }
     */
 
@@ -1264,17 +1318,17 @@ mod tests {
         */
 
         let partner = Partner::default();
-        let mut session = partner.init_session();
+        let session = partner.init_session_with_transport(std::sync::Arc::new(MockPandora::new()));
         let partner_login = partner
-            .login(&mut session)
+            .login(&session)
             .await
             .expect("Failed completing partner login");
-        session.update_partner_tokens(&partner_login);
+        session
+            .update_partner_tokens(&partner_login)
+            .expect("Failed decrypting partner login sync time");
 
-        let test_username_raw = include_str!("../../test_username.txt");
-        let test_username = test_username_raw.trim();
-        let test_password_raw = include_str!("../../test_password.txt");
-        let test_password = test_password_raw.trim();
+        let test_username = MockPandora::DEFAULT_USERNAME;
+        let test_password = MockPandora::DEFAULT_PASSWORD;
 
         let test_gender = UserGender::Male;
         let test_birth = 1970u32;
@@ -1287,9 +1341,9 @@ mod tests {
         // Theory is that the above credentials are for an existing account,
         // so this should fail as a duplicate account.
         match create_user(
-            &mut session,
-            &test_username,
-            &test_password,
+            &session,
+            test_username,
+            test_password,
             test_gender,
             test_birth,
             test_zip,
@@ -15,6 +15,7 @@ and are clearly indicated in the API documentation.
 #![deny(missing_docs)]
 pub mod errors;
 pub mod json;
+pub mod secret;
 // TODO: add REST support
 // https://6xq.net/pandora-apidoc/rest/
 //pub mod rest;
@@ -0,0 +1,97 @@
+/*!
+A wrapper for credentials, encryption keys and auth tokens, so that they don't
+end up in logs or error messages.
+
+[`Secret`] values are redacted when formatted with `Debug` or `Display`, and
+their memory is zeroed when they're dropped.  The wrapped value is only
+available through [`Secret::expose_secret()`].  They serialize to and from
+the plain value, so that requests, responses and saved sessions keep their
+json form.
+
+``` rust
+use pandora_api::secret::Secret;
+
+let password = Secret::from("hunter2");
+assert_eq!(format!("{:?}", password), "[REDACTED]");
+assert_eq!(password.expose_secret(), "hunter2");
+```
+*/
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// The text printed in place of a secret value.
+const REDACTED: &str = "[REDACTED]";
+
+/// A secret string value: a password, encryption key or auth token.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Wrap `value` as a secret.
+    pub fn new<S: Into<String>>(value: S) -> Self {
+        Self(value.into())
+    }
+
+    /// Get the secret value.  Take care not to log it, or keep copies of it
+    /// for longer than necessary.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the secret value is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl std::fmt::Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_test() {
+        let secret = Secret::new("hunter2");
+        assert_eq!(format!("{:?}", secret), REDACTED);
+        assert_eq!(secret.to_string(), REDACTED);
+        assert_eq!(format!("{:?}", Some(secret.clone())), "Some([REDACTED])");
+        assert_eq!(secret.expose_secret(), "hunter2");
+
+        let json = serde_json::to_string(&secret).expect("Failed serializing secret");
+        assert_eq!(json, r#""hunter2""#);
+        let secret: Secret = serde_json::from_str(&json).expect("Failed deserializing secret");
+        assert_eq!(secret, Secret::from("hunter2"));
+    }
+}
@@ -1,61 +1,65 @@
-use pandora_api::json::{auth, test, user, Partner};
+use std::sync::Arc;
+
+use pandora_api::json::mock::{MockPandora, MockServer};
+use pandora_api::json::{auth, test, user, PandoraSession, Partner};
 
 #[tokio::test]
 async fn basic_session_test() {
+    let server = MockServer::start(Arc::new(MockPandora::new()))
+        .await
+        .expect("Failed starting mock Pandora server");
     let partner = Partner::default();
-    let mut session = partner.init_session();
+    let session = PandoraSession::new(None, &partner, &server.endpoint());
 
     // This call doesn't even require partner authentication
-    let licensing = test::check_licensing(&mut session)
+    let licensing = test::check_licensing(&session)
         .await
         .expect("Failed while checking geographic licensing restrictions");
     assert!(licensing.is_allowed);
 
     // Do partner authentication
     let _partner_login = partner
-        .login(&mut session)
+        .login(&session)
         .await
         .expect("Failed during partner auth API request");
 
-    let test_username_raw = include_str!("../test_username.txt");
-    let test_username = test_username_raw.trim();
-    let test_password_raw = include_str!("../test_password.txt");
-    let test_password = test_password_raw.trim();
+    let test_username = MockPandora::DEFAULT_USERNAME;
+    let test_password = MockPandora::DEFAULT_PASSWORD;
 
     // Check username is valid
-    let user_validation = user::validate_username(&mut session, test_username)
+    let user_validation = user::validate_username(&session, test_username)
         .await
         .expect("Failed while validating username");
     assert!(user_validation.is_valid);
 
     // Check that an non-email username is invalid
-    let user_invalidation = user::validate_username(&mut session, "VGhlcmUgb25jZSB")
+    let user_invalidation = user::validate_username(&session, "VGhlcmUgb25jZSB")
         .await
         .expect("Failed while validating username");
     assert!(!user_invalidation.is_valid);
 
     // Check that an almost-certainly unused email username is valid and unique
-    let user_invalidation = user::validate_username(&mut session, "VGhlcmUgb25jZSB@gmail.com")
+    let user_invalidation = user::validate_username(&session, "VGhlcmUgb25jZSB@gmail.com")
         .await
         .expect("Failed while validating username");
     assert_eq!(user_invalidation.is_unique, Some(true));
 
     // Test login
-    let _login_response = auth::user_login(&mut session, &test_username, &test_password)
+    let _login_response = auth::user_login(&session, test_username, test_password)
         .await
         .expect("Failed while logging user in");
 
     // Get user usage info
-    let _usage_info = user::get_usage_info(&mut session)
+    let _usage_info = user::get_usage_info(&session)
         .await
         .expect("Failed while getting account usage information");
 
     // Check user subscription status
-    let _can_subscribe = user::can_subscribe(&mut session)
+    let _can_subscribe = user::can_subscribe(&session)
         .await
         .expect("Failed while verifying account subscription status");
 
-    let _change_settings = user::change_settings(&mut session, &test_username, &test_password)
+    let _change_settings = user::change_settings(&session, test_username, test_password)
         .await
         .expect("Failed while changing user settings");
     // TODO
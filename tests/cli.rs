@@ -0,0 +1,116 @@
+use std::process::{Command, Output};
+use std::sync::Arc;
+
+use pandora_api::json::mock::{MockPandora, MockServer};
+
+fn pandora(endpoint: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pandora"))
+        .args(["--endpoint", endpoint])
+        .args(args)
+        .env("PANDORA_USERNAME", MockPandora::DEFAULT_USERNAME)
+        .env("PANDORA_PASSWORD", MockPandora::DEFAULT_PASSWORD)
+        .env_remove("PANDORA_SESSION")
+        .output()
+        .expect("Failed to run pandora")
+}
+
+fn stdout(output: Output) -> String {
+    assert!(
+        output.status.success(),
+        "pandora failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("Invalid utf8 in pandora output")
+}
+
+#[tokio::test]
+async fn cli_session_test() {
+    let mock = Arc::new(MockPandora::new());
+    let server = MockServer::start(mock.clone())
+        .await
+        .expect("Failed starting mock Pandora server");
+    let endpoint = server.endpoint();
+    let session_file =
+        std::env::temp_dir().join(format!("pandora-cli-test-{}", std::process::id()));
+    let session_path = session_file
+        .to_str()
+        .expect("Invalid temp path")
+        .to_string();
+
+    let expiring = mock.clone();
+    tokio::task::spawn_blocking(move || {
+        let login = stdout(pandora(&endpoint, &["--session", &session_path, "login"]));
+        assert!(login.contains(MockPandora::DEFAULT_USERNAME));
+
+        // The auth tokens are left out of json output
+        for (command, token_key) in [
+            ("partner-login", "partnerAuthToken"),
+            ("login", "userAuthToken"),
+        ] {
+            let output = stdout(pandora(&endpoint, &["--output", "json", command]));
+            let result: serde_json::Value =
+                serde_json::from_str(&output).expect("Login output wasn't json");
+            assert_eq!(result[token_key], "[REDACTED]", "{}", command);
+        }
+
+        // Json output is the untyped API result
+        let stations = stdout(pandora(
+            &endpoint,
+            &["--session", &session_path, "--output", "json", "stations"],
+        ));
+        let stations: serde_json::Value =
+            serde_json::from_str(&stations).expect("Station list output wasn't json");
+        let station_token = stations["stations"][0]["stationToken"]
+            .as_str()
+            .expect("Missing station token")
+            .to_string();
+
+        let playlist = stdout(pandora(
+            &endpoint,
+            &["--session", &session_path, "playlist", &station_token],
+        ));
+        assert!(playlist.starts_with("TRACK TOKEN"));
+        assert!(playlist.lines().count() > 1);
+
+        // Expired saved tokens are replaced by logging in again
+        expiring.expire_tokens();
+        let usage = stdout(pandora(&endpoint, &["--session", &session_path, "usage"]));
+        assert!(usage.starts_with("FIELD"));
+        stdout(pandora(&endpoint, &["--session", &session_path, "usage"]));
+
+        // Or, without credentials, by running the login command again
+        expiring.expire_tokens();
+        let output = Command::new(env!("CARGO_BIN_EXE_pandora"))
+            .args(["--endpoint", &endpoint, "--session", &session_path, "usage"])
+            .env_remove("PANDORA_USERNAME")
+            .env_remove("PANDORA_PASSWORD")
+            .env_remove("PANDORA_SESSION")
+            .output()
+            .expect("Failed to run pandora");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("pandora login"));
+
+        // Without saved tokens or credentials, commands fail cleanly
+        let output = Command::new(env!("CARGO_BIN_EXE_pandora"))
+            .args(["--endpoint", &endpoint, "usage"])
+            .env_remove("PANDORA_USERNAME")
+            .env_remove("PANDORA_PASSWORD")
+            .env_remove("PANDORA_SESSION")
+            .output()
+            .expect("Failed to run pandora");
+        assert!(!output.status.success());
+        std::fs::remove_file(&session_path).expect("Failed removing session file");
+    })
+    .await
+    .expect("CLI test panicked");
+
+    // The saved session was reused rather than logging in again, except
+    // after its tokens expired, and the new tokens were saved in turn
+    let calls = mock.method_calls();
+    assert_eq!(
+        calls.iter().filter(|m| *m == "auth.userLogin").count(),
+        3,
+        "{:?}",
+        calls
+    );
+}